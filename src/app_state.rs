@@ -0,0 +1,1512 @@
+// Shared application state
+// Settings, audio, the metronome and derived data live here so callbacks read
+// from one place instead of round-tripping values through Slint properties
+
+use crate::audio::{self, NotePlayer, Waveform};
+use crate::chord_sheet::SheetLine;
+use crate::drill::{DrillAnswer, NoteDrill};
+use crate::fret_click::{self, ClickMode, DoubleClickAction, DoubleClickDetector};
+use crate::interval_quiz::{IntervalAnswer, IntervalQuiz};
+use crate::metronome::{Metronome, TapTempo};
+use crate::midi::Recorder;
+use crate::session::PracticeTimer;
+use crate::tuner::Tuner;
+use crate::music_theory::{self, CentsOffsets, Chord, ChordQuality, HarmonyInterval, Instrument, Key, MarkerKind, MarkerScheme, Note, Scale, Tuning, TuningPreset};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// Frets shown past the nut; each string also gets an open-string cell (fret 0)
+// Defaulting to 12 keeps component count manageable: 6 strings × 13 cells = 78 components
+pub const INITIAL_FRET_COUNT: u8 = 12;
+pub const MIN_FRET_COUNT: u8 = 12;
+pub const MAX_FRET_COUNT: u8 = 27;
+
+// Highest fret a capo can be placed at
+pub const MAX_CAPO: u8 = 12;
+
+// Fretboard zoom: cell sizes and fonts are multiplied by it, in steps of a quarter
+pub const MIN_ZOOM: f32 = 0.75;
+pub const MAX_ZOOM: f32 = 2.0;
+pub const ZOOM_STEP: f32 = 0.25;
+
+// Panes of the four-pane layout, indexed like PaneId in main.rs. Only the top-left
+// one is drawn today (see STACK_OVERFLOW_FIX.md); the others keep what they held.
+pub const PANE_COUNT: usize = 4;
+pub const MAIN_PANE: usize = 0;
+
+// Component tags (kind_to_tag in main.rs) of the panes that keep settings of their own
+const VIDEO_PANEL_KIND: i32 = 3;
+const FRETBOARD_KIND: i32 = 4;
+
+pub type SharedState = Rc<RefCell<AppState>>;
+
+// What one pane holds: its component and that component's own settings, e.g. a
+// Fretboard's key, scale and tuning or a Video Panel's URL. Null = nothing kept.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaneState {
+    pub kind: i32,
+    #[serde(default)]
+    pub settings: serde_json::Value,
+}
+
+impl PaneState {
+    // The drawn pane's state, read from the live settings its component uses
+    fn capture(settings: &Settings) -> PaneState {
+        let component = match settings.main_kind {
+            VIDEO_PANEL_KIND => serde_json::json!({ "video_url": settings.video_url }),
+            FRETBOARD_KIND => serde_json::json!({
+                "key": settings.key.to_int(),
+                "scale": settings.scale.to_int(),
+                "tuning": settings.tuning.to_int(),
+            }),
+            _ => serde_json::Value::Null,
+        };
+        PaneState { kind: settings.main_kind, settings: component }
+    }
+
+    // Make this the drawn pane. The video goes with its pane; key, scale and tuning
+    // are shared with the other components, so a pane without them leaves them be.
+    fn apply(&self, settings: &mut Settings) {
+        let int = |name: &str| self.settings.get(name).and_then(|value| value.as_i64()).map(|value| value as i32);
+        settings.main_kind = self.kind;
+        settings.video_url = self.settings.get("video_url").and_then(|url| url.as_str()).unwrap_or_default().to_string();
+        if let Some(key) = int("key") {
+            settings.key = Key::from_int(key);
+        }
+        if let Some(scale) = int("scale") {
+            settings.scale = Scale::from_int(scale);
+            // Scales with fewer notes have fewer positions
+            if settings.scale_position >= settings.scale.intervals().len() as i32 {
+                settings.scale_position = -1;
+            }
+        }
+        if let Some(tuning) = int("tuning") {
+            settings.tuning = TuningPreset::from_int(tuning);
+        }
+    }
+}
+
+// Window colors; the UI picks its palette from the tag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn from_int(value: i32) -> Theme {
+        match value {
+            1 => Theme::Light,
+            _ => Theme::Dark,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            Theme::Dark => 0,
+            Theme::Light => 1,
+        }
+    }
+
+    pub fn toggled(self) -> Theme {
+        match self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::Dark,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub main_kind: i32,
+    pub layout_locked: bool,
+    pub key: Key,
+    pub scale: Scale,
+    pub instrument: Instrument,
+    pub tuning: TuningPreset,
+    // Slides the highlighted pattern along the neck (movable root)
+    pub root_offset_frets: i32,
+    pub tension_detune: bool,
+    // Play scales with each note running into the next instead of a gap between them
+    pub legato: bool,
+    pub double_click_action: DoubleClickAction,
+    pub click_mode: ClickMode,
+    pub note_cents: CentsOffsets,
+    pub marker_scheme: MarkerScheme,
+    pub waveform: Waveform,
+    // String kept fully visible while the others are dimmed; -1 = no focus
+    pub focused_string: i32,
+    // Practice position (box) the scale highlighting is limited to; -1 = whole neck
+    pub scale_position: i32,
+    // Highlight this chord, built on the key, instead of the scale; None = scale
+    pub chord_highlight: Option<ChordQuality>,
+    // Highlight every position of this one note instead of the scale or chord; None = off
+    pub find_note: Option<Key>,
+    // Play this interval above a clicked fret as well; None = single notes
+    pub harmony: Option<HarmonyInterval>,
+    // Label frets with scale degrees (1, b3, 5...) instead of note names
+    pub show_degrees: bool,
+    // Color highlighted frets by octave instead of one accent color
+    pub octave_colors: bool,
+    pub fret_count: u8,
+    // Only draw these frets, lowest and highest (inclusive); None = the open string
+    // through fret_count
+    pub fret_window: Option<(u8, u8)>,
+    // Master playback gain for notes, chords and metronome clicks, 0.0-1.0
+    pub volume: f32,
+    // Length of a clicked note
+    pub note_duration_ms: u64,
+    // Delay between queueing a metronome click and hearing it; the beat flash waits this long
+    pub output_latency_ms: u32,
+    // Pitch of A4 in Hz; every played note is tuned relative to it
+    pub reference_a4: f32,
+    // Mirror the rendered board for left-handed players (view only)
+    pub left_handed: bool,
+    // Fret the capo sits at; 0 = no capo
+    pub capo: u8,
+    // Fretboard scale factor, MIN_ZOOM-MAX_ZOOM (view only)
+    pub zoom: f32,
+    // Lesson video shown in the Video Panel: a web URL or a local file path
+    pub video_url: String,
+    // What the other panes hold. The drawn pane's entry is left empty: its state is
+    // main_kind and the live fields above, and pane_states() fills it in from them.
+    pub panes: [PaneState; PANE_COUNT],
+    // Window position and size when the app last closed; None = let the OS decide
+    pub window: Option<PixelRect>,
+    pub theme: Theme,
+    // Longest run of right answers in the note drill, kept across sessions
+    pub drill_best_streak: u32,
+}
+
+impl Settings {
+    // Open strings of the selected instrument in the selected tuning
+    pub fn current_tuning(&self) -> Tuning {
+        self.tuning.tuning_for(self.instrument)
+    }
+
+    // Frets drawn on the board: the whole neck, or the window kept on it
+    pub fn visible_frets(&self) -> std::ops::RangeInclusive<u8> {
+        let last = clamp_fret_count(self.fret_count as i32);
+        match self.fret_window {
+            Some((low, high)) => {
+                let low = low.min(last);
+                low..=high.clamp(low, last)
+            }
+            None => 0..=last,
+        }
+    }
+
+    // The chord the board highlights, if it's in chord mode
+    pub fn highlighted_chord(&self) -> Option<Chord> {
+        self.chord_highlight.map(|quality| Chord::new(self.key, quality))
+    }
+
+    // Every pane's state, the drawn pane's as it stands now
+    pub fn pane_states(&self) -> [PaneState; PANE_COUNT] {
+        let mut panes = self.panes.clone();
+        panes[MAIN_PANE] = PaneState::capture(self);
+        panes
+    }
+
+    // Replace every pane's state; the drawn pane switches to what its entry holds
+    pub fn set_pane_states(&mut self, mut panes: [PaneState; PANE_COUNT]) {
+        std::mem::take(&mut panes[MAIN_PANE]).apply(self);
+        self.panes = panes;
+    }
+
+    // Exchange what two panes hold, component and state together
+    pub fn swap_panes(&mut self, a: usize, b: usize) {
+        let mut panes = self.pane_states();
+        panes.swap(a, b);
+        self.set_pane_states(panes);
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            main_kind: 0,
+            layout_locked: false,
+            key: Key::C,
+            scale: Scale::Major,
+            instrument: Instrument::Guitar6,
+            tuning: TuningPreset::Standard,
+            root_offset_frets: 0,
+            tension_detune: false,
+            legato: false,
+            double_click_action: DoubleClickAction::Retrigger,
+            click_mode: ClickMode::Click,
+            note_cents: CentsOffsets::default(),
+            marker_scheme: MarkerScheme::Standard,
+            waveform: Waveform::Sine,
+            focused_string: -1,
+            scale_position: -1,
+            chord_highlight: None,
+            find_note: None,
+            harmony: None,
+            show_degrees: false,
+            octave_colors: false,
+            fret_count: INITIAL_FRET_COUNT,
+            fret_window: None,
+            volume: audio::DEFAULT_VOLUME,
+            note_duration_ms: audio::DEFAULT_NOTE_DURATION_MS,
+            output_latency_ms: 0,
+            reference_a4: music_theory::DEFAULT_REFERENCE_A4,
+            left_handed: false,
+            capo: 0,
+            zoom: 1.0,
+            video_url: String::new(),
+            panes: Default::default(),
+            window: None,
+            theme: Theme::Dark,
+            drill_best_streak: 0,
+        }
+    }
+}
+
+// What a click on the fretboard sounds: the fretted note, then the harmony note
+// when double stops are on
+#[derive(Clone, Debug, PartialEq)]
+pub struct FretSound {
+    pub note: Note,
+    pub frequencies: Vec<f32>,
+    pub duration: Duration,
+    // A double click added the note to the note set or took it out
+    pub note_set_changed: bool,
+    // A double click bends the fretted note up to this pitch over `duration`
+    pub bend_to: Option<f32>,
+}
+
+impl FretSound {
+    pub fn play(&self, player: &mut dyn NotePlayer) {
+        if let Some(to_hz) = self.bend_to {
+            player.play_bend(self.frequencies[0], to_hz, self.duration.as_millis() as u64);
+            return;
+        }
+        match self.frequencies.as_slice() {
+            [frequency] => player.play_note_for(*frequency, self.duration),
+            frequencies => player.play_notes_for(frequencies, self.duration),
+        }
+    }
+
+    // Sound until the player's stop_note, for a fret held down in hold mode
+    pub fn hold(&self, player: &mut dyn NotePlayer) {
+        match self.frequencies.as_slice() {
+            [frequency] => player.start_note(*frequency),
+            frequencies => player.start_notes(frequencies),
+        }
+    }
+}
+
+// One fret cell as shown on the board
+#[derive(Clone, Debug, PartialEq)]
+pub struct FretCell {
+    pub string: u8,
+    pub fret: u8,
+    pub note: Note, // Sounding pitch, capo included; fixed until the tuning, capo or neck changes
+    pub note_name: String,
+    pub octave: i32, // Scientific pitch octave of the sounding note (E2 = 2)
+    pub frequency: f32, // Hz at the reference pitch, to one decimal
+    pub is_in_scale: bool,
+    pub is_root: bool,
+    pub degree: Option<&'static str>, // Scale degree, for in-scale cells
+    pub marker: MarkerKind, // Inlay under this fret
+    pub dimmed: bool,    // Another string has focus
+}
+
+// The settings the board is derived from; the cache is rebuilt when these change
+#[derive(Clone, Debug, PartialEq)]
+struct BoardInputs {
+    key: Key,
+    scale: Scale,
+    instrument: Instrument,
+    tuning: TuningPreset,
+    root_offset_frets: i32,
+    marker_scheme: MarkerScheme,
+    focused_string: i32,
+    scale_position: i32,
+    chord_highlight: Option<ChordQuality>,
+    find_note: Option<Key>,
+    fret_count: u8,
+    fret_window: Option<(u8, u8)>,
+    capo: u8,
+    reference_a4: f32,
+}
+
+impl BoardInputs {
+    fn from_settings(settings: &Settings) -> Self {
+        BoardInputs {
+            key: settings.key,
+            scale: settings.scale,
+            instrument: settings.instrument,
+            tuning: settings.tuning,
+            root_offset_frets: settings.root_offset_frets,
+            marker_scheme: settings.marker_scheme.clone(),
+            focused_string: settings.focused_string,
+            scale_position: settings.scale_position,
+            chord_highlight: settings.chord_highlight,
+            find_note: settings.find_note,
+            fret_count: settings.fret_count,
+            fret_window: settings.fret_window,
+            capo: settings.capo,
+            reference_a4: settings.reference_a4,
+        }
+    }
+
+    // Whether the cells' notes are still right and only their marking needs redoing
+    fn same_notes(&self, other: &BoardInputs) -> bool {
+        self.instrument == other.instrument
+            && self.tuning == other.tuning
+            && self.fret_count == other.fret_count
+            && self.fret_window == other.fret_window
+            && self.capo == other.capo
+            && self.reference_a4 == other.reference_a4
+    }
+}
+
+// The settings the string labels and the focus and position pickers are derived from
+#[derive(Clone, Debug, PartialEq)]
+struct PickerInputs {
+    key: Key,
+    scale: Scale,
+    instrument: Instrument,
+    tuning: TuningPreset,
+    left_handed: bool,
+}
+
+impl PickerInputs {
+    fn from_settings(settings: &Settings) -> Self {
+        PickerInputs {
+            key: settings.key,
+            scale: settings.scale,
+            instrument: settings.instrument,
+            tuning: settings.tuning,
+            left_handed: settings.left_handed,
+        }
+    }
+}
+
+pub struct AppState {
+    pub settings: Settings,
+    // The sound card, or a silent player when audio is off or no device exists
+    pub audio: Box<dyn NotePlayer>,
+    // The Scales pane's drone on the key's tonic; it follows key changes
+    pub drone: bool,
+    // The scale run started from the Scales panel, so playing it again restarts it
+    pub scale_playback: Option<audio::Playback>,
+    pub metronome: Metronome,
+    pub tap_tempo: TapTempo,
+    pub tuner: Tuner,
+    pub practice: PracticeTimer,
+    // Pane that keyboard shortcuts place components into (pane-id in main.slint)
+    pub focused_pane: i32,
+    // Custom note set built by double-clicking frets
+    pub note_set: Vec<Note>,
+    pub double_click: DoubleClickDetector,
+    // Chord sheet as loaded, and how far it is currently transposed (semitones)
+    pub chord_sheet: Vec<SheetLine>,
+    pub sheet_transpose: i32,
+    pub drill: NoteDrill,
+    pub interval_quiz: IntervalQuiz,
+    // Fret presses captured for MIDI export
+    pub recorder: Recorder,
+    board_cache: Option<(BoardInputs, Vec<Vec<FretCell>>)>,
+    picker_inputs: Option<PickerInputs>,
+}
+
+impl AppState {
+    pub fn new(audio: Box<dyn NotePlayer>) -> Self {
+        AppState {
+            settings: Settings::default(),
+            audio,
+            drone: false,
+            scale_playback: None,
+            metronome: Metronome::default(),
+            tap_tempo: TapTempo::new(),
+            tuner: Tuner::new(),
+            practice: PracticeTimer::new(),
+            focused_pane: 0,
+            note_set: Vec::new(),
+            double_click: DoubleClickDetector::default(),
+            chord_sheet: Vec::new(),
+            sheet_transpose: 0,
+            drill: NoteDrill::default(),
+            interval_quiz: IntervalQuiz::default(),
+            recorder: Recorder::default(),
+            board_cache: None,
+            picker_inputs: None,
+        }
+    }
+
+    pub fn shared(self) -> SharedState {
+        Rc::new(RefCell::new(self))
+    }
+
+    // Cells for every string (low to high), regenerated only when the settings they
+    // depend on have changed. A key, scale or highlight change re-marks the cached
+    // cells in place; only a new tuning, capo or neck length builds them afresh.
+    pub fn board(&mut self) -> &[Vec<FretCell>] {
+        let inputs = BoardInputs::from_settings(&self.settings);
+        match &mut self.board_cache {
+            Some((cached, _)) if *cached == inputs => {}
+            Some((cached, board)) if cached.same_notes(&inputs) => {
+                for cells in board.iter_mut() {
+                    mark_string(cells, &self.settings);
+                }
+                *cached = inputs;
+            }
+            _ => {
+                let board = (0..self.settings.instrument.string_count())
+                    .map(|string| generate_string_data(string, &self.settings))
+                    .collect();
+                self.board_cache = Some((inputs, board));
+            }
+        }
+        &self.board_cache.as_ref().unwrap().1
+    }
+
+    // Rows as drawn, top to bottom: the low string first with the nut on the left,
+    // or the whole board mirrored in left-handed mode. Only the order changes;
+    // each cell keeps its logical string and fret, so clicks and note lookups
+    // (get_note_at_position) work the same either way.
+    pub fn display_rows(&mut self) -> Vec<Vec<FretCell>> {
+        let left_handed = self.settings.left_handed;
+        let board = self.board();
+        if left_handed {
+            board.iter().rev().map(|cells| cells.iter().rev().cloned().collect()).collect()
+        } else {
+            board.to_vec()
+        }
+    }
+
+    // Whether the string labels and picker choices have changed since last asked,
+    // so most fretboard updates can leave them alone
+    pub fn pickers_changed(&mut self) -> bool {
+        let inputs = PickerInputs::from_settings(&self.settings);
+        if self.picker_inputs.as_ref() == Some(&inputs) {
+            return false;
+        }
+        self.picker_inputs = Some(inputs);
+        true
+    }
+
+    // Open-string names, low to high, spelled for the current key
+    pub fn string_labels(&self) -> Vec<&'static str> {
+        let key = self.settings.key;
+        self.settings.current_tuning().strings.iter().map(|n| n.note.name_in_key(key)).collect()
+    }
+
+    // Choices for the string-focus picker: "All strings", then each string from
+    // the lowest, named the way players count them (1st is the highest)
+    pub fn focus_options(&self) -> Vec<String> {
+        let count = self.settings.instrument.string_count();
+        let mut options = vec!["All strings".to_string()];
+        options.extend((1..=count).rev().map(|n| {
+            let suffix = match n {
+                1 => "st",
+                2 => "nd",
+                3 => "rd",
+                _ => "th",
+            };
+            format!("{}{} string", n, suffix)
+        }));
+        options
+    }
+
+    // Choices for the scale-position picker: "Whole neck", then one per scale note
+    pub fn position_options(&self) -> Vec<String> {
+        let mut options = vec!["Whole neck".to_string()];
+        options.extend((1..=self.settings.scale.intervals().len()).map(|n| format!("Position {}", n)));
+        options
+    }
+
+    // Work out what a click on the fretboard plays, applying the double-click action.
+    // The caller plays the result, so the UI can also refresh the note set.
+    pub fn fret_clicked(&mut self, string: u8, fret: u8, now: Instant) -> FretSound {
+        let note = self.note_at(string, fret);
+        let mut frequencies = vec![self.playback_frequency(note)];
+        if let Some(interval) = self.settings.harmony {
+            frequencies.push(self.playback_frequency(music_theory::harmony_note(note, interval)));
+        }
+        let mut duration = Duration::from_millis(self.settings.note_duration_ms);
+        let mut note_set_changed = false;
+        let mut bend_to = None;
+
+        if self.double_click.register(string as i32, fret as i32, now) {
+            match self.settings.double_click_action {
+                DoubleClickAction::Retrigger => {}
+                DoubleClickAction::OctaveUp => frequencies.iter_mut().for_each(|f| *f = fret_click::octave_up(*f)),
+                DoubleClickAction::Sustain => duration = fret_click::SUSTAIN_DURATION,
+                DoubleClickAction::AddToNoteSet => {
+                    // Double-clicking a note already in the set removes it
+                    self.toggle_in_note_set(note);
+                    note_set_changed = true;
+                }
+                DoubleClickAction::BendUp => {
+                    bend_to = Some(fret_click::whole_step_up(frequencies[0]));
+                    duration = fret_click::BEND_DURATION;
+                }
+            }
+        }
+        if self.settings.click_mode == ClickMode::Hold {
+            self.recorder.hold(note, now);
+        } else {
+            self.recorder.record(note, now, duration);
+        }
+        FretSound { note, frequencies, duration, note_set_changed, bend_to }
+    }
+
+    // A fret held down in hold mode was let go
+    pub fn fret_released(&mut self, now: Instant) {
+        self.audio.stop_note();
+        self.recorder.release(now);
+    }
+
+    pub fn note_at(&self, string: u8, fret: u8) -> Note {
+        music_theory::get_note_at_position_with_capo(string, fret, &self.settings.current_tuning(), self.settings.capo)
+    }
+
+    // Ask the next drill question somewhere on the frets shown, at or above the capo
+    pub fn next_drill_question(&mut self) {
+        let tuning = self.settings.current_tuning();
+        self.drill.next_question(&tuning, self.settings.visible_frets(), self.settings.capo);
+    }
+
+    // Score a drill answer and move on to the next question. The best streak goes
+    // into the settings so it's saved with the layout.
+    pub fn answer_drill(&mut self, text: &str) -> Option<DrillAnswer> {
+        let answer = self.drill.answer(text)?;
+        self.settings.drill_best_streak = self.drill.stats().best_streak;
+        self.next_drill_question();
+        Some(answer)
+    }
+
+    // The drill's prompt and score, after the verdict on the last answer if there was one
+    pub fn drill_status(&self, last: Option<DrillAnswer>) -> String {
+        let Some(question) = self.drill.question() else { return String::new() };
+        let mut status = match last {
+            Some(answer) if answer.correct => format!("Right, {}. ", answer.note.note.name_in_key(self.settings.key)),
+            Some(answer) => format!("No, that was {}. ", answer.note.note.name_in_key(self.settings.key)),
+            None => String::new(),
+        };
+        let stats = self.drill.stats();
+        let string = self.focus_options().get(question.string as usize + 1).cloned().unwrap_or_default();
+        let _ = write!(
+            status,
+            "{}, fret {}: which note? Score {}/{}, streak {} (best {})",
+            string, question.fret, stats.correct, stats.asked, stats.streak, stats.best_streak
+        );
+        status
+    }
+
+    // Score an interval quiz answer and move on to the next question
+    pub fn answer_interval_quiz(&mut self, answer: music_theory::Interval) -> Option<IntervalAnswer> {
+        let answer = self.interval_quiz.answer(answer)?;
+        self.interval_quiz.next_question();
+        Some(answer)
+    }
+
+    // Both notes of the current interval question, as played
+    pub fn interval_question_frequencies(&self) -> Vec<f32> {
+        self.interval_quiz
+            .question()
+            .map_or(Vec::new(), |question| question.notes().iter().map(|&note| self.playback_frequency(note)).collect())
+    }
+
+    // The quiz's prompt and score, after the verdict on the last answer if there was one
+    pub fn interval_quiz_status(&self, last: Option<IntervalAnswer>) -> String {
+        if self.interval_quiz.question().is_none() {
+            return String::new();
+        }
+        let mut status = match last {
+            Some(answer) if answer.correct => format!("Right, {}. ", answer.interval.name()),
+            Some(answer) => format!("No, that was {}. ", answer.interval.name()),
+            None => String::new(),
+        };
+        let total = self.interval_quiz.total();
+        let _ = write!(status, "Which interval? Score {}/{}", total.correct, total.asked);
+        if let Some(weakest) = self.interval_quiz.weakest() {
+            let score = self.interval_quiz.score(weakest);
+            let _ = write!(status, ", weakest {} {}/{}", weakest.name(), score.correct, score.asked);
+        }
+        status
+    }
+
+    // Hand the saved waveform, volume and note length to the player
+    pub fn apply_sound_settings(&mut self) {
+        self.audio.set_waveform(self.settings.waveform);
+        self.audio.set_volume(self.settings.volume);
+        self.audio.set_note_duration(self.settings.note_duration_ms);
+    }
+
+    // Start or stop the drone, sounding the key's tonic two octaves below middle C
+    pub fn set_drone(&mut self, on: bool) {
+        self.drone = on;
+        if on {
+            let frequency = self.playback_frequency(Note::new(self.settings.key, 2));
+            self.audio.start_drone(frequency);
+        } else {
+            self.audio.stop_drone();
+        }
+    }
+
+    // Frequency to play for a note: microtonal offsets first, then the
+    // ear-training detune that makes tensions sound slightly "unstable"
+    pub fn playback_frequency(&self, note: Note) -> f32 {
+        let mut frequency =
+            music_theory::calculate_frequency_with_offsets(note, &self.settings.note_cents, self.settings.reference_a4);
+        if self.settings.tension_detune {
+            let degree = music_theory::degree_number(note, self.settings.key);
+            frequency = music_theory::apply_cents(frequency, music_theory::detune_cents_for_degree(degree));
+        }
+        frequency
+    }
+
+    // One octave of the scale from the lowest root on the given string, ascending
+    // and then back down to the root
+    pub fn scale_run(&self, string: u8) -> Vec<Note> {
+        let key = self.settings.key;
+        // Every pitch class appears within the first 12 frets
+        let root = (0..12)
+            .map(|fret| self.note_at(string, fret))
+            .find(|note| note.note == key)
+            .unwrap_or_else(|| self.note_at(string, 0));
+        let mut run = music_theory::scale_notes_one_octave(key, self.settings.scale, root.octave);
+        run.push(root.transpose(12));
+        let descending: Vec<Note> = run.iter().rev().skip(1).copied().collect();
+        run.extend(descending);
+        run
+    }
+
+    // Sound the scale run from the focused string (or the lowest), once or, looped,
+    // over and over after the metronome's count-in. Either replaces a run already going.
+    pub fn play_scale(&mut self, looped: bool) {
+        self.scale_playback = None;
+        let string = u8::try_from(self.settings.focused_string).unwrap_or(0);
+        let frequencies: Vec<f32> = self.scale_run(string).iter()
+            .map(|&n| self.playback_frequency(n))
+            .collect();
+        let note_ms = self.settings.note_duration_ms;
+        let playback = if looped {
+            let (bars, time_signature) = (self.metronome.count_in_bars(), self.metronome.time_signature());
+            self.audio.play_loop(&frequencies, note_ms, audio::SCALE_GAP_MS, bars, time_signature)
+        } else {
+            self.audio.play_sequence(&frequencies, note_ms, audio::SCALE_GAP_MS, self.settings.legato)
+        };
+        self.scale_playback = Some(playback);
+    }
+
+    // Add a note to the custom set, or remove it if it's already there
+    pub fn toggle_in_note_set(&mut self, note: Note) {
+        if let Some(pos) = self.note_set.iter().position(|n| *n == note) {
+            self.note_set.remove(pos);
+        } else {
+            self.note_set.push(note);
+        }
+    }
+
+    // The custom note set if there is one, else the tonic triad of the key
+    pub fn chord_notes(&self) -> Vec<Note> {
+        if self.note_set.is_empty() {
+            music_theory::tonic_triad(self.settings.key, self.settings.scale, 3)
+        } else {
+            self.note_set.clone()
+        }
+    }
+}
+
+// Piano in the Keys pane: two octaves up from C3, plus the C on top
+pub const PIANO_LOWEST: Note = Note { note: Key::C, octave: 3 };
+pub const PIANO_KEY_COUNT: usize = 25;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PianoKey {
+    pub note: Note,
+    pub is_black: bool,
+    // Position among the white keys; for a black key, that of the white key to its left
+    pub white_index: usize,
+    pub is_in_scale: bool,
+    pub is_root: bool,
+}
+
+// The note of the piano key at `index` (0 = lowest) and whether it's a black key
+pub fn piano_key_note(index: usize) -> (Note, bool) {
+    let note = PIANO_LOWEST.transpose(index as i32);
+    (note, note.note.is_accidental())
+}
+
+// Every key of the piano, lowest first, marked against the key and scale
+pub fn piano_keys(key: Key, scale: Scale) -> Vec<PianoKey> {
+    // The lowest key is a C, so a black key always has a white key to its left
+    let mut white_keys = 0;
+    (0..PIANO_KEY_COUNT)
+        .map(|index| {
+            let (note, is_black) = piano_key_note(index);
+            if !is_black {
+                white_keys += 1;
+            }
+            let white_index = white_keys - 1;
+            PianoKey {
+                note,
+                is_black,
+                white_index,
+                is_in_scale: music_theory::is_note_in_scale(note, key, scale),
+                is_root: music_theory::is_root_note(note, key),
+            }
+        })
+        .collect()
+}
+
+// Generate fret cells for a specific string
+// root_offset_frets slides the highlighted pattern along the neck as if the key
+// root were that many frets higher, without changing the note names shown
+pub fn generate_string_data(string: u8, settings: &Settings) -> Vec<FretCell> {
+    let tuning = settings.current_tuning();
+
+    // The open string plus every fret up to fret_count, or just the window
+    let mut data: Vec<FretCell> = settings.visible_frets()
+        .map(|fret| {
+            let note = music_theory::get_note_at_position_with_capo(string, fret, &tuning, settings.capo);
+            let frequency = music_theory::calculate_frequency_with_reference(note, settings.reference_a4);
+            FretCell {
+                string,
+                fret,
+                note,
+                note_name: String::new(),
+                octave: note.octave,
+                frequency: (frequency * 10.0).round() / 10.0,
+                is_in_scale: false,
+                is_root: false,
+                degree: None,
+                marker: MarkerKind::None,
+                dimmed: false,
+            }
+        })
+        .collect();
+    mark_string(&mut data, settings);
+    data
+}
+
+// Redo everything about a string's cells that follows from the key, scale and
+// highlight settings, keeping their notes. Names are rewritten into the existing
+// strings, so once every cell has held a name this doesn't allocate.
+pub fn mark_string(cells: &mut [FretCell], settings: &Settings) {
+    let position = (settings.scale_position >= 0)
+        .then(|| music_theory::scale_position_range(settings.key, settings.scale, settings.scale_position as usize));
+    let chord = settings.highlighted_chord();
+
+    for cell in cells {
+        let note = cell.note;
+        let shifted = note.transpose(-settings.root_offset_frets);
+
+        cell.note_name.clear();
+        cell.note_name.push_str(note.note.name_in_key(settings.key));
+        let _ = write!(cell.note_name, "{}", note.octave);
+        // In chord mode only the chord tones light up, anywhere on the neck; finding
+        // a note lights up every fret that sounds it, ignoring the key altogether
+        cell.is_in_scale = match (settings.find_note, &chord) {
+            (Some(target), _) => music_theory::matches_pitch_class(note, target),
+            (None, Some(chord)) => music_theory::is_note_in_chord(shifted, chord),
+            (None, None) => music_theory::is_note_in_scale(shifted, settings.key, settings.scale)
+                && position.is_none_or(|range| in_position(cell.fret as i32 - settings.root_offset_frets, range)),
+        };
+        cell.is_root = settings.find_note.is_none() && music_theory::is_root_note(shifted, settings.key);
+        cell.degree = match (settings.find_note, &chord) {
+            (Some(_), _) => None,
+            (None, Some(chord)) => music_theory::chord_degree(shifted, chord),
+            (None, None) => music_theory::scale_degree(shifted, settings.key, settings.scale),
+        };
+        cell.marker = music_theory::marker_kind(cell.fret, &settings.marker_scheme);
+        // Greyed out behind the capo as well as off the focused string
+        cell.dimmed = (settings.focused_string >= 0 && settings.focused_string != cell.string as i32) || cell.fret < settings.capo;
+    }
+}
+
+// Whether the fret falls in the position's range, or the same shape an octave
+// away (so high positions still show on a 12-fret board)
+fn in_position(fret: i32, (low, high): (u8, u8)) -> bool {
+    [fret - 12, fret, fret + 12].iter().any(|f| (low as i32..=high as i32).contains(f))
+}
+
+// Keep the fret count to what real necks have
+pub fn clamp_fret_count(frets: i32) -> u8 {
+    frets.clamp(MIN_FRET_COUNT as i32, MAX_FRET_COUNT as i32) as u8
+}
+
+pub fn clamp_capo(fret: i32) -> u8 {
+    fret.clamp(0, MAX_CAPO as i32) as u8
+}
+
+pub fn clamp_zoom(zoom: f32) -> f32 {
+    if zoom.is_nan() {
+        return 1.0;
+    }
+    zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+// A window or monitor area in physical pixels; monitors left of or above the
+// primary one have negative coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelRect {
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+}
+
+// How much of a restored window has to be on a monitor for it to be left alone:
+// enough of the title bar to grab and drag
+const MIN_VISIBLE_PX: i32 = 100;
+
+// Where to put a saved window given the monitors there are now. A window whose top
+// edge is on a monitor with enough of it showing stays put; otherwise (say the
+// monitor it was on is gone) it's moved and, if need be, shrunk onto the monitor it
+// overlaps most, or the first one. With no monitors known it's returned unchanged.
+pub fn clamp_to_screens(window: PixelRect, screens: &[PixelRect]) -> PixelRect {
+    let overlap = |screen: &PixelRect| {
+        let width = (window.right().min(screen.right()) - window.x.max(screen.x)).max(0) as i64;
+        let height = (window.bottom().min(screen.bottom()) - window.y.max(screen.y)).max(0) as i64;
+        (width, height)
+    };
+    let reachable = screens.iter().any(|screen| {
+        let (width, _) = overlap(screen);
+        width >= MIN_VISIBLE_PX as i64 && window.y >= screen.y && window.y <= screen.bottom() - MIN_VISIBLE_PX
+    });
+    if reachable {
+        return window;
+    }
+    let Some(screen) = screens.iter().max_by_key(|screen| {
+        let (width, height) = overlap(screen);
+        width * height
+    }).filter(|screen| {
+        let (width, height) = overlap(screen);
+        width * height > 0
+    }).or(screens.first()) else {
+        return window;
+    };
+
+    let width = window.width.min(screen.width);
+    let height = window.height.min(screen.height);
+    PixelRect {
+        x: window.x.clamp(screen.x, screen.right() - width as i32),
+        y: window.y.clamp(screen.y, screen.bottom() - height as i32),
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighted(cells: &[FretCell]) -> Vec<u8> {
+        cells.iter().filter(|c| c.is_in_scale).map(|c| c.fret).collect()
+    }
+
+    #[test]
+    fn test_changing_scale_regenerates_board() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        assert_eq!(state.board().len(), 6);
+        // Low E string in C Major: E F G A B C D, then E again at the 12th fret
+        assert_eq!(highlighted(&state.board()[0]), vec![0, 1, 3, 5, 7, 8, 10, 12]);
+
+        state.settings.scale = Scale::MinorPentatonic;
+        // C Minor Pentatonic: C Eb F G Bb
+        assert_eq!(highlighted(&state.board()[0]), vec![1, 3, 6, 8, 11]);
+    }
+
+    #[test]
+    fn test_changing_key_respells_board() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.key = Key::F;
+        assert_eq!(state.board()[0][6].note_name, "Bb2");
+        assert_eq!(state.string_labels(), vec!["E", "A", "D", "G", "B", "E"]);
+    }
+
+    #[test]
+    fn test_board_spelling_follows_key() {
+        let names = |key: Key| -> Vec<String> {
+            generate_string_data(0, &Settings { key, ..Settings::default() })
+                .into_iter()
+                .map(|cell| cell.note_name)
+                .collect()
+        };
+        // F is a flat key: fret 6 on the low E is Bb
+        let f = names(Key::F);
+        assert_eq!(f[1], "F2");
+        assert_eq!(f[6], "Bb2");
+        // Eb major shows Eb/Ab/Bb rather than D#/G#/A#
+        let e_flat = names(Key::Ds);
+        assert_eq!((e_flat[4].as_str(), e_flat[11].as_str()), ("Ab2", "Eb3"));
+        // A sharp key keeps sharps
+        assert_eq!(names(Key::E)[6], "A#2");
+    }
+
+    #[test]
+    fn test_marker_scheme_change_updates_board() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        assert_eq!(state.board()[0][3].marker, MarkerKind::Single);
+        assert_eq!(state.board()[0][12].marker, MarkerKind::Double);
+
+        state.settings.marker_scheme = MarkerScheme::Custom(vec![2]);
+        let marked: Vec<u8> = state.board()[0].iter().filter(|c| c.marker != MarkerKind::None).map(|c| c.fret).collect();
+        assert_eq!(marked, vec![2]);
+    }
+
+    #[test]
+    fn test_root_offset_moves_highlight_not_names() {
+        let settings = Settings { scale: Scale::MajorPentatonic, ..Settings::default() };
+        let base = generate_string_data(0, &settings);
+        let shifted = generate_string_data(0, &Settings { root_offset_frets: 2, ..settings });
+
+        // Each cell is highlighted iff the cell two frets below was highlighted before
+        for fret in 2..INITIAL_FRET_COUNT as usize {
+            assert_eq!(shifted[fret].is_in_scale, base[fret - 2].is_in_scale, "fret {}", fret);
+        }
+        assert!(base[3].is_in_scale); // G
+        assert!(shifted[5].is_in_scale);
+
+        // Note names per cell are unchanged
+        for fret in 0..INITIAL_FRET_COUNT as usize {
+            assert_eq!(base[fret].note_name, shifted[fret].note_name);
+        }
+    }
+
+    #[test]
+    fn test_fret_count_sets_cell_count() {
+        for frets in [12, 22, 24] {
+            let settings = Settings { fret_count: frets, ..Settings::default() };
+            let cells = generate_string_data(0, &settings);
+            assert_eq!(cells.len(), frets as usize + 1);
+            assert_eq!(cells.last().unwrap().fret, frets);
+        }
+
+        // Out-of-range counts are clamped
+        assert_eq!(clamp_fret_count(5), MIN_FRET_COUNT);
+        assert_eq!(clamp_fret_count(40), MAX_FRET_COUNT);
+        let settings = Settings { fret_count: 40, ..Settings::default() };
+        assert_eq!(generate_string_data(0, &settings).len(), MAX_FRET_COUNT as usize + 1);
+    }
+
+    #[test]
+    fn test_fret_window_limits_board() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.key = Key::A;
+        state.settings.scale = Scale::MinorPentatonic;
+        state.settings.fret_window = Some((5, 9));
+        let board = state.board().to_vec();
+        assert!(board.iter().all(|cells| cells.len() == 5));
+
+        // Low E string, frets 5-9: A2 A#2 B2 C3 C#3
+        let low_e: Vec<(u8, &str)> = board[0].iter().map(|c| (c.fret, c.note_name.as_str())).collect();
+        assert_eq!(low_e, vec![(5, "A2"), (6, "A#2"), (7, "B2"), (8, "C3"), (9, "C#3")]);
+        assert_eq!(highlighted(&board[0]), vec![5, 8]);
+        assert!(board[0][0].is_root);
+        assert_eq!(board[5][0].note_name, "A4");
+
+        // A window past the end of the neck is cut back to it
+        state.settings.fret_window = Some((20, 30));
+        assert_eq!(state.settings.visible_frets(), 12..=12);
+        state.settings.fret_window = Some((9, 5));
+        assert_eq!(state.settings.visible_frets(), 9..=9);
+    }
+
+    #[test]
+    fn test_board_marks_root_notes() {
+        let settings = Settings { key: Key::E, ..Settings::default() };
+        let low_e = generate_string_data(0, &settings);
+        let roots: Vec<u8> = low_e.iter().filter(|c| c.is_root).map(|c| c.fret).collect();
+        assert_eq!(roots, vec![0, 12]);
+        assert!(low_e.iter().filter(|c| c.is_root).all(|c| c.is_in_scale));
+    }
+
+    #[test]
+    fn test_board_carries_scale_degrees() {
+        let settings = Settings { key: Key::A, scale: Scale::MinorPentatonic, ..Settings::default() };
+        let low_e = generate_string_data(0, &settings);
+        assert_eq!(low_e[0].degree, Some("5")); // E
+        assert_eq!(low_e[8].degree, Some("b3")); // C
+        assert_eq!(low_e[7].degree, None); // B
+    }
+
+    #[test]
+    fn test_focused_string_dims_the_others() {
+        let settings = Settings { focused_string: 2, ..Settings::default() };
+        for string in 0..settings.instrument.string_count() {
+            let cells = generate_string_data(string, &settings);
+            assert!(cells.iter().all(|c| c.dimmed == (string != 2)), "string {}", string);
+        }
+
+        // No focus dims nothing, and the note math is unaffected
+        let unfocused = generate_string_data(0, &Settings::default());
+        assert!(unfocused.iter().all(|c| !c.dimmed));
+        let focused = generate_string_data(0, &settings);
+        for (a, b) in unfocused.iter().zip(&focused) {
+            assert_eq!((&a.note_name, a.is_in_scale), (&b.note_name, b.is_in_scale));
+        }
+    }
+
+    #[test]
+    fn test_board_carries_frequencies() {
+        let settings = Settings::default();
+        assert_eq!(generate_string_data(0, &settings)[0].frequency, 82.4); // E2
+        assert_eq!(generate_string_data(5, &settings)[0].frequency, 329.6); // E4
+        assert_eq!(generate_string_data(0, &settings)[5].frequency, 110.0); // A2
+
+        // The cached board follows the reference pitch
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        assert_eq!(state.board()[1][0].frequency, 110.0);
+        state.settings.reference_a4 = 432.0;
+        assert_eq!(state.board()[1][0].frequency, 108.0);
+    }
+
+    #[test]
+    fn test_playback_frequency_applies_detune_only_when_enabled() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let d4 = Note::new(Key::D, 4); // The 2nd of C: a tension
+        assert_eq!(state.playback_frequency(d4), music_theory::calculate_frequency(d4));
+
+        state.settings.tension_detune = true;
+        assert!(state.playback_frequency(d4) > music_theory::calculate_frequency(d4));
+        let c4 = Note::new(Key::C, 4);
+        assert_eq!(state.playback_frequency(c4), music_theory::calculate_frequency(c4));
+    }
+
+    #[test]
+    fn test_playback_frequency_follows_reference_pitch() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.reference_a4 = 442.0;
+        assert_eq!(state.playback_frequency(Note::new(Key::A, 4)), 442.0);
+        assert_eq!(state.playback_frequency(Note::new(Key::A, 3)), 221.0);
+    }
+
+    #[test]
+    fn test_scale_run_c_major() {
+        let state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        // Low E string: the first C is at fret 8 (C3)
+        let run = state.scale_run(0);
+        assert_eq!(run.len(), 15);
+        assert_eq!(run[0], Note::new(Key::C, 3));
+        assert_eq!(run[7], Note::new(Key::C, 4));
+        assert_eq!(run[14], Note::new(Key::C, 3));
+
+        let frequencies: Vec<f32> = run.iter().map(|&n| state.playback_frequency(n)).collect();
+        assert!(frequencies[..8].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(frequencies[7..].windows(2).all(|pair| pair[0] > pair[1]));
+
+        // On the A string the same C3 is at fret 3
+        assert_eq!(state.scale_run(1)[0], Note::new(Key::C, 3));
+    }
+
+    #[test]
+    fn test_left_handed_rows_keep_logical_positions() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let right = state.display_rows();
+        state.settings.left_handed = true;
+        let left = state.display_rows();
+
+        // Mirrored: high E on top, open strings in the last column
+        assert_eq!(left.len(), right.len());
+        assert_eq!((left[0][0].string, left[0][0].fret), (5, 12));
+        let open_high_e = left[0].last().unwrap();
+        assert_eq!((open_high_e.string, open_high_e.fret), (5, 0));
+        assert_eq!(open_high_e.note_name, "E4");
+        assert_eq!(left[5][12].note_name, right[0][0].note_name);
+
+        // Every cell still names the note at its logical string and fret
+        for cell in left.iter().flatten() {
+            let note = state.note_at(cell.string, cell.fret);
+            assert_eq!(cell.note_name, note.name_in_key(state.settings.key));
+        }
+        assert_eq!(state.note_at(0, 3), Note::new(Key::G, 2));
+    }
+
+    #[test]
+    fn test_capo_shifts_board_and_playback() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.capo = 2;
+        let board = state.board().to_vec();
+
+        // The "open" low string sounds F#2, which isn't in C major
+        assert_eq!(board[0][0].note_name, "F#2");
+        assert!(!board[0][0].is_in_scale);
+        assert_eq!(state.note_at(0, 0), Note::new(Key::Fs, 2));
+        assert_eq!(state.playback_frequency(state.note_at(0, 0)), music_theory::calculate_frequency(Note::new(Key::Fs, 2)));
+
+        // Frets behind the capo are greyed out; the capo fret and above are not
+        let dimmed: Vec<u8> = board[0].iter().filter(|c| c.dimmed).map(|c| c.fret).collect();
+        assert_eq!(dimmed, vec![0, 1]);
+
+        // In G major the capoed F# is in the scale
+        state.settings.key = Key::G;
+        assert!(state.board()[0][0].is_in_scale);
+
+        assert_eq!(clamp_capo(-1), 0);
+        assert_eq!(clamp_capo(20), MAX_CAPO);
+    }
+
+    #[test]
+    fn test_scale_position_limits_highlighting() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.key = Key::A;
+        state.settings.scale = Scale::MinorPentatonic;
+        assert_eq!(state.position_options().len(), 6);
+
+        // First box: frets 5-8 only
+        state.settings.scale_position = 0;
+        assert_eq!(highlighted(&state.board()[0]), vec![5, 8]);
+        assert_eq!(highlighted(&state.board()[5]), vec![5, 8]);
+
+        // Fourth box (12-15) also shows an octave down at the nut
+        state.settings.scale_position = 3;
+        assert_eq!(highlighted(&state.board()[0]), vec![0, 3, 12]);
+
+        // Back to the whole neck
+        state.settings.scale_position = -1;
+        assert_eq!(highlighted(&state.board()[0]), vec![0, 3, 5, 8, 10, 12]);
+    }
+
+    #[test]
+    fn test_cells_carry_octave() {
+        let board = generate_string_data(0, &Settings::default());
+        // Low E: E2 at the nut, E3 at the 12th fret, C3 at the 8th
+        assert_eq!(board[0].octave, 2);
+        assert_eq!(board[12].octave, board[0].octave + 1);
+        assert_eq!(board[8].octave, 3);
+        assert_eq!(board[7].octave, 2);
+
+        // A capo raises the open string's octave along with its pitch
+        let capoed = generate_string_data(0, &Settings { capo: 12, ..Settings::default() });
+        assert_eq!(capoed[0].octave, 3);
+    }
+
+    #[test]
+    fn test_chord_highlighting() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.chord_highlight = Some(ChordQuality::Major);
+
+        // Only C, E and G light up, on every string
+        for cells in state.board().to_vec() {
+            for cell in &cells {
+                let note = state.note_at(cell.string, cell.fret);
+                assert_eq!(cell.is_in_scale, [Key::C, Key::E, Key::G].contains(&note.note), "{}", cell.note_name);
+            }
+        }
+        // Low E string: E, G, C, E
+        assert_eq!(highlighted(&state.board()[0]), vec![0, 3, 8, 12]);
+        assert_eq!(state.board()[0][3].degree, Some("5"));
+
+        // Back to the scale
+        state.settings.chord_highlight = None;
+        assert_eq!(highlighted(&state.board()[0]), vec![0, 1, 3, 5, 7, 8, 10, 12]);
+    }
+
+    #[test]
+    fn test_key_change_remarks_board_in_place() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let before: Vec<*const FretCell> = state.board().iter().map(|cells| cells.as_ptr()).collect();
+
+        state.settings.key = Key::F;
+        state.settings.scale = Scale::NaturalMinor;
+        state.settings.focused_string = 2;
+        let settings = state.settings.clone();
+        let board = state.board();
+        // Same rows, same cells, just re-marked
+        assert_eq!(board.iter().map(|cells| cells.as_ptr()).collect::<Vec<_>>(), before);
+        assert_eq!(board.len(), 6);
+        for (string, cells) in board.iter().enumerate() {
+            assert_eq!(cells, &generate_string_data(string as u8, &settings));
+        }
+        assert_eq!(state.board()[0][6].note_name, "Bb2");
+
+        // A capo moves the notes, so the board is built afresh
+        state.settings.capo = 2;
+        let expected = generate_string_data(0, &state.settings);
+        assert_eq!(state.board()[0], expected);
+    }
+
+    #[test]
+    fn test_pickers_rebuilt_only_when_their_inputs_change() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        assert!(state.pickers_changed());
+        assert!(!state.pickers_changed());
+
+        // Marking-only changes leave the labels and pickers as they are
+        state.settings.chord_highlight = Some(ChordQuality::Minor);
+        state.settings.focused_string = 2;
+        state.settings.capo = 3;
+        assert!(!state.pickers_changed());
+
+        // The key respells the string labels; the instrument changes the focus choices
+        state.settings.key = Key::F;
+        assert!(state.pickers_changed());
+        state.settings.instrument = Instrument::Bass4;
+        assert!(state.pickers_changed());
+        assert!(!state.pickers_changed());
+    }
+
+    #[test]
+    fn test_find_note_highlights_every_position() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.chord_highlight = Some(ChordQuality::Minor);
+        state.settings.find_note = Some(Key::G);
+
+        // Both E strings have G at the 3rd fret, whatever the key or chord mode
+        let board = state.board().to_vec();
+        assert!(board[0][3].is_in_scale && board[5][3].is_in_scale);
+        assert_eq!(highlighted(&board[0]), vec![3]);
+        assert_eq!(highlighted(&board[3]), vec![0, 12]);
+        for cell in board.iter().flatten() {
+            let note = state.note_at(cell.string, cell.fret);
+            assert_eq!(cell.is_in_scale, music_theory::matches_pitch_class(note, Key::G));
+            assert!(!cell.is_root && cell.degree.is_none());
+        }
+
+        // Off again: back to the C minor chord tones
+        state.settings.find_note = None;
+        assert_eq!(highlighted(&state.board()[0]), vec![3, 8, 11]);
+    }
+
+    #[test]
+    fn test_board_follows_instrument() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let open_strings = |state: &mut AppState| -> Vec<String> {
+            state.board().iter().map(|cells| cells[0].note_name.clone()).collect()
+        };
+
+        state.settings.instrument = Instrument::Bass4;
+        assert_eq!(open_strings(&mut state), vec!["E1", "A1", "D2", "G2"]);
+        assert_eq!(state.string_labels(), vec!["E", "A", "D", "G"]);
+        assert_eq!(state.focus_options(), vec!["All strings", "4th string", "3rd string", "2nd string", "1st string"]);
+
+        state.settings.instrument = Instrument::Guitar7;
+        assert_eq!(open_strings(&mut state), vec!["B1", "E2", "A2", "D3", "G3", "B3", "E4"]);
+        assert_eq!(state.note_at(0, 5), Note::new(Key::E, 2));
+        assert_eq!(state.display_rows().len(), 7);
+        assert_eq!(state.focus_options()[1], "7th string");
+    }
+
+    #[test]
+    fn test_window_clamped_onto_screen() {
+        let primary = PixelRect { x: 0, y: 0, width: 1920, height: 1080 };
+        let left = PixelRect { x: -1280, y: 0, width: 1280, height: 1024 };
+        let window = |x, y| PixelRect { x, y, width: 1200, height: 800 };
+
+        // On screen, or hanging off an edge with the title bar still reachable: unchanged
+        assert_eq!(clamp_to_screens(window(100, 50), &[primary]), window(100, 50));
+        assert_eq!(clamp_to_screens(window(1500, 600), &[primary]), window(1500, 600));
+        assert_eq!(clamp_to_screens(window(-1150, 100), &[primary, left]), window(-1150, 100));
+
+        // Saved on a monitor that has since been unplugged, with only 50px left showing:
+        // back onto the primary
+        assert_eq!(clamp_to_screens(window(-1150, 100), &[primary]), window(0, 100));
+        assert_eq!(clamp_to_screens(window(5000, 3000), &[primary]), window(720, 280));
+        // Title bar above the top of the screen
+        assert_eq!(clamp_to_screens(window(300, -200), &[primary]), window(300, 0));
+
+        // Too big for the monitor it lands on: shrunk to fit
+        let laptop = PixelRect { x: 0, y: 0, width: 1024, height: 768 };
+        assert_eq!(clamp_to_screens(window(2000, 0), &[laptop]), laptop);
+
+        // No monitor information: nothing to clamp against
+        assert_eq!(clamp_to_screens(window(5000, 3000), &[]), window(5000, 3000));
+    }
+
+    #[test]
+    fn test_piano_key_mapping() {
+        assert_eq!(piano_key_note(0), (Note::new(Key::C, 3), false));
+        assert_eq!(piano_key_note(1), (Note::new(Key::Cs, 3), true));
+        assert_eq!(piano_key_note(4), (Note::new(Key::E, 3), false));
+        assert_eq!(piano_key_note(6), (Note::new(Key::Fs, 3), true));
+        assert_eq!(piano_key_note(12), (Note::new(Key::C, 4), false));
+        assert_eq!(piano_key_note(PIANO_KEY_COUNT - 1), (Note::new(Key::C, 5), false));
+
+        let keys = piano_keys(Key::G, Scale::Major);
+        assert_eq!(keys.len(), PIANO_KEY_COUNT);
+        // 7 white keys an octave, plus the top C
+        assert_eq!(keys.iter().filter(|k| !k.is_black).count(), 15);
+        // Black keys sit between the white keys either side: C# after C (0), F# after F (3)
+        assert_eq!((keys[1].white_index, keys[6].white_index), (0, 3));
+        assert_eq!((keys[2].white_index, keys[5].white_index), (1, 3));
+        // G major: F# is in, F isn't, and both Gs are roots
+        assert!(keys[6].is_in_scale && !keys[5].is_in_scale);
+        assert_eq!(keys.iter().filter(|k| k.is_root).count(), 2);
+        assert!(keys.iter().all(|k| k.is_in_scale == music_theory::is_note_in_scale(k.note, Key::G, Scale::Major)));
+    }
+
+    #[test]
+    fn test_drill_best_streak_goes_into_settings() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.drill = NoteDrill::new(3);
+        state.settings.fret_window = Some((5, 7));
+        assert_eq!(state.answer_drill("C"), None);
+
+        state.next_drill_question();
+        for _ in 0..2 {
+            let question = state.drill.question().unwrap();
+            assert!((5..=7).contains(&question.fret));
+            let answer = state.answer_drill(question.note.note.name()).unwrap();
+            assert!(answer.correct);
+        }
+        assert_eq!(state.settings.drill_best_streak, 2);
+        let status = state.drill_status(None);
+        assert!(status.ends_with("Score 2/2, streak 2 (best 2)"), "{}", status);
+
+        let question = state.drill.question().unwrap();
+        let wrong = state.answer_drill(question.note.transpose(2).note.name()).unwrap();
+        assert!(state.drill_status(Some(wrong)).starts_with("No, that was"));
+        assert_eq!(state.settings.drill_best_streak, 2);
+
+        // With a capo at 6, fret 5 is behind it and the notes asked are the capoed ones
+        state.settings.capo = 6;
+        for _ in 0..5 {
+            state.next_drill_question();
+            let question = state.drill.question().unwrap();
+            assert!((6..=7).contains(&question.fret));
+            assert_eq!(question.note, state.note_at(question.string, question.fret));
+            assert!(state.answer_drill(question.note.note.name()).unwrap().correct);
+        }
+    }
+
+    #[test]
+    fn test_interval_quiz_status() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.interval_quiz = IntervalQuiz::new(9);
+        assert!(state.interval_question_frequencies().is_empty());
+        assert_eq!(state.interval_quiz_status(None), "");
+
+        let question = state.interval_quiz.next_question();
+        let frequencies = state.interval_question_frequencies();
+        assert_eq!(frequencies, vec![state.playback_frequency(question.low), state.playback_frequency(question.high)]);
+        let answer = state.answer_interval_quiz(question.interval).unwrap();
+        assert!(answer.correct);
+        let status = state.interval_quiz_status(Some(answer));
+        assert!(status.starts_with("Right, "), "{}", status);
+        assert!(status.contains("Score 1/1"), "{}", status);
+        // Answering moves straight on to a new question
+        assert!(state.interval_quiz.question().is_some());
+    }
+
+    #[test]
+    fn test_fret_click_plays_through_player() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let mut player = audio::RecordingPlayer::default();
+        let now = Instant::now();
+
+        // Low string, 5th fret: A2
+        let sound = state.fret_clicked(0, 5, now);
+        assert_eq!(sound.note, Note::new(Key::A, 2));
+        sound.play(&mut player);
+        assert_eq!(*player.played.borrow(), vec![vec![110.0]]);
+
+        // Clicked again straight away with octave-up as the double-click action
+        state.settings.double_click_action = DoubleClickAction::OctaveUp;
+        state.fret_clicked(0, 5, now + Duration::from_millis(100)).play(&mut player);
+        assert_eq!(player.played.borrow()[1], vec![220.0]);
+
+        // Double stops sound both notes in one request
+        state.settings.harmony = Some(HarmonyInterval::Octave);
+        state.fret_clicked(0, 5, now + Duration::from_secs(5)).play(&mut player);
+        assert_eq!(player.played.borrow()[2], vec![110.0, 220.0]);
+
+        // Bend up slides the fretted note a whole step, to B2
+        state.settings.harmony = None;
+        state.settings.double_click_action = DoubleClickAction::BendUp;
+        state.fret_clicked(0, 5, now + Duration::from_secs(7));
+        state.fret_clicked(0, 5, now + Duration::from_millis(7100)).play(&mut player);
+        assert_eq!(player.played.borrow().len(), 3);
+        let (from_hz, to_hz) = player.bends[0];
+        assert_eq!(from_hz, 110.0);
+        assert!((to_hz - 123.47).abs() < 0.01);
+        assert!(!state.fret_clicked(1, 0, now + Duration::from_secs(10)).note_set_changed);
+    }
+
+    #[test]
+    fn test_sound_settings_and_drone_reach_the_player() {
+        let player = audio::RecordingPlayer::default();
+        let mut state = AppState::new(Box::new(player.clone()));
+
+        state.settings.waveform = Waveform::Square;
+        state.settings.volume = 0.4;
+        state.settings.note_duration_ms = 800;
+        state.apply_sound_settings();
+        assert_eq!(*player.settings.borrow(), audio::RecordedSettings {
+            waveform: Waveform::Square,
+            volume: 0.4,
+            note_duration_ms: 800,
+            drone: None,
+        });
+
+        // The drone sounds the key's tonic in octave 2
+        state.settings.key = Key::A;
+        state.set_drone(true);
+        assert_eq!(player.settings.borrow().drone, Some(110.0));
+        state.set_drone(false);
+        assert_eq!(player.settings.borrow().drone, None);
+
+        // Fret clicks reach the boxed player too
+        let sound = state.fret_clicked(0, 5, Instant::now());
+        sound.play(state.audio.as_mut());
+        assert_eq!(*player.played.borrow(), vec![vec![110.0]]);
+    }
+
+    #[test]
+    fn test_play_scale_once_and_looped() {
+        let player = audio::RecordingPlayer::default();
+        let mut state = AppState::new(Box::new(player.clone()));
+        let run: Vec<f32> = state.scale_run(0).iter().map(|&n| state.playback_frequency(n)).collect();
+
+        state.play_scale(false);
+        state.play_scale(true);
+        assert_eq!(*player.played.borrow(), vec![run.clone(), run]);
+        assert!(state.scale_playback.is_some());
+    }
+
+    #[test]
+    fn test_recording_captures_clicks_and_holds() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        state.settings.note_duration_ms = 300;
+
+        state.recorder.start(now);
+        state.fret_clicked(0, 5, now + ms(100));
+        // In hold mode the note lasts until the fret is let go
+        state.settings.click_mode = ClickMode::Hold;
+        state.fret_clicked(1, 0, now + ms(1000));
+        state.fret_released(now + ms(1600));
+
+        let take: Vec<(Note, Duration, Duration)> =
+            state.recorder.take().iter().map(|n| (n.note, n.start, n.duration)).collect();
+        assert_eq!(take, vec![
+            (Note::new(Key::A, 2), ms(100), ms(300)),
+            (Note::new(Key::A, 2), ms(1000), ms(600)),
+        ]);
+    }
+
+    #[test]
+    fn test_note_set_toggle_and_chord() {
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        assert_eq!(state.chord_notes(), music_theory::tonic_triad(Key::C, Scale::Major, 3));
+
+        let a3 = Note::new(Key::A, 3);
+        state.toggle_in_note_set(a3);
+        assert_eq!(state.chord_notes(), vec![a3]);
+        state.toggle_in_note_set(a3);
+        assert!(state.note_set.is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+// ChordPro-style chord sheets: chords in brackets inline with the lyrics,
+// e.g. "[G]Amazing [D]grace", rendered as a chord row above a lyric row
+
+use crate::chords;
+use crate::music_theory::{self, ChordQuality, Key, NoteSpelling};
+
+// A run of lyrics with the chord (if any) played at its start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub chord: Option<String>,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SheetLine {
+    pub segments: Vec<Segment>,
+}
+
+impl SheetLine {
+    pub fn has_chords(&self) -> bool {
+        self.segments.iter().any(|s| s.chord.is_some())
+    }
+
+    // Fingerings for the line's chords, e.g. "G 320003  D/F# xx0232"; chords the
+    // shape table doesn't have are left out
+    pub fn shape_row(&self) -> String {
+        let shapes: Vec<String> = self.segments
+            .iter()
+            .filter_map(|segment| segment.chord.as_deref())
+            .filter_map(|chord| {
+                let (root, quality) = parse_chord(chord)?;
+                Some(format!("{} {}", chord, chords::lookup_shape(root, quality)?.pattern()))
+            })
+            .collect();
+        shapes.join("  ")
+    }
+
+    // Chord row and lyric row, padded so each chord sits over the start of its
+    // segment in a monospace font. A chord longer than its lyrics pushes the
+    // following lyrics right so chords never run into each other.
+    pub fn aligned_rows(&self) -> (String, String) {
+        let mut chords = String::new();
+        let mut lyrics = String::new();
+        for segment in &self.segments {
+            let start = lyrics.chars().count();
+            pad_to(&mut chords, start);
+            let chord_width = match &segment.chord {
+                Some(chord) => {
+                    chords.push_str(chord);
+                    chord.chars().count() + 1
+                }
+                None => 0,
+            };
+            lyrics.push_str(&segment.text);
+            // Leave room for the chord plus a space before the next one
+            if segment.chord.is_some() {
+                pad_to(&mut lyrics, start + chord_width);
+            }
+        }
+        (chords.trim_end().to_string(), lyrics.trim_end().to_string())
+    }
+}
+
+fn pad_to(row: &mut String, width: usize) {
+    let len = row.chars().count();
+    if len < width {
+        row.extend(std::iter::repeat_n(' ', width - len));
+    }
+}
+
+// Parse one line. A '[' without a matching ']' (or with another '[' before it)
+// is kept as literal text, as is a stray ']'.
+pub fn parse_line(line: &str) -> SheetLine {
+    let mut segments = Vec::new();
+    let mut chord: Option<String> = None;
+    let mut text = String::new();
+    let mut rest = line.trim_end_matches(['\r', '\n']);
+
+    while let Some(open) = rest.find('[') {
+        let after = &rest[open + 1..];
+        let close = after.find(']').filter(|&close| !after[..close].contains('['));
+        match close {
+            Some(close) if !after[..close].trim().is_empty() => {
+                text.push_str(&rest[..open]);
+                if chord.is_some() || !text.is_empty() {
+                    segments.push(Segment { chord: chord.take(), text: std::mem::take(&mut text) });
+                }
+                chord = Some(after[..close].trim().to_string());
+                rest = &after[close + 1..];
+            }
+            // "[]" carries no chord; drop it
+            Some(close) => {
+                text.push_str(&rest[..open]);
+                rest = &after[close + 1..];
+            }
+            None => {
+                text.push_str(&rest[..=open]);
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    if chord.is_some() || !text.is_empty() {
+        segments.push(Segment { chord, text });
+    }
+    SheetLine { segments }
+}
+
+pub fn parse(sheet: &str) -> Vec<SheetLine> {
+    sheet.lines().map(parse_line).collect()
+}
+
+// Leading note name of a chord ("F#m7" -> F#), with the number of bytes it used.
+// Double sharps and flats ("F##", "Ebb") are read as the note they sound.
+fn parse_note_name(text: &str) -> Option<(Key, usize)> {
+    let mut chars = text.chars();
+    let natural: i32 = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let (offset, len) = match (chars.next(), chars.next()) {
+        (Some('#'), Some('#')) => (2, 3),
+        (Some('b'), Some('b')) => (-2, 3),
+        (Some('#'), _) => (1, 2),
+        (Some('b'), _) => (-1, 2),
+        _ => (0, 1),
+    };
+    Some((Key::from_int((natural + offset).rem_euclid(12)), len))
+}
+
+// Root and quality of a chord symbol ("F#m7" -> F#, m7); a slash bass is ignored.
+// None for anything that isn't a note name followed by a known quality.
+pub fn parse_chord(chord: &str) -> Option<(Key, ChordQuality)> {
+    let (root, root_len) = parse_note_name(chord)?;
+    let quality = chord[root_len..].split('/').next().unwrap_or_default();
+    let quality = (0..=8).map(ChordQuality::from_int).find(|known| known.suffix() == quality)?;
+    Some((root, quality))
+}
+
+fn shift(key: Key, semitones: i32) -> Key {
+    Key::from_int((key.to_int() + semitones).rem_euclid(12))
+}
+
+// Shift a chord's root and slash bass; chords that don't start with a note name
+// are returned unchanged
+pub fn transpose_chord(chord: &str, semitones: i32, spelling: NoteSpelling) -> String {
+    let Some((root, root_len)) = parse_note_name(chord) else { return chord.to_string() };
+    let rest = &chord[root_len..];
+    let (quality, bass) = match rest.split_once('/') {
+        Some((quality, bass)) => (quality, Some(bass)),
+        None => (rest, None),
+    };
+    let mut result = format!("{}{}", shift(root, semitones).name_with(spelling), quality);
+    if let Some(bass) = bass {
+        result.push('/');
+        match parse_note_name(bass) {
+            Some((note, len)) => {
+                result.push_str(shift(note, semitones).name_with(spelling));
+                result.push_str(&bass[len..]);
+            }
+            None => result.push_str(bass),
+        }
+    }
+    result
+}
+
+// The sheet's key, guessed from its first chord; a minor first chord means the
+// relative major, which decides sharps vs flats
+fn sheet_key(sheet: &[SheetLine]) -> Option<Key> {
+    let first = sheet.iter().flat_map(|line| &line.segments).find_map(|s| s.chord.as_deref())?;
+    let (root, len) = parse_note_name(first)?;
+    let quality = &first[len..];
+    let minor = quality.starts_with('m') && !quality.starts_with("maj");
+    Some(if minor { shift(root, 3) } else { root })
+}
+
+// Move every chord by the given number of semitones, spelled with the new key's
+// signature: flats in a flat key (C up one is Db, so Db Gb Ab), sharps otherwise
+pub fn transpose(sheet: &[SheetLine], semitones: i32) -> Vec<SheetLine> {
+    let spelling = match sheet_key(sheet).map(|key| music_theory::key_signature_accidentals(shift(key, semitones))) {
+        Some(accidentals) if accidentals < 0 => NoteSpelling::Flat,
+        _ => NoteSpelling::Sharp,
+    };
+    sheet
+        .iter()
+        .map(|line| SheetLine {
+            segments: line
+                .segments
+                .iter()
+                .map(|segment| Segment {
+                    chord: segment.chord.as_deref().map(|c| transpose_chord(c, semitones, spelling)),
+                    text: segment.text.clone(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(chord: Option<&str>, text: &str) -> Segment {
+        Segment { chord: chord.map(str::to_string), text: text.to_string() }
+    }
+
+    #[test]
+    fn test_parse_inline_chords() {
+        let line = parse_line("[G]Amazing [D]grace");
+        assert_eq!(line.segments, vec![seg(Some("G"), "Amazing "), seg(Some("D"), "grace")]);
+        assert_eq!(
+            line.aligned_rows(),
+            ("G       D".to_string(), "Amazing grace".to_string())
+        );
+
+        // Lyrics before the first chord, and a chord at the end of the line
+        let line = parse_line("How [C]sweet the sound[G]");
+        assert_eq!(line.segments, vec![seg(None, "How "), seg(Some("C"), "sweet the sound"), seg(Some("G"), "")]);
+        assert_eq!(line.aligned_rows().0, "    C              G");
+    }
+
+    #[test]
+    fn test_long_chords_push_lyrics_apart() {
+        let (chords, lyrics) = parse_line("[Cmaj7]a[G]b").aligned_rows();
+        assert_eq!(chords, "Cmaj7 G");
+        assert_eq!(lyrics, "a     b");
+    }
+
+    #[test]
+    fn test_shape_row_shows_each_chords_fingering() {
+        assert_eq!(parse_chord("F#m"), Some((Key::Fs, ChordQuality::Minor)));
+        assert_eq!(parse_chord("Bbmaj7/D"), Some((Key::As, ChordQuality::Maj7)));
+        assert_eq!(parse_chord("N.C."), None);
+        assert_eq!(parse_chord("C9"), None);
+
+        let line = parse_line("[G]Amazing [D/F#]grace how [Em]sweet [Bdim]the [C]sound");
+        // Bdim has no shape in the table
+        assert_eq!(line.shape_row(), "G 320003  D/F# xx0232  Em 022000  C x32010");
+        assert_eq!(parse_line("Just lyrics").shape_row(), "");
+    }
+
+    #[test]
+    fn test_transpose_chords() {
+        assert_eq!(transpose_chord("B", 1, NoteSpelling::Sharp), "C");
+        assert_eq!(transpose_chord("C", -1, NoteSpelling::Sharp), "B");
+        assert_eq!(transpose_chord("D/F#", 2, NoteSpelling::Sharp), "E/G#");
+        assert_eq!(transpose_chord("D/F#", 3, NoteSpelling::Flat), "F/A");
+        assert_eq!(transpose_chord("Bbmaj7", 2, NoteSpelling::Sharp), "Cmaj7");
+        assert_eq!(transpose_chord("Am7", 1, NoteSpelling::Flat), "Bbm7");
+        // Unrecognized tokens stay as they are
+        assert_eq!(transpose_chord("N.C.", 5, NoteSpelling::Sharp), "N.C.");
+        assert_eq!(transpose_chord("G/x", 2, NoteSpelling::Sharp), "A/x");
+        // Double accidentals are read as the note they sound and respelled plainly
+        assert_eq!(transpose_chord("F##m", 0, NoteSpelling::Sharp), "Gm");
+        assert_eq!(transpose_chord("Ebb7/Bbb", 1, NoteSpelling::Flat), "Eb7/Bb");
+    }
+
+    #[test]
+    fn test_transpose_one_four_five_into_remote_keys() {
+        let sheet = parse("[C]one [F]four [G]five");
+        let chords = |semitones| -> Vec<String> {
+            transpose(&sheet, semitones)[0].segments.iter().filter_map(|s| s.chord.clone()).collect()
+        };
+        // Db major has five flats; D major two sharps
+        assert_eq!(chords(1), vec!["Db", "Gb", "Ab"]);
+        assert_eq!(chords(2), vec!["D", "G", "A"]);
+        assert_eq!(chords(6), vec!["F#", "B", "C#"]);
+        assert_eq!(chords(-2), vec!["Bb", "Eb", "F"]);
+    }
+
+    #[test]
+    fn test_transpose_sheet_uses_target_key_spelling() {
+        let sheet = parse("[G]Amazing [D/F#]grace\nhow [Em]sweet");
+        // G up 3 is Bb: a flat key
+        let up = transpose(&sheet, 3);
+        assert_eq!(up[0].segments[0].chord.as_deref(), Some("Bb"));
+        assert_eq!(up[0].segments[1].chord.as_deref(), Some("F/A"));
+        assert_eq!(up[1].segments[1].chord.as_deref(), Some("Gm"));
+        // Lyrics untouched
+        assert_eq!(up[0].segments[0].text, "Amazing ");
+
+        // G up 4 is B: a sharp key
+        assert_eq!(transpose(&sheet, 4)[0].segments[1].chord.as_deref(), Some("F#/A#"));
+    }
+
+    #[test]
+    fn test_plain_blank_and_malformed_lines() {
+        assert!(!parse_line("Just lyrics").has_chords());
+        assert_eq!(parse_line("Just lyrics").aligned_rows(), (String::new(), "Just lyrics".to_string()));
+        assert!(parse_line("").segments.is_empty());
+
+        // Unclosed and stray brackets stay as text
+        assert_eq!(parse_line("oops [G sing").segments, vec![seg(None, "oops [G sing")]);
+        assert_eq!(parse_line("a ] b").segments, vec![seg(None, "a ] b")]);
+        assert_eq!(parse_line("[[G]x").segments, vec![seg(None, "["), seg(Some("G"), "x")]);
+        assert_eq!(parse_line("[]x").segments, vec![seg(None, "x")]);
+
+        assert_eq!(parse("[G]one\n\ntwo").len(), 3);
+    }
+}
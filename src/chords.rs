@@ -0,0 +1,199 @@
+// Reference chord shapes (open and barre) for beginners
+
+use crate::music_theory::{self, ChordQuality, Key, Note, Tuning};
+use std::time::Duration;
+
+// One chord voicing across the six strings, low E first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChordShape {
+    pub name: &'static str,
+    pub frets: [Option<u8>; 6], // None = muted string, Some(0) = open
+    pub fingers: [u8; 6],       // 1 = index .. 4 = pinky, 0 = no finger
+}
+
+impl ChordShape {
+    // Conventional tab notation, e.g. "x32010" for C; frets above 9 are bracketed
+    pub fn pattern(&self) -> String {
+        self.frets
+            .iter()
+            .map(|fret| match fret {
+                None => "x".to_string(),
+                Some(f) if *f > 9 => format!("({})", f),
+                Some(f) => f.to_string(),
+            })
+            .collect()
+    }
+
+    // Barre shapes hold one finger across several strings
+    pub fn is_barre(&self) -> bool {
+        self.fingers.iter().filter(|&&f| f == 1).count() > 1
+    }
+
+    // The same shape fingered above a capo: every sounding string moves up
+    pub fn capoed(&self, capo: u8) -> ChordShape {
+        ChordShape { frets: self.frets.map(|fret| fret.map(|f| f + capo)), ..*self }
+    }
+}
+
+const X: Option<u8> = None;
+const fn f(fret: u8) -> Option<u8> {
+    Some(fret)
+}
+
+pub const CHORD_SHAPES: &[ChordShape] = &[
+    // Open chords
+    ChordShape { name: "C", frets: [X, f(3), f(2), f(0), f(1), f(0)], fingers: [0, 3, 2, 0, 1, 0] },
+    ChordShape { name: "A", frets: [X, f(0), f(2), f(2), f(2), f(0)], fingers: [0, 0, 1, 2, 3, 0] },
+    ChordShape { name: "G", frets: [f(3), f(2), f(0), f(0), f(0), f(3)], fingers: [2, 1, 0, 0, 0, 3] },
+    ChordShape { name: "E", frets: [f(0), f(2), f(2), f(1), f(0), f(0)], fingers: [0, 2, 3, 1, 0, 0] },
+    ChordShape { name: "D", frets: [X, X, f(0), f(2), f(3), f(2)], fingers: [0, 0, 0, 1, 3, 2] },
+    ChordShape { name: "Am", frets: [X, f(0), f(2), f(2), f(1), f(0)], fingers: [0, 0, 2, 3, 1, 0] },
+    ChordShape { name: "Em", frets: [f(0), f(2), f(2), f(0), f(0), f(0)], fingers: [0, 2, 3, 0, 0, 0] },
+    ChordShape { name: "Dm", frets: [X, X, f(0), f(2), f(3), f(1)], fingers: [0, 0, 0, 2, 3, 1] },
+    // Sevenths
+    ChordShape { name: "E7", frets: [f(0), f(2), f(0), f(1), f(0), f(0)], fingers: [0, 2, 0, 1, 0, 0] },
+    ChordShape { name: "A7", frets: [X, f(0), f(2), f(0), f(2), f(0)], fingers: [0, 0, 2, 0, 3, 0] },
+    ChordShape { name: "D7", frets: [X, X, f(0), f(2), f(1), f(2)], fingers: [0, 0, 0, 2, 1, 3] },
+    ChordShape { name: "G7", frets: [f(3), f(2), f(0), f(0), f(0), f(1)], fingers: [3, 2, 0, 0, 0, 1] },
+    ChordShape { name: "C7", frets: [X, f(3), f(2), f(3), f(1), f(0)], fingers: [0, 3, 2, 4, 1, 0] },
+    ChordShape { name: "B7", frets: [X, f(2), f(1), f(2), f(0), f(2)], fingers: [0, 2, 1, 3, 0, 4] },
+    ChordShape { name: "Am7", frets: [X, f(0), f(2), f(0), f(1), f(0)], fingers: [0, 0, 2, 0, 1, 0] },
+    ChordShape { name: "Em7", frets: [f(0), f(2), f(0), f(0), f(0), f(0)], fingers: [0, 2, 0, 0, 0, 0] },
+    ChordShape { name: "Dm7", frets: [X, X, f(0), f(2), f(1), f(1)], fingers: [0, 0, 0, 2, 1, 1] },
+    ChordShape { name: "Cmaj7", frets: [X, f(3), f(2), f(0), f(0), f(0)], fingers: [0, 3, 2, 0, 0, 0] },
+    ChordShape { name: "Fmaj7", frets: [X, X, f(3), f(2), f(1), f(0)], fingers: [0, 0, 3, 2, 1, 0] },
+    // Suspended
+    ChordShape { name: "Dsus2", frets: [X, X, f(0), f(2), f(3), f(0)], fingers: [0, 0, 0, 1, 3, 0] },
+    ChordShape { name: "Dsus4", frets: [X, X, f(0), f(2), f(3), f(3)], fingers: [0, 0, 0, 1, 3, 4] },
+    ChordShape { name: "Asus2", frets: [X, f(0), f(2), f(2), f(0), f(0)], fingers: [0, 0, 1, 2, 0, 0] },
+    ChordShape { name: "Asus4", frets: [X, f(0), f(2), f(2), f(3), f(0)], fingers: [0, 0, 1, 2, 3, 0] },
+    // Barre chords
+    ChordShape { name: "F", frets: [f(1), f(3), f(3), f(2), f(1), f(1)], fingers: [1, 3, 4, 2, 1, 1] },
+    ChordShape { name: "Bb", frets: [X, f(1), f(3), f(3), f(3), f(1)], fingers: [0, 1, 3, 3, 3, 1] },
+    ChordShape { name: "Bm", frets: [X, f(2), f(4), f(4), f(3), f(2)], fingers: [0, 1, 3, 4, 2, 1] },
+    ChordShape { name: "F#m", frets: [f(2), f(4), f(4), f(2), f(2), f(2)], fingers: [1, 3, 4, 1, 1, 1] },
+    ChordShape { name: "Gm", frets: [f(3), f(5), f(5), f(3), f(3), f(3)], fingers: [1, 3, 4, 1, 1, 1] },
+    ChordShape { name: "Cm", frets: [X, f(3), f(5), f(5), f(4), f(3)], fingers: [0, 1, 3, 4, 2, 1] },
+];
+
+// One string of a strum: what sounds and when it starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrummedNote {
+    pub string: u8,
+    pub note: Note,
+    pub onset: Duration,
+}
+
+// Notes of a downstroke across the shape, low string first, each sounding string
+// starting `strum` after the previous one; muted strings are skipped. Shapes are
+// for six strings: on a 7-string they sit on the top six, and an instrument with
+// fewer strings can't play them.
+pub fn strum_notes(shape: &ChordShape, tuning: &Tuning, strum: Duration) -> Vec<StrummedNote> {
+    let Some(lowest) = tuning.strings.len().checked_sub(shape.frets.len()) else { return Vec::new() };
+    shape.frets
+        .iter()
+        .enumerate()
+        .filter_map(|(string, fret)| fret.map(|fret| ((lowest + string) as u8, fret)))
+        .enumerate()
+        .map(|(i, (string, fret))| StrummedNote {
+            string,
+            note: music_theory::get_note_at_position(string, fret, tuning),
+            onset: strum * i as u32,
+        })
+        .collect()
+}
+
+// Look up a reference shape by chord name (case-sensitive: "Am", not "AM")
+pub fn chord_shape(name: &str) -> Option<ChordShape> {
+    CHORD_SHAPES.iter().find(|shape| shape.name == name.trim()).copied()
+}
+
+// Look up a reference shape by root and quality, whichever way the table spells
+// the root (F#m, Bb). The Chord Sheet shows these under each line's chords.
+pub fn lookup_shape(root: Key, quality: ChordQuality) -> Option<ChordShape> {
+    [root.name(), root.flat_name()]
+        .iter()
+        .find_map(|name| chord_shape(&format!("{}{}", name, quality.suffix())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_chord_patterns() {
+        let pattern = |name| chord_shape(name).unwrap().pattern();
+        assert_eq!(pattern("C"), "x32010");
+        assert_eq!(pattern("G"), "320003");
+        assert_eq!(pattern("D"), "xx0232");
+        assert_eq!(pattern("Em"), "022000");
+        assert_eq!(pattern("Am"), "x02210");
+        assert!(!chord_shape("C").unwrap().is_barre());
+    }
+
+    #[test]
+    fn test_strum_c_major() {
+        use crate::music_theory::Key;
+        let c = chord_shape("C").unwrap();
+        let strum = strum_notes(&c, &Tuning::STANDARD, Duration::from_millis(30));
+
+        // The muted low E is skipped; the rest are C E G C E across octaves
+        assert_eq!(strum.len(), 5);
+        assert_eq!(strum[0].string, 1);
+        let notes: Vec<(Key, i32)> = strum.iter().map(|s| (s.note.note, s.note.octave)).collect();
+        assert_eq!(notes, vec![(Key::C, 3), (Key::E, 3), (Key::G, 3), (Key::C, 4), (Key::E, 4)]);
+        let frequencies: Vec<f32> = strum.iter().map(|s| music_theory::calculate_frequency(s.note)).collect();
+        assert!((frequencies[0] - 130.81).abs() < 0.01);
+        assert!((frequencies[2] - 196.00).abs() < 0.01);
+
+        // Onsets step evenly from the first sounding string
+        let onsets: Vec<u128> = strum.iter().map(|s| s.onset.as_millis()).collect();
+        assert_eq!(onsets, vec![0, 30, 60, 90, 120]);
+    }
+
+    #[test]
+    fn test_strum_on_other_instruments_and_capo() {
+        use crate::music_theory::{Instrument, Key};
+        let c = chord_shape("C").unwrap();
+        let strum = Duration::from_millis(30);
+
+        // The 7-string's low B is left out, like the muted low E
+        let seven = strum_notes(&c, &Instrument::Guitar7.standard_tuning(), strum);
+        let six = strum_notes(&c, &Tuning::STANDARD, strum);
+        assert_eq!(seven.len(), six.len());
+        assert!(seven.iter().zip(&six).all(|(s, g)| s.string == g.string + 1 && s.note == g.note));
+        assert!(strum_notes(&c, &Instrument::Bass4.standard_tuning(), strum).is_empty());
+
+        // C shape with a capo at 2 sounds D
+        let capoed = strum_notes(&c.capoed(2), &Tuning::STANDARD, strum);
+        assert_eq!(capoed[0].note, Note::new(Key::D, 3));
+        assert_eq!(c.capoed(2).pattern(), "x54232");
+    }
+
+    #[test]
+    fn test_lookup_by_root_and_quality() {
+        let pattern = |root, quality| lookup_shape(root, quality).unwrap().pattern();
+        assert_eq!(pattern(Key::C, ChordQuality::Major), "x32010");
+        assert_eq!(pattern(Key::E, ChordQuality::Minor), "022000");
+        assert_eq!(pattern(Key::G, ChordQuality::Dom7), "320001");
+        // Either spelling of the root finds the shape
+        assert_eq!(pattern(Key::As, ChordQuality::Major), "x13331");
+        assert_eq!(pattern(Key::Fs, ChordQuality::Minor), "244222");
+        assert_eq!(lookup_shape(Key::Cs, ChordQuality::Dim), None);
+
+        // Open and muted strings stay distinct
+        let c = lookup_shape(Key::C, ChordQuality::Major).unwrap();
+        assert_eq!(c.frets[0], None);
+        assert_eq!(c.frets[5], Some(0));
+    }
+
+    #[test]
+    fn test_barre_and_unknown_shapes() {
+        let f = chord_shape("F").unwrap();
+        assert_eq!(f.pattern(), "133211");
+        assert!(f.is_barre());
+
+        assert_eq!(chord_shape("H7"), None);
+        assert_eq!(chord_shape(""), None);
+    }
+}
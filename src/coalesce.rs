@@ -0,0 +1,105 @@
+// Coalescing for re-entrant updates: a request made while a pass is running isn't
+// dropped or run nested, it's remembered and run once the current pass is over
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const IDLE: u8 = 0;
+const RUNNING: u8 = 1;
+// Running, and asked for again since the pass began
+const DIRTY: u8 = 2;
+
+pub struct CoalescedUpdate {
+    state: AtomicU8,
+}
+
+impl CoalescedUpdate {
+    pub const fn new() -> Self {
+        CoalescedUpdate { state: AtomicU8::new(IDLE) }
+    }
+
+    // Run `pass`, then once more if it was asked for again while running (however
+    // many times). Asked from inside a pass, it only marks the update dirty and
+    // returns false without running anything.
+    pub fn run(&self, mut pass: impl FnMut()) -> bool {
+        loop {
+            match self.state.compare_exchange(IDLE, RUNNING, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(IDLE) => continue,
+                Err(_) => {
+                    // Still running (or already dirty): leave the follow-up to that pass.
+                    // If it finished in the meantime, take the update on here instead.
+                    if self.state.compare_exchange(RUNNING, DIRTY, Ordering::SeqCst, Ordering::SeqCst)
+                        .map_or_else(|current| current == DIRTY, |_| true)
+                    {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        // Back to idle even if a pass panics, so later updates aren't locked out
+        let _guard = IdleOnDrop(&self.state);
+        loop {
+            pass();
+            if self.state.compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                return true;
+            }
+            // Asked for again during the pass: clear the mark and go round once more
+            self.state.store(RUNNING, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Default for CoalescedUpdate {
+    fn default() -> Self {
+        CoalescedUpdate::new()
+    }
+}
+
+struct IdleOnDrop<'a>(&'a AtomicU8);
+
+impl Drop for IdleOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.store(IDLE, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_request_during_pass_runs_once_more() {
+        let update = CoalescedUpdate::new();
+        let passes = Cell::new(0);
+        let ran = update.run(|| {
+            passes.set(passes.get() + 1);
+            // Several requests during the first pass still make just one follow-up
+            if passes.get() == 1 {
+                assert!(!update.run(|| unreachable!()));
+                assert!(!update.run(|| unreachable!()));
+            }
+        });
+        assert!(ran);
+        assert_eq!(passes.get(), 2);
+
+        // Once done, the next request runs straight away, and just once
+        assert!(update.run(|| passes.set(passes.get() + 1)));
+        assert_eq!(passes.get(), 3);
+    }
+
+    #[test]
+    fn test_panicking_pass_releases_update() {
+        let update = CoalescedUpdate::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            update.run(|| panic!("pass failed"));
+        }));
+        assert!(result.is_err());
+
+        // Not left marked as running: the next request gets its pass
+        let mut passes = 0;
+        assert!(update.run(|| passes += 1));
+        assert_eq!(passes, 1);
+    }
+}
@@ -0,0 +1,175 @@
+// Fretboard diagrams as SVG, for pasting scale shapes into practice notes
+
+use crate::music_theory::{self, Key, MarkerKind, MarkerScheme, Scale, Tuning};
+use std::fmt::Write;
+
+const FRET_WIDTH: u32 = 40;
+const STRING_SPACING: u32 = 24;
+// Room around the neck, and left of the nut for open-string dots
+const MARGIN: u32 = 30;
+const NOTE_RADIUS: u32 = 9;
+const MARKER_RADIUS: u32 = 4;
+
+// Draw the key's scale across the neck from the nut to the given fret, in tab
+// orientation (highest string at the top). In-scale notes are dots labelled with
+// their names, filled in for the root; inlays follow the standard dot scheme.
+pub fn export_fretboard_svg(key: Key, scale: Scale, tuning: &Tuning, frets: u8) -> String {
+    let strings = tuning.strings.len() as u32;
+    let neck_width = FRET_WIDTH * frets as u32;
+    let neck_height = STRING_SPACING * strings.saturating_sub(1);
+    let width = neck_width + 2 * MARGIN;
+    let height = neck_height + 2 * MARGIN;
+    let nut_x = MARGIN;
+    // Highest string at the top, as in tab
+    let string_y = |string: u32| MARGIN + (strings - 1 - string) * STRING_SPACING;
+    // Notes sit in the middle of their fret; open strings sit left of the nut
+    let note_x = |fret: u32| if fret == 0 { nut_x - FRET_WIDTH / 2 + 5 } else { nut_x + fret * FRET_WIDTH - FRET_WIDTH / 2 };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="9">"#,
+        w = width,
+        h = height
+    );
+    let _ = writeln!(svg, r#"<title>{} {}</title>"#, key.name(), scale.name());
+    let _ = writeln!(svg, r##"<rect width="{}" height="{}" fill="#1E1F24"/>"##, width, height);
+
+    // Inlays between the middle strings; octave frets get two
+    let middle_y = MARGIN + neck_height / 2;
+    for fret in music_theory::get_marked_frets(&MarkerScheme::Standard) {
+        if fret == 0 || fret > frets {
+            continue;
+        }
+        let x = note_x(fret as u32);
+        let ys = if music_theory::fret_marker_kind(fret) == MarkerKind::Double && strings > 2 {
+            vec![middle_y - STRING_SPACING, middle_y + STRING_SPACING]
+        } else {
+            vec![middle_y]
+        };
+        for y in ys {
+            let _ = writeln!(svg, r##"<circle class="marker" cx="{}" cy="{}" r="{}" fill="#9AA0A6"/>"##, x, y, MARKER_RADIUS);
+        }
+    }
+
+    // Nut, frets and strings
+    let _ = writeln!(
+        svg,
+        r##"<line x1="{x}" y1="{top}" x2="{x}" y2="{bottom}" stroke="#E6E6E6" stroke-width="4"/>"##,
+        x = nut_x,
+        top = MARGIN,
+        bottom = MARGIN + neck_height
+    );
+    for fret in 1..=frets as u32 {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x}" y1="{top}" x2="{x}" y2="{bottom}" stroke="#4A4D57" stroke-width="2"/>"##,
+            x = nut_x + fret * FRET_WIDTH,
+            top = MARGIN,
+            bottom = MARGIN + neck_height
+        );
+    }
+    for string in 0..strings {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="#9AA0A6" stroke-width="1"/>"##,
+            nut_x,
+            nut_x + neck_width,
+            y = string_y(string)
+        );
+    }
+
+    // Scale notes, the same way the board works them out
+    for string in 0..strings {
+        for fret in 0..=frets {
+            let note = music_theory::get_note_at_position(string as u8, fret, tuning);
+            if !music_theory::is_note_in_scale(note, key, scale) {
+                continue;
+            }
+            let is_root = music_theory::is_root_note(note, key);
+            let (fill, text) = if is_root { ("#5CC8FF", "#1E1F24") } else { ("#1E1F24", "#E6E6E6") };
+            let (x, y) = (note_x(fret as u32), string_y(string));
+            let _ = writeln!(
+                svg,
+                r##"<circle class="note" cx="{}" cy="{}" r="{}" fill="{}" stroke="#5CC8FF" stroke-width="1.5"/>"##,
+                x, y, NOTE_RADIUS, fill
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" fill="{}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                x,
+                y,
+                text,
+                note.note.name_in_key(key)
+            );
+        }
+    }
+
+    // Fret numbers under the marked frets
+    for fret in music_theory::get_marked_frets(&MarkerScheme::Standard) {
+        if fret == 0 || fret > frets {
+            continue;
+        }
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#9AA0A6" text-anchor="middle">{}</text>"##,
+            note_x(fret as u32),
+            height - MARGIN / 3,
+            fret
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every tag is closed in order, and nothing but whitespace follows the root
+    fn assert_well_formed(xml: &str) {
+        let mut open: Vec<&str> = Vec::new();
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..].find('>').expect("unterminated tag") + start;
+            let tag = &rest[start + 1..end];
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "mismatched </{}>", name);
+            } else if !tag.ends_with('/') {
+                open.push(tag.split_whitespace().next().unwrap());
+            }
+            rest = &rest[end + 1..];
+            if open.is_empty() {
+                assert!(rest.trim().is_empty(), "content after the root element");
+            }
+        }
+        assert!(open.is_empty(), "unclosed tags: {:?}", open);
+    }
+
+    #[test]
+    fn test_c_major_diagram() {
+        let svg = export_fretboard_svg(Key::C, Scale::Major, &Tuning::STANDARD, 12);
+        assert!(svg.starts_with("<svg"));
+        assert_well_formed(&svg);
+
+        // Every open string of standard tuning is in C major, so each string has
+        // 8 scale notes from the nut to the 12th fret; plus 4 single and 1 double inlay
+        assert_eq!(svg.matches(r#"<circle class="note""#).count(), 48);
+        assert_eq!(svg.matches(r#"<circle class="marker""#).count(), 6);
+        assert_eq!(svg.matches("<circle").count(), 54);
+        // Only the Cs are filled: one per string
+        assert_eq!(svg.matches(r##"fill="#5CC8FF" stroke"##).count(), 6);
+    }
+
+    #[test]
+    fn test_diagram_follows_tuning_and_length() {
+        let bass = music_theory::Instrument::Bass4.standard_tuning();
+        let svg = export_fretboard_svg(Key::E, Scale::MinorPentatonic, &bass, 5);
+        assert_well_formed(&svg);
+        // Four strings, five frets, nut
+        assert_eq!(svg.matches("<line").count(), 4 + 5 + 1);
+        // E minor pentatonic on E A D G: frets 0 3 5 / 0 2 5 / 0 2 5 / 0 2 5
+        assert_eq!(svg.matches(r#"<circle class="note""#).count(), 12);
+    }
+}
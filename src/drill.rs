@@ -0,0 +1,173 @@
+// Note-finding drill: the app picks a string and fret, the player types the note's
+// name, and the drill keeps score
+
+use crate::music_theory::{self, Note, Tuning};
+use crate::rng::Xorshift;
+use std::ops::RangeInclusive;
+
+// One question: the position asked about and the note it sounds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrillQuestion {
+    pub string: u8,
+    pub fret: u8,
+    pub note: Note,
+}
+
+// How an answer went, with the note it should have been for the feedback
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrillAnswer {
+    pub correct: bool,
+    pub note: Note,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrillStats {
+    pub asked: u32,
+    pub correct: u32,
+    // Right answers in a row, and the longest run so far (kept across sessions)
+    pub streak: u32,
+    pub best_streak: u32,
+}
+
+// Whether a typed name is the note, in any spelling: "A#" and "Bb" both answer A#.
+// Only the letter and accidental count; an octave, if typed, is ignored.
+pub fn is_correct(answer: &str, note: Note) -> bool {
+    Note::parse(answer).is_some_and(|typed| typed.note == note.note)
+}
+
+pub struct NoteDrill {
+    rng: Xorshift,
+    question: Option<DrillQuestion>,
+    stats: DrillStats,
+}
+
+impl NoteDrill {
+    pub fn new(seed: u32) -> Self {
+        NoteDrill {
+            rng: Xorshift::new(seed),
+            question: None,
+            stats: DrillStats::default(),
+        }
+    }
+
+    // Seeded from the clock, so each run asks about different positions
+    pub fn from_clock() -> Self {
+        NoteDrill { rng: Xorshift::from_clock(), ..NoteDrill::new(1) }
+    }
+
+    // Pick a string of the tuning and a fret in the range, not the position just asked.
+    // Frets behind the capo (0 = none) are skipped, and the note is the capoed one.
+    pub fn next_question(&mut self, tuning: &Tuning, frets: RangeInclusive<u8>, capo: u8) -> DrillQuestion {
+        let strings = tuning.strings.len().max(1) as u32;
+        let low = (*frets.start()).max(capo);
+        let high = (*frets.end()).max(low);
+        let span = (high - low) as u32 + 1;
+        let pick = |drill: &mut NoteDrill| {
+            let string = drill.rng.next_below(strings) as u8;
+            let fret = low + drill.rng.next_below(span) as u8;
+            (string, fret)
+        };
+        let mut position = pick(self);
+        // One re-roll is enough to make a repeat rare without looping on a one-fret range
+        if self.question.map(|q| (q.string, q.fret)) == Some(position) {
+            position = pick(self);
+        }
+        let (string, fret) = position;
+        let question = DrillQuestion { string, fret, note: music_theory::get_note_at_position_with_capo(string, fret, tuning, capo) };
+        self.question = Some(question);
+        question
+    }
+
+    pub fn question(&self) -> Option<DrillQuestion> {
+        self.question
+    }
+
+    // Check an answer to the current question and score it; None if nothing was asked.
+    // The question is used up either way.
+    pub fn answer(&mut self, text: &str) -> Option<DrillAnswer> {
+        let question = self.question.take()?;
+        let correct = is_correct(text, question.note);
+        self.stats.asked += 1;
+        if correct {
+            self.stats.correct += 1;
+            self.stats.streak += 1;
+            self.stats.best_streak = self.stats.best_streak.max(self.stats.streak);
+        } else {
+            self.stats.streak = 0;
+        }
+        Some(DrillAnswer { correct, note: question.note })
+    }
+
+    pub fn stats(&self) -> DrillStats {
+        self.stats
+    }
+
+    // Carry over the best streak from an earlier session
+    pub fn set_best_streak(&mut self, best: u32) {
+        self.stats.best_streak = self.stats.best_streak.max(best);
+    }
+
+    // Start a new round: the score and streak go back to zero, the best streak stays
+    pub fn reset(&mut self) {
+        self.question = None;
+        self.stats = DrillStats { best_streak: self.stats.best_streak, ..DrillStats::default() };
+    }
+}
+
+impl Default for NoteDrill {
+    fn default() -> Self {
+        NoteDrill::from_clock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music_theory::Key;
+
+    #[test]
+    fn test_answers_accept_enharmonic_spellings() {
+        let a_sharp = Note::new(Key::As, 3);
+        assert!(is_correct("A#", a_sharp));
+        assert!(is_correct("Bb", a_sharp));
+        assert!(is_correct(" bb ", a_sharp));
+        assert!(is_correct("A#3", a_sharp));
+        assert!(!is_correct("B", a_sharp));
+        assert!(!is_correct("A", a_sharp));
+        assert!(!is_correct("", a_sharp));
+        assert!(!is_correct("H", a_sharp));
+
+        // Cb and B# name B and C
+        assert!(is_correct("Cb", Note::new(Key::B, 2)));
+        assert!(is_correct("B#", Note::new(Key::C, 3)));
+    }
+
+    #[test]
+    fn test_drill_keeps_score() {
+        let mut drill = NoteDrill::new(7);
+        assert_eq!(drill.answer("C"), None);
+
+        for _ in 0..3 {
+            let question = drill.next_question(&Tuning::STANDARD, 0..=12, 0);
+            assert!(question.fret <= 12 && question.string < 6);
+            assert_eq!(question.note, music_theory::get_note_at_position(question.string, question.fret, &Tuning::STANDARD));
+            let answer = drill.answer(question.note.note.flat_name()).unwrap();
+            assert!(answer.correct);
+        }
+        assert_eq!(drill.stats(), DrillStats { asked: 3, correct: 3, streak: 3, best_streak: 3 });
+
+        // A wrong answer ends the streak but not the best one
+        let question = drill.next_question(&Tuning::STANDARD, 5..=5, 0);
+        assert_eq!(question.fret, 5);
+        let wrong = question.note.transpose(1).note.name();
+        let answer = drill.answer(wrong).unwrap();
+        assert_eq!(answer, DrillAnswer { correct: false, note: question.note });
+        assert_eq!(drill.stats(), DrillStats { asked: 4, correct: 3, streak: 0, best_streak: 3 });
+
+        // A saved best carries over, and a new round keeps it
+        drill.set_best_streak(10);
+        drill.reset();
+        assert_eq!(drill.stats(), DrillStats { best_streak: 10, ..DrillStats::default() });
+        assert_eq!(drill.question(), None);
+    }
+}
@@ -0,0 +1,201 @@
+// Fret click handling: double-click detection, configurable actions and the click mode
+
+use crate::music_theory;
+use std::time::{Duration, Instant};
+
+// Two clicks on the same fret closer together than this count as a double click
+pub const DOUBLE_CLICK_THRESHOLD: Duration = Duration::from_millis(350);
+
+// How long a note rings when the "sustain" double-click action is selected
+pub const SUSTAIN_DURATION: Duration = Duration::from_millis(2000);
+
+// How long the "bend up" double-click action takes to reach the bent pitch
+pub const BEND_DURATION: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoubleClickAction {
+    Retrigger,
+    OctaveUp,
+    Sustain,
+    AddToNoteSet,
+    BendUp,
+}
+
+impl DoubleClickAction {
+    // Number of actions; tags run 0..COUNT
+    pub const COUNT: i32 = 5;
+
+    pub fn from_int(value: i32) -> DoubleClickAction {
+        match value {
+            1 => DoubleClickAction::OctaveUp,
+            2 => DoubleClickAction::Sustain,
+            3 => DoubleClickAction::AddToNoteSet,
+            4 => DoubleClickAction::BendUp,
+            _ => DoubleClickAction::Retrigger,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            DoubleClickAction::Retrigger => 0,
+            DoubleClickAction::OctaveUp => 1,
+            DoubleClickAction::Sustain => 2,
+            DoubleClickAction::AddToNoteSet => 3,
+            DoubleClickAction::BendUp => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DoubleClickAction::Retrigger => "Retrigger",
+            DoubleClickAction::OctaveUp => "Octave up",
+            DoubleClickAction::Sustain => "Sustain",
+            DoubleClickAction::AddToNoteSet => "Add to note set",
+            DoubleClickAction::BendUp => "Bend up",
+        }
+    }
+
+    // Cycle to the next action (used by the settings button)
+    pub fn next(self) -> DoubleClickAction {
+        DoubleClickAction::from_int((self.to_int() + 1) % DoubleClickAction::COUNT)
+    }
+}
+
+// What pressing a fret does: play a note of the set length, or hold the note
+// for as long as the fret is held down, like actually fretting it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClickMode {
+    Click,
+    Hold,
+}
+
+impl ClickMode {
+    pub fn from_int(value: i32) -> ClickMode {
+        match value {
+            1 => ClickMode::Hold,
+            _ => ClickMode::Click,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            ClickMode::Click => 0,
+            ClickMode::Hold => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClickMode::Click => "Click",
+            ClickMode::Hold => "Hold",
+        }
+    }
+
+    pub fn next(self) -> ClickMode {
+        ClickMode::from_int((self.to_int() + 1) % 2)
+    }
+}
+
+pub struct DoubleClickDetector {
+    threshold: Duration,
+    last_click: Option<(i32, i32, Instant)>,
+}
+
+impl DoubleClickDetector {
+    pub fn new(threshold: Duration) -> Self {
+        DoubleClickDetector {
+            threshold,
+            last_click: None,
+        }
+    }
+
+    // Register a click and return true if it completes a double click on the same fret
+    pub fn register(&mut self, string: i32, fret: i32, now: Instant) -> bool {
+        let is_double = match self.last_click {
+            Some((last_string, last_fret, at)) => {
+                last_string == string && last_fret == fret && now.duration_since(at) <= self.threshold
+            }
+            None => false,
+        };
+
+        // A completed double click starts over, so a third click isn't another double
+        self.last_click = if is_double { None } else { Some((string, fret, now)) };
+        is_double
+    }
+}
+
+impl Default for DoubleClickDetector {
+    fn default() -> Self {
+        Self::new(DOUBLE_CLICK_THRESHOLD)
+    }
+}
+
+// Frequency one octave above the given frequency
+pub fn octave_up(frequency: f32) -> f32 {
+    frequency * 2.0
+}
+
+// Where a bend up lands: a whole step (two semitones) above
+pub fn whole_step_up(frequency: f32) -> f32 {
+    music_theory::apply_cents(frequency, 200.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clicks_within_threshold_are_double() {
+        let mut detector = DoubleClickDetector::default();
+        let start = Instant::now();
+        assert!(!detector.register(0, 5, start));
+        assert!(detector.register(0, 5, start + Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_spaced_clicks_are_not_double() {
+        let mut detector = DoubleClickDetector::default();
+        let start = Instant::now();
+        assert!(!detector.register(0, 5, start));
+        assert!(!detector.register(0, 5, start + Duration::from_millis(800)));
+    }
+
+    #[test]
+    fn test_clicks_on_different_frets_are_not_double() {
+        let mut detector = DoubleClickDetector::default();
+        let start = Instant::now();
+        assert!(!detector.register(0, 5, start));
+        assert!(!detector.register(1, 5, start + Duration::from_millis(100)));
+        assert!(!detector.register(1, 6, start + Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_triple_click_is_one_double() {
+        let mut detector = DoubleClickDetector::default();
+        let start = Instant::now();
+        assert!(!detector.register(2, 3, start));
+        assert!(detector.register(2, 3, start + Duration::from_millis(100)));
+        assert!(!detector.register(2, 3, start + Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_octave_up() {
+        assert_eq!(octave_up(440.0), 880.0);
+        assert_eq!(octave_up(82.41), 164.82);
+        // A2 bent up a whole step reaches B2
+        assert!((whole_step_up(110.0) - 123.47).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_action_int_round_trip() {
+        for tag in 0..5 {
+            assert_eq!(DoubleClickAction::from_int(tag).to_int(), tag);
+        }
+        assert_eq!(DoubleClickAction::AddToNoteSet.next(), DoubleClickAction::BendUp);
+        assert_eq!(DoubleClickAction::BendUp.next(), DoubleClickAction::Retrigger);
+        for tag in 0..2 {
+            assert_eq!(ClickMode::from_int(tag).to_int(), tag);
+        }
+        assert_eq!(ClickMode::Hold.next(), ClickMode::Click);
+    }
+}
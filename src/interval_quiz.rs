@@ -0,0 +1,188 @@
+// Interval ear training: the app sounds two notes together, the player names the
+// interval between them, and the quiz keeps score for each interval
+
+use crate::music_theory::{self, Interval, Key, Note};
+use crate::rng::Xorshift;
+
+// Intervals asked about, smallest first; a unison isn't much of a question
+pub const QUIZ_INTERVALS: [Interval; 12] = [
+    Interval::MinorSecond,
+    Interval::MajorSecond,
+    Interval::MinorThird,
+    Interval::MajorThird,
+    Interval::PerfectFourth,
+    Interval::Tritone,
+    Interval::PerfectFifth,
+    Interval::MinorSixth,
+    Interval::MajorSixth,
+    Interval::MinorSeventh,
+    Interval::MajorSeventh,
+    Interval::Octave,
+];
+
+// Roots run from C3 up to two octaves above, so even an octave on top stays easy to hear
+const LOWEST_ROOT: Note = Note { note: Key::C, octave: 3 };
+const ROOT_SPAN: u32 = 24;
+
+// Two notes to play, the lower first, and the interval between them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntervalQuestion {
+    pub low: Note,
+    pub high: Note,
+    pub interval: Interval,
+}
+
+impl IntervalQuestion {
+    pub fn notes(&self) -> [Note; 2] {
+        [self.low, self.high]
+    }
+}
+
+// How an answer went, with the interval it should have been for the feedback
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntervalAnswer {
+    pub correct: bool,
+    pub interval: Interval,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IntervalScore {
+    pub asked: u32,
+    pub correct: u32,
+}
+
+impl IntervalScore {
+    // Share answered right, or None before the first question
+    pub fn accuracy(self) -> Option<f32> {
+        (self.asked > 0).then(|| self.correct as f32 / self.asked as f32)
+    }
+}
+
+pub struct IntervalQuiz {
+    rng: Xorshift,
+    question: Option<IntervalQuestion>,
+    // Indexed like QUIZ_INTERVALS
+    scores: [IntervalScore; 12],
+}
+
+impl IntervalQuiz {
+    pub fn new(seed: u32) -> Self {
+        IntervalQuiz {
+            rng: Xorshift::new(seed),
+            question: None,
+            scores: [IntervalScore::default(); 12],
+        }
+    }
+
+    // Seeded from the clock, so each run plays different intervals and roots
+    pub fn from_clock() -> Self {
+        IntervalQuiz { rng: Xorshift::from_clock(), ..IntervalQuiz::new(1) }
+    }
+
+    // A random interval on a random root
+    pub fn next_question(&mut self) -> IntervalQuestion {
+        let interval = QUIZ_INTERVALS[self.rng.next_below(QUIZ_INTERVALS.len() as u32) as usize];
+        let low = LOWEST_ROOT.transpose(self.rng.next_below(ROOT_SPAN + 1) as i32);
+        let high = low.transpose(interval.semitones());
+        let question = IntervalQuestion { low, high, interval: music_theory::interval_between(low, high) };
+        self.question = Some(question);
+        question
+    }
+
+    pub fn question(&self) -> Option<IntervalQuestion> {
+        self.question
+    }
+
+    // Check an answer to the current question and score it against the interval
+    // asked; None if nothing was asked. The question is used up either way.
+    pub fn answer(&mut self, answer: Interval) -> Option<IntervalAnswer> {
+        let question = self.question.take()?;
+        let correct = answer == music_theory::interval_between(question.low, question.high);
+        if let Some(score) = self.score_mut(question.interval) {
+            score.asked += 1;
+            score.correct += correct as u32;
+        }
+        Some(IntervalAnswer { correct, interval: question.interval })
+    }
+
+    fn score_mut(&mut self, interval: Interval) -> Option<&mut IntervalScore> {
+        let i = QUIZ_INTERVALS.iter().position(|&asked| asked == interval)?;
+        Some(&mut self.scores[i])
+    }
+
+    pub fn score(&self, interval: Interval) -> IntervalScore {
+        QUIZ_INTERVALS
+            .iter()
+            .position(|&asked| asked == interval)
+            .map_or(IntervalScore::default(), |i| self.scores[i])
+    }
+
+    // Every interval's score added up
+    pub fn total(&self) -> IntervalScore {
+        self.scores.iter().fold(IntervalScore::default(), |total, score| IntervalScore {
+            asked: total.asked + score.asked,
+            correct: total.correct + score.correct,
+        })
+    }
+
+    // The interval with the lowest accuracy of those asked so far, to practise next
+    pub fn weakest(&self) -> Option<Interval> {
+        QUIZ_INTERVALS
+            .iter()
+            .zip(self.scores.iter())
+            .filter_map(|(&interval, score)| Some((interval, score.accuracy()?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(interval, _)| interval)
+    }
+
+    pub fn reset(&mut self) {
+        self.question = None;
+        self.scores = [IntervalScore::default(); 12];
+    }
+}
+
+impl Default for IntervalQuiz {
+    fn default() -> Self {
+        IntervalQuiz::from_clock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_questions_match_interval_between() {
+        let mut quiz = IntervalQuiz::new(11);
+        for _ in 0..200 {
+            let question = quiz.next_question();
+            assert_eq!(question.interval, music_theory::interval_between(question.low, question.high));
+            assert!(QUIZ_INTERVALS.contains(&question.interval));
+            assert!(question.low.semitone_value() < question.high.semitone_value());
+            let lowest = LOWEST_ROOT.semitone_value();
+            assert!((lowest..=lowest + ROOT_SPAN as i32).contains(&question.low.semitone_value()));
+        }
+    }
+
+    #[test]
+    fn test_quiz_scores_each_interval() {
+        let mut quiz = IntervalQuiz::new(5);
+        assert_eq!(quiz.answer(Interval::PerfectFifth), None);
+
+        let first = quiz.next_question();
+        assert_eq!(quiz.answer(first.interval), Some(IntervalAnswer { correct: true, interval: first.interval }));
+        let second = quiz.next_question();
+        let wrong = if second.interval == Interval::Tritone { Interval::PerfectFifth } else { Interval::Tritone };
+        assert_eq!(quiz.answer(wrong), Some(IntervalAnswer { correct: false, interval: second.interval }));
+        assert_eq!(quiz.question(), None);
+
+        assert_eq!(quiz.total(), IntervalScore { asked: 2, correct: 1 });
+        assert_eq!(quiz.score(second.interval).accuracy().map(|a| a < 1.0), Some(true));
+        assert_eq!(quiz.weakest(), Some(second.interval));
+        assert_eq!(quiz.score(Interval::Unison).accuracy(), None);
+
+        quiz.reset();
+        assert_eq!(quiz.total(), IntervalScore::default());
+        assert_eq!(quiz.weakest(), None);
+    }
+}
@@ -1,5 +1,20 @@
+mod app_state;
 mod audio;
+mod chord_sheet;
+mod chords;
+mod coalesce;
+mod diagram;
+mod drill;
+mod fret_click;
+mod interval_quiz;
+mod metronome;
+mod midi;
 mod music_theory;
+mod notation;
+mod rng;
+mod self_check;
+mod session;
+mod tuner;
 
 // Minimal test module for diagnostics
 #[allow(dead_code)]
@@ -8,19 +23,25 @@ mod minimal_test;
 use anyhow::Result;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use slint::SharedString;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use slint::{Model, SharedString};
+use std::sync::atomic::{AtomicU32, Ordering};
 
-use music_theory::{Key, Scale};
+use app_state::{AppState, FretCell, PaneState, PixelRect, Settings, SharedState, Theme};
+use audio::NotePlayer;
+use coalesce::CoalescedUpdate;
+use fret_click::{ClickMode, DoubleClickAction};
+use metronome::{Metronome, RampEnd, Subdivision, TempoTrainer, TimeSignature};
+use music_theory::{ChordQuality, HarmonyInterval, Instrument, Key, MarkerScheme, Note, Scale, TuningPreset};
 
 slint::include_modules!();
 
+// Only the top-left pane is drawn today (see STACK_OVERFLOW_FIX.md); the others
+// keep their components in Settings.panes until swapped into it
 #[derive(Clone, Copy, Debug)]
 enum PaneId { TopLeft = 0, TopRight = 1, BottomLeft = 2, BottomRight = 3 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-enum ComponentKind { None, Metronome, ChordSheet, VideoPanel, Fretboard, Keys, Scales }
+enum ComponentKind { None, Metronome, ChordSheet, VideoPanel, Fretboard, Keys, Scales, Tuner }
 
 static COMPONENT_NAMES: Lazy<Vec<(ComponentKind, &'static str)>> = Lazy::new(|| {
     vec![
@@ -30,10 +51,37 @@ static COMPONENT_NAMES: Lazy<Vec<(ComponentKind, &'static str)>> = Lazy::new(||
         (ComponentKind::Fretboard, "Fretboard"),
         (ComponentKind::Keys, "Keys"),
         (ComponentKind::Scales, "Scales"),
+        (ComponentKind::Tuner, "Tuner"),
     ]
 });
 
-fn apply_component(app: &AppWindow, kind: ComponentKind) {
+// Keyboard shortcut digits: Ctrl+1 is the first entry of COMPONENT_NAMES (Metronome),
+// Ctrl+2 Chord Sheet, Ctrl+3 Video Panel, Ctrl+4 Fretboard, Ctrl+5 Keys, Ctrl+6 Scales,
+// Ctrl+7 Tuner. `key` is the text of the pressed key.
+fn component_for_shortcut(key: &str) -> Option<ComponentKind> {
+    let mut chars = key.chars();
+    let digit = chars.next()?.to_digit(10)? as usize;
+    if chars.next().is_some() {
+        return None;
+    }
+    let index = digit.checked_sub(1)?;
+    COMPONENT_NAMES.get(index).map(|(kind, _)| kind.clone())
+}
+
+// Pane changes are blocked while the layout is locked, except re-applying
+// the component that is already shown (which changes nothing)
+fn is_pane_change_permitted(layout_locked: bool, current_kind: i32, new_kind: i32) -> bool {
+    !layout_locked || current_kind == new_kind
+}
+
+// Returns false if the change was rejected because the layout is locked
+fn apply_component(app: &AppWindow, state: &mut AppState, kind: ComponentKind) -> bool {
+    let settings = &mut state.settings;
+    if !is_pane_change_permitted(settings.layout_locked, settings.main_kind, kind_to_tag(kind.clone())) {
+        eprintln!("[apply_component] Layout is locked, ignoring change to {:?}", kind);
+        return false;
+    }
+
     let title = match kind {
         ComponentKind::None => "",
         ComponentKind::Metronome => "Metronome",
@@ -42,18 +90,67 @@ fn apply_component(app: &AppWindow, kind: ComponentKind) {
         ComponentKind::Fretboard => "Fretboard",
         ComponentKind::Keys => "Keys",
         ComponentKind::Scales => "Scales",
+        ComponentKind::Tuner => "Tuner",
     };
     let title = SharedString::from(title);
     let kind_tag = kind_to_tag(kind.clone());
 
     eprintln!("[apply_component] Setting component: {:?} -> kind_tag: {}, title: {}", kind, kind_tag, title);
     
+    settings.main_kind = kind_tag;
     app.set_main_title(title);
     app.set_main_kind(kind_tag);
     
     eprintln!("[apply_component] After setting: main_kind = {}", app.get_main_kind());
     
-    let _ = save_layout(app);
+    let _ = save_layout(settings);
+    true
+}
+
+// Exchange what two panes hold and save the result. Swapping with the drawn pane
+// brings the other one's component, with its state, forward. Returns false if the
+// layout is locked.
+fn swap_panes(app: &AppWindow, state: &mut AppState, a: PaneId, b: PaneId) -> bool {
+    if state.settings.layout_locked {
+        eprintln!("[swap_panes] Layout is locked, ignoring swap of {:?} and {:?}", a, b);
+        return false;
+    }
+    let key = state.settings.key;
+    state.settings.swap_panes(a as usize, b as usize);
+    let kind = state.settings.main_kind;
+    app.set_main_kind(kind);
+    app.set_main_title(title_for(kind));
+    app.set_video_url(SharedString::from(state.settings.video_url.as_str()));
+    // A Fretboard brings its own key, scale and tuning
+    app.set_selected_key(state.settings.key.to_int());
+    app.set_selected_scale(state.settings.scale.to_int());
+    app.set_scale_position(state.settings.scale_position);
+    app.set_selected_tuning(state.settings.tuning.to_int());
+    if state.settings.key != key && state.drone {
+        state.set_drone(true);
+    }
+    if kind == kind_to_tag(ComponentKind::Fretboard) {
+        update_fret_data(app, state);
+    }
+    update_piano_keys(app, state);
+    // Release the microphone once the Tuner is no longer shown
+    if kind != kind_to_tag(ComponentKind::Tuner) && state.tuner.is_running() {
+        state.tuner.stop();
+        app.set_tuner_running(false);
+    }
+    let _ = save_layout(&state.settings);
+    true
+}
+
+// Alt+1..Alt+4 swap the drawn pane with pane 1-4 (TopLeft, TopRight, BottomLeft,
+// BottomRight); `key` is the text of the pressed key
+fn pane_for_shortcut(key: &str) -> Option<PaneId> {
+    let mut chars = key.chars();
+    let digit = chars.next()?.to_digit(10)? as i32;
+    if chars.next().is_some() || !(1..=app_state::PANE_COUNT as i32).contains(&digit) {
+        return None;
+    }
+    Some(pane_id_from(digit - 1))
 }
 
 fn kind_to_tag(kind: ComponentKind) -> i32 {
@@ -65,42 +162,737 @@ fn kind_to_tag(kind: ComponentKind) -> i32 {
         ComponentKind::Fretboard => 4,
         ComponentKind::Keys => 5,
         ComponentKind::Scales => 6,
+        ComponentKind::Tuner => 7,
     }
 }
 
 #[derive(Serialize, Deserialize, Default)]
 struct Layout {
+    #[serde(default)]
     main_kind: i32,
+    #[serde(default)]
+    layout_locked: bool,
+    // Per-pitch-class microtonal offsets in cents, indexed C..B (empty = 12-TET)
+    #[serde(default)]
+    note_cents: Vec<f32>,
+    #[serde(default)]
+    double_click_action: i32,
+    #[serde(default)]
+    click_mode: i32,
+    #[serde(default)]
+    marker_scheme: i32,
+    // Only used by the custom marker scheme
+    #[serde(default)]
+    custom_marker_frets: Vec<u8>,
+    #[serde(default)]
+    waveform: i32,
+    // Restored together so the board comes up on the right instrument and tuning
+    #[serde(default)]
+    instrument: i32,
+    #[serde(default)]
+    tuning: i32,
+    // 0 (missing) means the default fret count
+    #[serde(default)]
+    fret_count: u8,
+    // Missing means the whole neck
+    #[serde(default)]
+    fret_window: Option<(u8, u8)>,
+    #[serde(default = "default_volume")]
+    volume: f32,
+    // 0 (missing) means the default note duration
+    #[serde(default)]
+    note_duration_ms: u64,
+    #[serde(default)]
+    output_latency_ms: u32,
+    // 0 (missing) means concert pitch
+    #[serde(default)]
+    reference_a4: f32,
+    #[serde(default)]
+    left_handed: bool,
+    #[serde(default)]
+    capo: u8,
+    // 0 (missing) means unzoomed
+    #[serde(default)]
+    zoom: f32,
+    #[serde(default)]
+    video_url: String,
+    // Every pane with its component's settings, indexed like PaneId. Missing (older
+    // layouts) means only the drawn pane was saved, as main_kind, tuning and video_url.
+    #[serde(default)]
+    panes: Vec<PaneState>,
+    // Window geometry in physical pixels; a width of 0 (missing) means none was saved
+    #[serde(default)]
+    window_x: i32,
+    #[serde(default)]
+    window_y: i32,
+    #[serde(default)]
+    window_width: u32,
+    #[serde(default)]
+    window_height: u32,
+    // Missing (older layouts) means the dark theme
+    #[serde(default)]
+    theme: i32,
+    #[serde(default)]
+    drill_best_streak: u32,
 }
 
-fn layout_path() -> std::io::Result<std::path::PathBuf> {
+fn default_volume() -> f32 {
+    audio::DEFAULT_VOLUME
+}
+
+impl Layout {
+    fn from_settings(settings: &Settings) -> Self {
+        let note_cents = if settings.note_cents == music_theory::CentsOffsets::default() {
+            Vec::new()
+        } else {
+            settings.note_cents.cents.to_vec()
+        };
+        Layout {
+            main_kind: settings.main_kind,
+            layout_locked: settings.layout_locked,
+            note_cents,
+            double_click_action: settings.double_click_action.to_int(),
+            click_mode: settings.click_mode.to_int(),
+            marker_scheme: settings.marker_scheme.to_int(),
+            custom_marker_frets: match &settings.marker_scheme {
+                MarkerScheme::Custom(frets) => frets.clone(),
+                _ => Vec::new(),
+            },
+            waveform: settings.waveform.to_int(),
+            instrument: settings.instrument.to_int(),
+            tuning: settings.tuning.to_int(),
+            fret_count: settings.fret_count,
+            fret_window: settings.fret_window,
+            volume: settings.volume,
+            note_duration_ms: settings.note_duration_ms,
+            output_latency_ms: settings.output_latency_ms,
+            reference_a4: settings.reference_a4,
+            left_handed: settings.left_handed,
+            capo: settings.capo,
+            zoom: settings.zoom,
+            video_url: settings.video_url.clone(),
+            panes: settings.pane_states().to_vec(),
+            window_x: settings.window.map_or(0, |window| window.x),
+            window_y: settings.window.map_or(0, |window| window.y),
+            window_width: settings.window.map_or(0, |window| window.width),
+            window_height: settings.window.map_or(0, |window| window.height),
+            theme: settings.theme.to_int(),
+            drill_best_streak: settings.drill_best_streak,
+        }
+    }
+
+    fn apply_to(&self, settings: &mut Settings) {
+        // Older layouts have no panes, so the others start empty. The flat fields below
+        // win for the drawn pane; its saved entry only adds what they don't carry (a
+        // Fretboard's key and scale), and only if it still holds the same component.
+        let mut panes: [PaneState; app_state::PANE_COUNT] = Default::default();
+        for (pane, saved) in panes.iter_mut().zip(&self.panes) {
+            *pane = saved.clone();
+        }
+        if panes[app_state::MAIN_PANE].kind != self.main_kind {
+            panes[app_state::MAIN_PANE] = PaneState::default();
+        }
+        settings.set_pane_states(panes);
+        settings.main_kind = self.main_kind;
+        settings.video_url = self.video_url.clone();
+        settings.layout_locked = self.layout_locked;
+        for (i, cents) in self.note_cents.iter().take(12).enumerate() {
+            settings.note_cents.cents[i] = *cents;
+        }
+        settings.double_click_action = DoubleClickAction::from_int(self.double_click_action);
+        settings.click_mode = ClickMode::from_int(self.click_mode);
+        settings.marker_scheme = match MarkerScheme::from_int(self.marker_scheme) {
+            MarkerScheme::Custom(_) => MarkerScheme::Custom(self.custom_marker_frets.clone()),
+            scheme => scheme,
+        };
+        settings.waveform = audio::Waveform::from_int(self.waveform);
+        settings.instrument = Instrument::from_int(self.instrument);
+        settings.tuning = TuningPreset::from_int(self.tuning);
+        if self.fret_count != 0 {
+            settings.fret_count = app_state::clamp_fret_count(self.fret_count as i32);
+        }
+        settings.fret_window = self.fret_window;
+        settings.volume = audio::clamp_volume(self.volume);
+        if self.note_duration_ms != 0 {
+            settings.note_duration_ms = audio::clamp_note_duration_ms(self.note_duration_ms);
+        }
+        settings.output_latency_ms = metronome::clamp_output_latency_ms(self.output_latency_ms);
+        if self.reference_a4 != 0.0 {
+            settings.reference_a4 = music_theory::clamp_reference_a4(self.reference_a4);
+        }
+        settings.left_handed = self.left_handed;
+        settings.capo = app_state::clamp_capo(self.capo as i32);
+        if self.zoom != 0.0 {
+            settings.zoom = app_state::clamp_zoom(self.zoom);
+        }
+        settings.window = (self.window_width != 0 && self.window_height != 0).then_some(PixelRect {
+            x: self.window_x,
+            y: self.window_y,
+            width: self.window_width,
+            height: self.window_height,
+        });
+        settings.theme = Theme::from_int(self.theme);
+        // Loading older settings (e.g. a preset) never lowers the best streak
+        settings.drill_best_streak = settings.drill_best_streak.max(self.drill_best_streak);
+    }
+}
+
+// Per-user config directory: %APPDATA% on Windows, ~/.config (or $XDG_CONFIG_HOME) on Linux,
+// ~/Library/Application Support on macOS
+fn config_base_dir() -> std::io::Result<std::path::PathBuf> {
+    if let Some(dirs) = directories::BaseDirs::new() {
+        return Ok(dirs.config_dir().to_path_buf());
+    }
     let base = std::env::var("APPDATA").map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-    let dir = std::path::PathBuf::from(base).join("guitar-practice-dashboard");
+    Ok(std::path::PathBuf::from(base))
+}
+
+fn layout_path() -> std::io::Result<std::path::PathBuf> {
+    layout_path_in(&config_base_dir()?)
+}
+
+// layout.json in the app's folder under `base`, creating the folder if needed
+fn layout_path_in(base: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    let dir = base.join("guitar-practice-dashboard");
     std::fs::create_dir_all(&dir)?;
     Ok(dir.join("layout.json"))
 }
 
-fn save_layout(app: &AppWindow) -> std::io::Result<()> {
-    let layout = Layout {
-        main_kind: app.get_main_kind(),
-    };
+fn save_layout(settings: &Settings) -> std::io::Result<()> {
+    let layout = Layout::from_settings(settings);
     let path = layout_path()?;
     let data = serde_json::to_vec_pretty(&layout)
         .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Failed to serialize layout: {}", e)))?;
     std::fs::write(path, data)
 }
 
-fn load_layout(app: &AppWindow) -> std::io::Result<()> {
-    let path = layout_path()?;
-    if let Ok(bytes) = std::fs::read(path) {
-        if let Ok(layout) = serde_json::from_slice::<Layout>(&bytes) {
-            app.set_main_kind(layout.main_kind);
-            app.set_main_title(title_for(layout.main_kind));
+// What the first run shows, before any layout.json has been saved. There's one
+// pane today; it opens on the Fretboard rather than empty.
+fn default_layout() -> Layout {
+    Layout::from_settings(&Settings { main_kind: kind_to_tag(ComponentKind::Fretboard), ..Settings::default() })
+}
+
+fn load_layout(settings: &mut Settings) -> std::io::Result<()> {
+    load_layout_in(&layout_path()?, settings)
+}
+
+// A missing file is a first run and gets the default layout; one that can't be
+// read or parsed leaves the settings alone
+fn load_layout_in(path: &std::path::Path, settings: &mut Settings) -> std::io::Result<()> {
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            if let Ok(layout) = serde_json::from_slice::<Layout>(&bytes) {
+                layout.apply_to(settings);
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => default_layout().apply_to(settings),
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+fn window_geometry(app: &AppWindow) -> PixelRect {
+    let position = app.window().position();
+    let size = app.window().size();
+    PixelRect { x: position.x, y: position.y, width: size.width, height: size.height }
+}
+
+// Monitor areas as winit reports them; empty until the native window exists
+fn monitor_rects(app: &AppWindow) -> Vec<PixelRect> {
+    use slint::winit_030::WinitWindowAccessor;
+    app.window()
+        .with_winit_window(|window| {
+            window.available_monitors()
+                .map(|monitor| {
+                    let (position, size) = (monitor.position(), monitor.size());
+                    PixelRect { x: position.x, y: position.y, width: size.width, height: size.height }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Put the window back where it was, kept on a monitor that still exists
+fn restore_window_geometry(app: &AppWindow, saved: PixelRect) {
+    let screens = monitor_rects(app);
+    let geometry = app_state::clamp_to_screens(saved, &screens);
+    app.window().set_size(slint::PhysicalSize::new(geometry.width, geometry.height));
+    // Without monitors to check against, the saved position could be off-screen; leave it to the OS
+    if !screens.is_empty() {
+        app.window().set_position(slint::PhysicalPosition::new(geometry.x, geometry.y));
+    }
+}
+
+// Load a chord sheet file into the Chord Sheet pane; errors are shown in the pane
+fn open_chord_sheet(app: &AppWindow, state: &mut AppState, path: &str) {
+    let path = path.trim().trim_matches('"');
+    match std::fs::read_to_string(path) {
+        Ok(text) => {
+            state.chord_sheet = chord_sheet::parse(&text);
+            state.sheet_transpose = 0;
+            // Plain lyrics load fine but have nothing to transpose; say so
+            let chords = if state.chord_sheet.iter().any(|line| line.has_chords()) { "" } else { ", no chords" };
+            app.set_sheet_status(SharedString::from(format!("{} ({} lines{})", path, state.chord_sheet.len(), chords)));
+            render_chord_sheet(app, state);
         }
+        Err(e) => app.set_sheet_status(SharedString::from(format!("Could not open {}: {}", path, e))),
     }
+}
+
+// Show the loaded sheet at its current transposition
+fn render_chord_sheet(app: &AppWindow, state: &AppState) {
+    let lines: Vec<SheetLineData> = chord_sheet::transpose(&state.chord_sheet, state.sheet_transpose).iter()
+        .map(|line| {
+            let (chords, lyrics) = line.aligned_rows();
+            SheetLineData {
+                chords: SharedString::from(chords),
+                lyrics: SharedString::from(lyrics),
+                shapes: SharedString::from(line.shape_row()),
+            }
+        })
+        .collect();
+    app.set_sheet_transpose(state.sheet_transpose);
+    app.set_sheet_lines(slint::ModelRc::new(slint::VecModel::from(lines)));
+}
+
+// Hand a URL or file path to the system's default handler (browser, video player)
+fn open_in_system(target: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    let mut command = std::process::Command::new("explorer");
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = std::process::Command::new("xdg-open");
+    command.arg(target).spawn().map(|_| ())
+}
+
+// Exported diagrams go in exports/ next to layout.json
+fn exports_dir() -> std::io::Result<std::path::PathBuf> {
+    let dir = config_base_dir()?.join("guitar-practice-dashboard").join("exports");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Write the current key and scale as an SVG diagram, with the scale on a staff
+// beside it; returns the diagram's file
+fn export_svg(settings: &Settings) -> std::io::Result<std::path::PathBuf> {
+    let svg = diagram::export_fretboard_svg(settings.key, settings.scale, &settings.current_tuning(), settings.fret_count);
+    let name = format!("{} {}", settings.key.name(), settings.scale.name()).replace('#', "sharp");
+    let dir = exports_dir()?;
+    let path = dir.join(format!("{}.svg", name));
+    std::fs::write(&path, svg)?;
+    let clef = notation::Clef::for_instrument(settings.instrument);
+    let staff = notation::export_staff_svg(settings.key, settings.scale, clef);
+    std::fs::write(dir.join(format!("{} staff.svg", name)), staff)?;
+    Ok(path)
+}
+
+// Write the recorded take as a MIDI file at the metronome tempo; returns the file
+fn export_recording(state: &AppState) -> std::io::Result<std::path::PathBuf> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = exports_dir()?.join(format!("recording {}.mid", stamp));
+    midi::export_midi(state.recorder.take(), &path, state.metronome.bpm() as f32)?;
+    Ok(path)
+}
+
+// Practice sessions are logged next to layout.json, one JSON object per line
+fn sessions_path() -> std::io::Result<std::path::PathBuf> {
+    let dir = config_base_dir()?.join("guitar-practice-dashboard");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join("sessions.jsonl"))
+}
+
+// Stop the practice timer and log the run it just timed, with what was being practiced
+fn end_practice_session(state: &mut AppState) -> std::io::Result<()> {
+    let Some(duration) = state.practice.stop(std::time::Instant::now()) else { return Ok(()) };
+    let entry = session::SessionEntry::new(std::time::SystemTime::now(), duration, state.settings.key.name(), state.settings.scale.name());
+    session::append_entry(&sessions_path()?, &entry)
+}
+
+fn practice_today_text() -> String {
+    let entries = sessions_path().map(|path| session::read_log(&path)).unwrap_or_default();
+    format!("Today: {}", session::format_duration(session::total_today(&entries, std::time::SystemTime::now())))
+}
+
+// Named presets live next to layout.json as presets/<name>.json
+fn presets_dir() -> std::io::Result<std::path::PathBuf> {
+    let dir = config_base_dir()?.join("guitar-practice-dashboard").join("presets");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Make a preset name safe to use as a file name; None if nothing usable is left
+fn preset_file_stem(name: &str) -> Option<String> {
+    let stem: String = name.trim()
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) { '_' } else { c })
+        .collect();
+    let stem = stem.trim_matches('.').to_string();
+    if stem.is_empty() { None } else { Some(stem) }
+}
+
+fn preset_path_in(dir: &std::path::Path, name: &str) -> std::io::Result<std::path::PathBuf> {
+    let stem = preset_file_stem(name)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid preset name: {:?}", name)))?;
+    Ok(dir.join(format!("{}.json", stem)))
+}
+
+fn save_preset_in(dir: &std::path::Path, settings: &Settings, name: &str) -> std::io::Result<()> {
+    let path = preset_path_in(dir, name)?;
+    let data = serde_json::to_vec_pretty(&Layout::from_settings(settings))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Failed to serialize preset: {}", e)))?;
+    std::fs::write(path, data)
+}
+
+fn load_preset_in(dir: &std::path::Path, settings: &mut Settings, name: &str) -> std::io::Result<()> {
+    let bytes = std::fs::read(preset_path_in(dir, name)?)?;
+    let layout = serde_json::from_slice::<Layout>(&bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Failed to parse preset: {}", e)))?;
+    layout.apply_to(settings);
     Ok(())
 }
 
+fn list_presets_in(dir: &std::path::Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+// Deleting a preset that doesn't exist is not an error
+fn delete_preset_in(dir: &std::path::Path, name: &str) -> std::io::Result<()> {
+    match std::fs::remove_file(preset_path_in(dir, name)?) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn save_preset(settings: &Settings, name: &str) -> std::io::Result<()> {
+    save_preset_in(&presets_dir()?, settings, name)
+}
+
+fn load_preset(settings: &mut Settings, name: &str) -> std::io::Result<()> {
+    load_preset_in(&presets_dir()?, settings, name)
+}
+
+// Bumped whenever the settings backup changes shape; migrate_settings_bundle
+// brings older documents up to this version
+const SETTINGS_BUNDLE_VERSION: u64 = 1;
+
+// Metronome setup carried in the settings backup (layout.json doesn't keep it)
+#[derive(Serialize, Deserialize)]
+struct MetronomeDefaults {
+    bpm: u32,
+    beats_per_bar: u32,
+    beat_unit: u32,
+    subdivision: i32,
+    swing_ratio: f32,
+    accented_beats: Vec<u32>,
+    count_in_bars: u32,
+    click_sound: i32,
+    // Missing from bundles saved before visual-only mode
+    #[serde(default)]
+    silent: bool,
+}
+
+impl MetronomeDefaults {
+    fn from_metronome(metronome: &Metronome) -> Self {
+        let time_signature = metronome.time_signature();
+        MetronomeDefaults {
+            bpm: metronome.bpm(),
+            beats_per_bar: time_signature.beats_per_bar,
+            beat_unit: time_signature.beat_unit,
+            subdivision: metronome.subdivision().to_int(),
+            swing_ratio: metronome.swing_ratio(),
+            accented_beats: metronome.accented_beats().to_vec(),
+            count_in_bars: metronome.count_in_bars(),
+            click_sound: metronome.click_sound().to_int(),
+            silent: metronome.is_silent(),
+        }
+    }
+
+    fn apply_to(&self, metronome: &mut Metronome) {
+        metronome.set_tempo(self.bpm);
+        metronome.set_time_signature(TimeSignature::new(self.beats_per_bar, self.beat_unit));
+        metronome.set_subdivision(Subdivision::from_int(self.subdivision));
+        metronome.set_swing_ratio(self.swing_ratio);
+        metronome.set_accented_beats(self.accented_beats.clone());
+        metronome.set_count_in_bars(self.count_in_bars);
+        metronome.set_click_sound(audio::ClickSound::from_int(self.click_sound));
+        metronome.set_silent(self.silent);
+    }
+}
+
+// Every saved preset by name; presets that can't be read are left out
+fn read_presets_in(dir: &std::path::Path) -> serde_json::Map<String, serde_json::Value> {
+    list_presets_in(dir)
+        .into_iter()
+        .filter_map(|name| {
+            let bytes = std::fs::read(preset_path_in(dir, &name).ok()?).ok()?;
+            Some((name, serde_json::from_slice(&bytes).ok()?))
+        })
+        .collect()
+}
+
+// The settings backup: layout, key and scale, metronome setup and saved presets
+fn export_bundle(state: &AppState, presets: serde_json::Map<String, serde_json::Value>) -> serde_json::Value {
+    serde_json::json!({
+        "version": SETTINGS_BUNDLE_VERSION,
+        "layout": Layout::from_settings(&state.settings),
+        "key": state.settings.key.to_int(),
+        "scale": state.settings.scale.to_int(),
+        "metronome": MetronomeDefaults::from_metronome(&state.metronome),
+        "presets": presets,
+    })
+}
+
+fn export_all(state: &AppState) -> serde_json::Value {
+    let presets = presets_dir().map(|dir| read_presets_in(&dir)).unwrap_or_default();
+    export_bundle(state, presets)
+}
+
+// Bring an older backup up to SETTINGS_BUNDLE_VERSION. A document without a version
+// predates the backup format: it's a bare layout, i.e. layout.json or a preset file.
+fn migrate_settings_bundle(value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let serde_json::Value::Object(fields) = value else {
+        return Err("Settings file is not a JSON object".to_string());
+    };
+    if fields.contains_key("version") {
+        return Ok(serde_json::Value::Object(fields));
+    }
+    Ok(serde_json::json!({ "version": 1, "layout": fields }))
+}
+
+// Lay `incoming`'s fields over `current` one at a time. Missing fields keep their
+// current values; unknown fields and values of the wrong type are skipped with a
+// warning rather than failing the section.
+fn merge_fields<T>(current: &T, incoming: &serde_json::Value, section: &str, warnings: &mut Vec<String>) -> Option<T>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let Some(incoming) = incoming.as_object() else {
+        warnings.push(format!("Skipped {}: not an object", section));
+        return None;
+    };
+    let serde_json::Value::Object(mut merged) = serde_json::to_value(current).ok()? else { return None };
+    for (name, value) in incoming {
+        let Some(previous) = merged.get(name).cloned() else {
+            warnings.push(format!("Ignored unknown setting {}.{}", section, name));
+            continue;
+        };
+        merged.insert(name.clone(), value.clone());
+        if serde_json::from_value::<T>(serde_json::Value::Object(merged.clone())).is_err() {
+            warnings.push(format!("Ignored invalid setting {}.{}", section, name));
+            merged.insert(name.clone(), previous);
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(merged)).ok()
+}
+
+// Restore a settings backup into the state, writing its presets into `presets_dir`.
+// Returns warnings for whatever was skipped; only a document that isn't a settings
+// object at all is an error.
+fn import_bundle_in(presets_dir: &std::path::Path, state: &mut AppState, value: serde_json::Value) -> Result<Vec<String>, String> {
+    let bundle = migrate_settings_bundle(value)?;
+    let mut warnings = Vec::new();
+    let version = bundle["version"].as_u64().unwrap_or(0);
+    if version > SETTINGS_BUNDLE_VERSION {
+        warnings.push(format!("Settings file is version {}, newer than this app understands ({})", version, SETTINGS_BUNDLE_VERSION));
+    }
+
+    if let Some(incoming) = bundle.get("layout") {
+        if let Some(layout) = merge_fields(&Layout::from_settings(&state.settings), incoming, "layout", &mut warnings) {
+            layout.apply_to(&mut state.settings);
+        }
+    }
+    match bundle.get("key").map(|key| key.as_i64()) {
+        Some(Some(key)) if (0..12).contains(&key) => state.settings.key = Key::from_int(key as i32),
+        Some(_) => warnings.push("Ignored invalid setting key".to_string()),
+        None => {}
+    }
+    // Scale tags start at 1; anything that isn't one of them is ignored rather than read as Major
+    let known_scale = |tag: i64| Scale::ALL.into_iter().find(|scale| scale.to_int() as i64 == tag);
+    match bundle.get("scale").map(|scale| scale.as_i64().and_then(known_scale)) {
+        Some(Some(scale)) => state.settings.scale = scale,
+        Some(None) => warnings.push("Ignored invalid setting scale".to_string()),
+        None => {}
+    }
+    if let Some(incoming) = bundle.get("metronome") {
+        let current = MetronomeDefaults::from_metronome(&state.metronome);
+        if let Some(metronome) = merge_fields(&current, incoming, "metronome", &mut warnings) {
+            metronome.apply_to(&mut state.metronome);
+        }
+    }
+    if let Some(presets) = bundle.get("presets").and_then(|presets| presets.as_object()) {
+        for (name, incoming) in presets {
+            let defaults = Layout::from_settings(&Settings::default());
+            let Some(layout) = merge_fields(&defaults, incoming, &format!("presets.{}", name), &mut warnings) else { continue };
+            let mut settings = Settings::default();
+            layout.apply_to(&mut settings);
+            if let Err(e) = save_preset_in(presets_dir, &settings, name) {
+                warnings.push(format!("Could not save preset {:?}: {}", name, e));
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+// Restore a settings backup and show the result everywhere
+fn import_all(app: &AppWindow, state: &mut AppState, value: serde_json::Value) -> Result<Vec<String>, String> {
+    let dir = presets_dir().map_err(|e| format!("Could not open the presets folder: {}", e))?;
+    let warnings = import_bundle_in(&dir, state, value)?;
+    apply_loaded_settings(app, state);
+    update_piano_keys(app, state);
+    sync_metronome_to_ui(app, &state.metronome);
+    set_preset_names(app);
+    let _ = save_layout(&state.settings);
+    Ok(warnings)
+}
+
+// Written when the app exits. Unlike layout.json it also keeps the key, scale and
+// metronome setup, so the next run picks up where this one left off.
+fn last_settings_path() -> std::io::Result<std::path::PathBuf> {
+    let dir = config_base_dir()?.join("guitar-practice-dashboard");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join("last-settings.json"))
+}
+
+// The whole settings bundle, less the presets (they have their own folder)
+fn save_all_in(path: &std::path::Path, state: &AppState) -> std::io::Result<()> {
+    let bundle = export_bundle(state, serde_json::Map::new());
+    let data = serde_json::to_vec_pretty(&bundle)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Failed to serialize settings: {}", e)))?;
+    std::fs::write(path, data)
+}
+
+// Persist everything once the event loop is done, whether or not anything was changed
+fn save_all_on_exit(app: &AppWindow, state: &mut AppState) {
+    // Closing the window already recorded its geometry; quitting any other way leaves it up
+    if app.window().is_visible() {
+        state.settings.window = Some(window_geometry(app));
+    }
+    if let Err(e) = save_layout(&state.settings) {
+        eprintln!("[exit] Could not save layout: {}", e);
+    }
+    if let Err(e) = last_settings_path().and_then(|path| save_all_in(&path, state)) {
+        eprintln!("[exit] Could not save settings: {}", e);
+    }
+}
+
+// Restore what was saved at the last exit. The layout section is skipped: layout.json
+// is saved on every change, so it's never older.
+fn load_last_settings(state: &mut AppState) -> Result<Vec<String>, String> {
+    let path = last_settings_path().map_err(|e| e.to_string())?;
+    let Ok(bytes) = std::fs::read(path) else { return Ok(Vec::new()) };
+    let mut bundle: serde_json::Value = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    if let Some(fields) = bundle.as_object_mut() {
+        fields.remove("layout");
+    }
+    let dir = presets_dir().map_err(|e| e.to_string())?;
+    import_bundle_in(&dir, state, bundle)
+}
+
+fn list_presets() -> Vec<String> {
+    presets_dir().map(|dir| list_presets_in(&dir)).unwrap_or_default()
+}
+
+fn delete_preset(name: &str) -> std::io::Result<()> {
+    delete_preset_in(&presets_dir()?, name)
+}
+
+fn set_preset_names(app: &AppWindow) {
+    let names: Vec<SharedString> = list_presets().into_iter().map(SharedString::from).collect();
+    app.set_preset_names(slint::ModelRc::new(slint::VecModel::from(names)));
+}
+
+// Apply freshly loaded settings everywhere they're used
+fn apply_loaded_settings(app: &AppWindow, state: &mut AppState) {
+    state.apply_sound_settings();
+    state.metronome.set_volume(state.settings.volume);
+    state.metronome.set_output_latency(std::time::Duration::from_millis(state.settings.output_latency_ms as u64));
+    state.drill.set_best_streak(state.settings.drill_best_streak);
+    sync_settings_to_ui(app, &state.settings);
+    if state.settings.main_kind == kind_to_tag(ComponentKind::Fretboard) {
+        update_fret_data(app, state);
+    }
+}
+
+// Push every setting the UI displays into its Slint property
+fn sync_settings_to_ui(app: &AppWindow, settings: &Settings) {
+    app.set_main_kind(settings.main_kind);
+    app.set_main_title(title_for(settings.main_kind));
+    app.set_layout_locked(settings.layout_locked);
+    app.set_selected_key(settings.key.to_int());
+    app.set_selected_scale(settings.scale.to_int());
+    app.set_selected_instrument(settings.instrument.to_int());
+    app.set_selected_tuning(settings.tuning.to_int());
+    app.set_root_offset_frets(settings.root_offset_frets);
+    app.set_tension_detune(settings.tension_detune);
+    app.set_legato(settings.legato);
+    app.set_double_click_action_name(SharedString::from(settings.double_click_action.name()));
+    app.set_click_mode_name(SharedString::from(settings.click_mode.name()));
+    app.set_selected_marker_scheme(settings.marker_scheme.to_int());
+    app.set_custom_marker_text(custom_marker_text(&settings.marker_scheme));
+    app.set_waveform_name(SharedString::from(settings.waveform.name()));
+    app.set_focused_string(settings.focused_string);
+    app.set_show_degrees(settings.show_degrees);
+    app.set_octave_colors(settings.octave_colors);
+    app.set_fret_count(settings.fret_count as i32);
+    // The spin boxes keep their last window while it's off
+    app.set_fret_window_enabled(settings.fret_window.is_some());
+    if let Some((low, high)) = settings.fret_window {
+        app.set_fret_window_low(low as i32);
+        app.set_fret_window_high(high as i32);
+    }
+    app.set_volume(settings.volume);
+    app.set_note_duration_ms(settings.note_duration_ms as i32);
+    app.set_metronome_latency_ms(settings.output_latency_ms as i32);
+    app.set_reference_a4(settings.reference_a4.round() as i32);
+    app.set_note_cents(note_cents_model(&settings.note_cents));
+    app.set_left_handed(settings.left_handed);
+    app.set_capo(settings.capo as i32);
+    app.set_zoom(settings.zoom);
+    app.set_video_url(SharedString::from(settings.video_url.as_str()));
+    app.set_theme(settings.theme.to_int());
+}
+
+// Offsets rounded to whole cents for the tuner pane's spin box
+fn note_cents_model(offsets: &music_theory::CentsOffsets) -> slint::ModelRc<i32> {
+    let cents: Vec<i32> = offsets.cents.iter().map(|c| c.round() as i32).collect();
+    slint::ModelRc::new(slint::VecModel::from(cents))
+}
+
+fn custom_marker_text(scheme: &MarkerScheme) -> SharedString {
+    let frets: Vec<String> = music_theory::get_marked_frets(scheme).iter().map(|f| f.to_string()).collect();
+    SharedString::from(frets.join(", "))
+}
+
+// The note set, then the keys and scales it fits best
+fn note_set_text(notes: &[Note]) -> SharedString {
+    let names: Vec<String> = notes.iter().map(|n| n.name()).collect();
+    let fits: Vec<String> = music_theory::suggest_key_scale(notes).iter()
+        .take(3)
+        .map(|&(key, scale, score)| if score < 1.0 {
+            format!("{} {} ({:.0}%)", key.name(), scale.name(), score * 100.0)
+        } else {
+            format!("{} {}", key.name(), scale.name())
+        })
+        .collect();
+    if fits.is_empty() {
+        SharedString::from(names.join(" "))
+    } else {
+        SharedString::from(format!("{} - fits {}", names.join(" "), fits.join(", ")))
+    }
+}
+
 fn title_for(kind_tag: i32) -> SharedString {
     match kind_tag {
         1 => SharedString::from("Metronome"),
@@ -109,76 +901,188 @@ fn title_for(kind_tag: i32) -> SharedString {
         4 => SharedString::from("Fretboard"),
         5 => SharedString::from("Keys"),
         6 => SharedString::from("Scales"),
+        7 => SharedString::from("Tuner"),
         _ => SharedString::from(""),
     }
 }
 
-// Generate fret data for a specific string
-// Using 12 frets (0-11) to keep component count manageable
-// With only 1 pane now, we only create 1 Fretboard = 6 strings × 12 frets = 72 components (safe)
-const INITIAL_FRET_COUNT: u8 = 12;
+// Sound both notes of the current interval question together
+fn play_interval_question(state: &mut AppState) {
+    let frequencies = state.interval_question_frequencies();
+    state.audio.play_notes(&frequencies);
+}
 
-fn generate_string_data(string: i32, key: Key, scale: Scale) -> slint::ModelRc<FretData> {
-    let mut data = Vec::with_capacity(INITIAL_FRET_COUNT as usize);
-    
-    // Generate data for 12 frets (0-11)
-    for fret in 0..INITIAL_FRET_COUNT {
-        let note = music_theory::get_note_at_position(string as u8, fret);
-        let note_name = note.name();
-        let is_in_scale = music_theory::is_note_in_scale(note, key, scale);
-        
-        data.push(FretData {
-            string: string,
-            fret: fret as i32,
-            note_name: SharedString::from(note_name),
-            is_in_scale,
+// Show the drill's question on the board, with the prompt and score
+fn sync_drill_to_ui(app: &AppWindow, state: &AppState, last: Option<drill::DrillAnswer>) {
+    let question = state.drill.question();
+    app.set_drill_active(question.is_some());
+    app.set_drill_string(question.map_or(-1, |q| q.string as i32));
+    app.set_drill_fret(question.map_or(-1, |q| q.fret as i32));
+    app.set_drill_status(SharedString::from(state.drill_status(last)));
+}
+
+// Push the metronome's setup into the Metronome pane
+fn sync_metronome_to_ui(app: &AppWindow, metronome: &Metronome) {
+    app.set_metronome_bpm(metronome.bpm() as i32);
+    app.set_metronome_beats_per_bar(metronome.time_signature().beats_per_bar as i32);
+    app.set_metronome_beat_unit(metronome.time_signature().beat_unit as i32);
+    app.set_metronome_subdivision(metronome.subdivision().to_int());
+    app.set_metronome_swing_percent((metronome.swing_ratio() * 100.0).round() as i32);
+    app.set_metronome_count_in_bars(metronome.count_in_bars() as i32);
+    app.set_metronome_click_sound(metronome.click_sound().to_int());
+    app.set_metronome_silent(metronome.is_silent());
+    sync_metronome_accents(app, metronome);
+}
+
+// Show which beats of the bar are accented
+fn sync_metronome_accents(app: &AppWindow, metronome: &Metronome) {
+    let accents = metronome.accent_pattern();
+    app.set_metronome_accents(slint::ModelRc::new(slint::VecModel::from(accents)));
+}
+
+// Mark the piano in the Keys pane for the current key and scale
+fn update_piano_keys(app: &AppWindow, state: &AppState) {
+    let key = state.settings.key;
+    let keys: Vec<PianoKeyData> = app_state::piano_keys(key, state.settings.scale).iter()
+        .map(|piano_key| PianoKeyData {
+            note_name: SharedString::from(piano_key.note.name_in_key(key)),
+            black: piano_key.is_black,
+            white_index: piano_key.white_index as i32,
+            in_scale: piano_key.is_in_scale,
+            is_root: piano_key.is_root,
+        })
+        .collect();
+    app.set_piano_keys(slint::ModelRc::new(slint::VecModel::from(keys)));
+}
+
+// Convert a string's fret cells into the Slint model
+fn to_fret_model(cells: &[FretCell]) -> slint::ModelRc<FretData> {
+    let data: Vec<FretData> = cells.iter()
+        .map(|cell| FretData {
+            string: cell.string as i32,
+            fret: cell.fret as i32,
+            note_name: SharedString::from(cell.note_name.as_str()),
+            octave: cell.octave,
+            frequency: cell.frequency,
+            is_in_scale: cell.is_in_scale,
+            is_root: cell.is_root,
+            degree: SharedString::from(cell.degree.unwrap_or("")),
+            marker: cell.marker.to_int(),
+            dimmed: cell.dimmed,
+        })
+        .collect();
+    slint::ModelRc::new(slint::VecModel::from(data))
+}
+
+// Copy a cell into a row already in the model; returns whether anything changed.
+// Names are only replaced when they differ, so re-marking allocates nothing.
+fn update_fret_row(row: &mut FretData, cell: &FretCell) -> bool {
+    let degree = cell.degree.unwrap_or("");
+    let fields = (cell.string as i32, cell.fret as i32, cell.octave, cell.frequency, cell.is_in_scale, cell.is_root, cell.marker.to_int(), cell.dimmed);
+    let unchanged = (row.string, row.fret, row.octave, row.frequency, row.is_in_scale, row.is_root, row.marker, row.dimmed) == fields
+        && row.note_name.as_str() == cell.note_name
+        && row.degree.as_str() == degree;
+    if unchanged {
+        return false;
+    }
+    (row.string, row.fret, row.octave, row.frequency, row.is_in_scale, row.is_root, row.marker, row.dimmed) = fields;
+    if row.note_name.as_str() != cell.note_name {
+        row.note_name = SharedString::from(cell.note_name.as_str());
+    }
+    if row.degree.as_str() != degree {
+        row.degree = SharedString::from(degree);
+    }
+    true
+}
+
+// Write the board into the models on screen, touching only the cells that changed.
+// Returns false, leaving them alone, if their shape no longer fits (another string
+// count or neck length, or nothing shown yet) and new models are needed instead.
+fn refresh_fret_models(strings: &slint::ModelRc<StringRowData>, board: &[Vec<FretCell>], left_handed: bool) -> bool {
+    // Drawn top to bottom, mirrored in left-handed mode like display_rows
+    let drawn = |index: usize| if left_handed { &board[board.len() - 1 - index] } else { &board[index] };
+    let fits = strings.row_count() == board.len()
+        && (0..board.len()).all(|index| {
+            strings.row_data(index).is_some_and(|row| row.cells.row_count() == drawn(index).len())
         });
+    if !fits {
+        return false;
     }
-    
-    slint::ModelRc::new(slint::VecModel::from(data))
+    for index in 0..board.len() {
+        let Some(row) = strings.row_data(index) else { continue };
+        let cells = drawn(index);
+        for column in 0..cells.len() {
+            let cell = if left_handed { &cells[cells.len() - 1 - column] } else { &cells[column] };
+            if let Some(mut data) = row.cells.row_data(column) {
+                if update_fret_row(&mut data, cell) {
+                    row.cells.set_row_data(column, data);
+                }
+            }
+        }
+    }
+    true
 }
 
-// Update fret data when key or scale changes
-// Use a static flag to prevent infinite recursion
-static UPDATING_FRET_DATA: AtomicBool = AtomicBool::new(false);
+// Bumped each time the metronome starts or stops. A beat flash waits out the output
+// latency; one left over from an earlier run finds the count moved on and is dropped.
+static METRONOME_RUN: AtomicU32 = AtomicU32::new(0);
 
-fn update_fret_data(app: &AppWindow) {
-    // Prevent recursive updates - if we're already updating, just return
-    if UPDATING_FRET_DATA.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
-        eprintln!("Warning: Prevented recursive call to update_fret_data");
-        return;
+// Change the metronome's setup, restarting it if it was running so the change
+// applies from the next click
+fn restart_metronome_after(app: &AppWindow, state: &SharedState, change: impl FnOnce(&mut AppState)) {
+    let was_running = {
+        let mut state = state.borrow_mut();
+        change(&mut state);
+        let was_running = state.metronome.is_running();
+        state.metronome.stop();
+        was_running
+    };
+    if was_running {
+        app.invoke_metronome_toggled();
     }
-    
-    // Use defer-like pattern with a guard to ensure flag is reset
-    let _guard = FretDataUpdateGuard;
-    
-    let key = Key::from_int(app.get_selected_key());
-    let scale = Scale::from_int(app.get_selected_scale());
-    
-    // Generate all data first
-    let string_0 = generate_string_data(0, key, scale);
-    let string_1 = generate_string_data(1, key, scale);
-    let string_2 = generate_string_data(2, key, scale);
-    let string_3 = generate_string_data(3, key, scale);
-    let string_4 = generate_string_data(4, key, scale);
-    let string_5 = generate_string_data(5, key, scale);
-    
-    // Then set all properties at once to minimize property change notifications
-    app.set_string_0_data(string_0.into());
-    app.set_string_1_data(string_1.into());
-    app.set_string_2_data(string_2.into());
-    app.set_string_3_data(string_3.into());
-    app.set_string_4_data(string_4.into());
-    app.set_string_5_data(string_5.into());
 }
 
-// Guard to ensure the flag is reset even if we panic
-struct FretDataUpdateGuard;
+// Update fret data when key or scale changes
+// Setting the models can call back into here; such a request isn't run nested but
+// remembered, and the update runs once more when the current pass is done
+static FRET_DATA_UPDATE: CoalescedUpdate = CoalescedUpdate::new();
+
+fn update_fret_data(app: &AppWindow, state: &mut AppState) {
+    FRET_DATA_UPDATE.run(|| write_fret_data(app, state));
+}
+
+fn write_fret_data(app: &AppWindow, state: &mut AppState) {
+    // The board is cached in the state until key/scale/tuning change. Usually only
+    // the marking has changed and the rows on screen are updated where they stand;
+    // otherwise new rows are built, in the order they're drawn.
+    let left_handed = state.settings.left_handed;
+    if !refresh_fret_models(&app.get_strings(), state.board(), left_handed) {
+        let rows: Vec<StringRowData> = state.display_rows().iter()
+            .map(|cells| StringRowData { cells: to_fret_model(cells) })
+            .collect();
+        app.set_strings(slint::ModelRc::new(slint::VecModel::from(rows)));
+    }
+    if !state.pickers_changed() {
+        return;
+    }
 
-impl Drop for FretDataUpdateGuard {
-    fn drop(&mut self) {
-        UPDATING_FRET_DATA.store(false, Ordering::SeqCst);
+    let mut labels: Vec<SharedString> = state.string_labels().into_iter()
+        .map(SharedString::from)
+        .collect();
+    if state.settings.left_handed {
+        labels.reverse();
     }
+    let focus_options: Vec<SharedString> = state.focus_options().into_iter()
+        .map(SharedString::from)
+        .collect();
+    let position_options: Vec<SharedString> = state.position_options().into_iter()
+        .map(SharedString::from)
+        .collect();
+    
+    // Then set the rest at once to minimize property change notifications
+    app.set_string_labels(slint::ModelRc::new(slint::VecModel::from(labels)));
+    app.set_focus_options(slint::ModelRc::new(slint::VecModel::from(focus_options)));
+    app.set_position_options(slint::ModelRc::new(slint::VecModel::from(position_options)));
 }
 
 fn main() {
@@ -196,6 +1100,20 @@ fn main() {
         return;
     }
     
+    // Check music-theory invariants and exit (for CI smoke runs)
+    if std::env::var("SELF_CHECK").is_ok() {
+        eprintln!("[MODE] Running theory self-check...");
+        let failures = self_check::run();
+        for failure in &failures {
+            eprintln!("[SELF CHECK FAILED] {}", failure);
+        }
+        if !failures.is_empty() {
+            std::process::exit(1);
+        }
+        eprintln!("[SELF CHECK] ✓ All invariants hold");
+        return;
+    }
+    
     // Set up panic hook with enhanced logging
     std::panic::set_hook(Box::new(|panic_info| {
         eprintln!("========================================");
@@ -244,49 +1162,86 @@ fn run_app(disable_audio: bool, disable_layout: bool, disable_callbacks: bool) -
 
     // Audio initialization (optional)
     eprintln!("[STEP 3/10] Audio initialization...");
-    let audio_player: Option<Arc<audio::AudioPlayer>> = if disable_audio {
+    let audio_player: Box<dyn NotePlayer> = if disable_audio {
         eprintln!("[STEP 3/10] ⚠ Audio DISABLED by flag");
-        None
+        Box::new(audio::AudioPlayer::silent())
     } else {
         match audio::AudioPlayer::new() {
             Ok(player) => {
                 eprintln!("[STEP 3/10] ✓ Audio initialized");
-                Some(Arc::new(player))
+                Box::new(player)
             },
             Err(e) => {
                 eprintln!("[STEP 3/10] ⚠ Audio failed: {}", e);
-                None
+                Box::new(audio::AudioPlayer::silent())
             }
         }
     };
 
+    // All callbacks share this state instead of reading settings back out of the UI
+    let state: SharedState = AppState::new(audio_player).shared();
+    // Metronome runs its own timer thread; clicks are silent when audio is disabled
+    state.borrow_mut().metronome.set_click_enabled(!disable_audio);
+
     // Initialize string data - CRITICAL: Start with empty arrays
     // Slint creates components for ALL for-loops during initialization
     // Even empty arrays cause component tree creation, but empty is safer
     eprintln!("[STEP 4/10] Initializing string data arrays as empty...");
-    let empty_data: Vec<FretData> = Vec::new();
-    let empty_model: slint::ModelRc<FretData> = slint::ModelRc::new(slint::VecModel::from(empty_data));
-    app.set_string_0_data(empty_model.clone().into());
-    app.set_string_1_data(empty_model.clone().into());
-    app.set_string_2_data(empty_model.clone().into());
-    app.set_string_3_data(empty_model.clone().into());
-    app.set_string_4_data(empty_model.clone().into());
-    app.set_string_5_data(empty_model.into());
+    let empty_rows: Vec<StringRowData> = Vec::new();
+    app.set_strings(slint::ModelRc::new(slint::VecModel::from(empty_rows)));
     eprintln!("[STEP 4/10] ✓ Empty arrays initialized - no FretCells will be created");
-    
+
+    // Reference chord shapes for the Chord Sheet (static, set once)
+    let shape_lines: Vec<SharedString> = chords::CHORD_SHAPES.iter()
+        .map(|shape| {
+            let barre = if shape.is_barre() { "  barre" } else { "" };
+            SharedString::from(format!("{:<4} {}{}", shape.name, shape.pattern(), barre))
+        })
+        .collect();
+    app.set_chord_shapes(slint::ModelRc::new(slint::VecModel::from(shape_lines)));
+    app.set_practice_today(SharedString::from(practice_today_text()));
+    update_piano_keys(&app, &state.borrow());
+
     // Layout loading (optional)
     if disable_layout {
         eprintln!("[STEP 5/10] ⚠ Layout loading DISABLED by flag");
     } else {
         eprintln!("[STEP 5/10] Loading layout...");
-        let _ = load_layout(&app);
+        let mut state = state.borrow_mut();
+        let _ = load_layout(&mut state.settings);
+        match load_last_settings(&mut state) {
+            Ok(warnings) => warnings.iter().for_each(|warning| eprintln!("[STEP 5/10] {}", warning)),
+            Err(e) => eprintln!("[STEP 5/10] Could not read last settings: {}", e),
+        }
+        // Populates fret data too if the layout loaded with Fretboard active
+        apply_loaded_settings(&app, &mut state);
+        update_piano_keys(&app, &state);
+        sync_metronome_to_ui(&app, &state.metronome);
+        set_preset_names(&app);
         eprintln!("[STEP 5/10] ✓ Layout loaded");
-        
-        // If layout loaded with Fretboard active, populate fret data
-        if app.get_main_kind() == 4 {
-            eprintln!("[STEP 5/10] Fretboard detected in layout, populating fret data...");
-            update_fret_data(&app);
+    }
+
+    // Window geometry is saved with the layout when the window closes, and restored
+    // once the event loop is running (the native window and monitor list exist by then)
+    if !disable_layout {
+        if let Some(saved) = state.borrow().settings.window {
+            let app_weak = app.as_weak();
+            slint::Timer::single_shot(std::time::Duration::ZERO, move || {
+                if let Some(app) = app_weak.upgrade() {
+                    restore_window_geometry(&app, saved);
+                }
+            });
         }
+        let app_weak = app.as_weak();
+        let state = state.clone();
+        app.window().on_close_requested(move || {
+            if let Some(app) = app_weak.upgrade() {
+                let mut state = state.borrow_mut();
+                state.settings.window = Some(window_geometry(&app));
+                let _ = save_layout(&state.settings);
+            }
+            slint::CloseRequestResponse::HideWindow
+        });
     }
 
     // Callbacks setup (optional)
@@ -294,10 +1249,11 @@ fn run_app(disable_audio: bool, disable_layout: bool, disable_callbacks: bool) -
         eprintln!("[STEP 6/10] ⚠ Callbacks DISABLED by flag");
     } else {
         eprintln!("[STEP 6/10] Setting up callbacks...");
-        
+
         // Wire up add-component callback - this is called when user clicks a component button in the menu
         {
             let app_weak = app.as_weak();
+            let state = state.clone();
             app.on_add_component(move |kind| {
                 eprintln!("[on_add_component] Received kind: {}", kind);
                 if let Some(app) = app_weak.upgrade() {
@@ -309,14 +1265,21 @@ fn run_app(disable_audio: bool, disable_layout: bool, disable_callbacks: bool) -
                         4 => ComponentKind::Fretboard,
                         5 => ComponentKind::Keys,
                         6 => ComponentKind::Scales,
+                        7 => ComponentKind::Tuner,
                         _ => ComponentKind::None,
                     };
                     eprintln!("[on_add_component] Mapped to component_kind: {:?}", component_kind);
-                    apply_component(&app, component_kind);
+                    let mut state = state.borrow_mut();
+                    let applied = apply_component(&app, &mut state, component_kind);
                     // If Fretboard is being shown, ensure fret data is populated
-                    if kind == 4 {
+                    if applied && kind == 4 {
                         eprintln!("[on_add_component] Populating fret data for Fretboard...");
-                        update_fret_data(&app);
+                        update_fret_data(&app, &mut state);
+                    }
+                    // Release the microphone once the Tuner is no longer shown
+                    if applied && kind != 7 && state.tuner.is_running() {
+                        state.tuner.stop();
+                        app.set_tuner_running(false);
                     }
                 } else {
                     eprintln!("[on_add_component] ERROR: Could not upgrade app weak reference");
@@ -324,50 +1287,1237 @@ fn run_app(disable_audio: bool, disable_layout: bool, disable_callbacks: bool) -
             });
         }
 
-        // Wire up fretboard interactions
+        // Wire up pane focus, the component shortcuts (Ctrl+digit, see component_for_shortcut)
+        // and the pane swap shortcuts (Alt+digit, see pane_for_shortcut)
         {
-            let audio_player_opt = audio_player.clone();
-            app.on_fret_clicked(move |string, fret| {
-                let note = music_theory::get_note_at_position(string as u8, fret as u8);
-                let frequency = music_theory::calculate_frequency(note);
-                if let Some(ref audio_player) = audio_player_opt {
-                    audio_player.play_note(frequency);
-                }
+            let state = state.clone();
+            app.on_pane_focused(move |pane_id| {
+                state.borrow_mut().focused_pane = pane_id;
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            app.on_shortcut_pressed(move |key| {
+                let Some(kind) = component_for_shortcut(&key) else { return false };
+                let Some(app) = app_weak.upgrade() else { return false };
+                // Only the main pane exists today, so whichever is focused, that's where it goes
+                eprintln!("[shortcut] Ctrl+{} -> {:?}", key, kind);
+                // Same path as picking the component from the menu
+                app.invoke_add_component(kind_to_tag(kind));
+                true
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_pane_swap_pressed(move |key| {
+                let Some(pane) = pane_for_shortcut(&key) else { return false };
+                let Some(app) = app_weak.upgrade() else { return false };
+                eprintln!("[shortcut] Alt+{} -> swap {:?} with {:?}", key, PaneId::TopLeft, pane);
+                swap_panes(&app, &mut state.borrow_mut(), PaneId::TopLeft, pane);
+                true
             });
         }
 
-        // Wire up key selection
+        // Wire up named layout presets
         {
             let app_weak = app.as_weak();
-            app.on_key_selected(move |key_int| {
+            let state = state.clone();
+            app.on_preset_selected(move |name| {
                 if let Some(app) = app_weak.upgrade() {
-                    if app.get_selected_key() != key_int {
-                        app.set_selected_key(key_int);
-                        update_fret_data(&app);
+                    let mut state = state.borrow_mut();
+                    match load_preset(&mut state.settings, &name) {
+                        Ok(()) => {
+                            apply_loaded_settings(&app, &mut state);
+                            app.set_current_preset(name);
+                            let _ = save_layout(&state.settings);
+                        }
+                        Err(e) => eprintln!("[on_preset_selected] Could not load preset {:?}: {}", name, e),
                     }
                 }
             });
         }
-
-        // Wire up scale selection
         {
             let app_weak = app.as_weak();
-            app.on_scale_selected(move |scale_int| {
+            let state = state.clone();
+            app.on_preset_saved(move |name| {
                 if let Some(app) = app_weak.upgrade() {
-                    if app.get_selected_scale() != scale_int {
-                        app.set_selected_scale(scale_int);
-                        update_fret_data(&app);
+                    match save_preset(&state.borrow().settings, &name) {
+                        Ok(()) => {
+                            set_preset_names(&app);
+                            // Show the name as it was stored, after sanitizing
+                            app.set_current_preset(SharedString::from(preset_file_stem(&name).unwrap_or_default()));
+                        }
+                        Err(e) => eprintln!("[on_preset_saved] Could not save preset {:?}: {}", name, e),
                     }
                 }
             });
         }
-
-        
-        eprintln!("[STEP 6/10] ✓ Callbacks set up");
-    }
-
-    eprintln!("[STEP 7/10] All initialization complete");
-    eprintln!("[STEP 8/10] About to call app.run()...");
+        {
+            let app_weak = app.as_weak();
+            app.on_preset_deleted(move |name| {
+                if let Some(app) = app_weak.upgrade() {
+                    if let Err(e) = delete_preset(&name) {
+                        eprintln!("[on_preset_deleted] Could not delete preset {:?}: {}", name, e);
+                    }
+                    set_preset_names(&app);
+                    app.set_current_preset(SharedString::new());
+                }
+            });
+        }
+
+        // Wire up settings export / import
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_settings_exported(move |path| {
+                if let Some(app) = app_weak.upgrade() {
+                    let bundle = export_all(&state.borrow());
+                    let path = path.trim().trim_matches('"');
+                    let target = if path.is_empty() {
+                        exports_dir().map(|dir| dir.join("settings.json"))
+                    } else {
+                        Ok(std::path::PathBuf::from(path))
+                    };
+                    let written = target.and_then(|target| {
+                        let data = serde_json::to_vec_pretty(&bundle)
+                            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                        std::fs::write(&target, data).map(|()| target)
+                    });
+                    let status = match written {
+                        Ok(target) => format!("Exported to {}", target.display()),
+                        Err(e) => format!("Export failed: {}", e),
+                    };
+                    app.set_settings_status(SharedString::from(status));
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_settings_imported(move |path| {
+                if let Some(app) = app_weak.upgrade() {
+                    let path = path.trim().trim_matches('"');
+                    let value = std::fs::read(path)
+                        .map_err(|e| format!("Could not open {}: {}", path, e))
+                        .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).map_err(|e| format!("Not a settings file: {}", e)));
+                    let imported = value.and_then(|value| import_all(&app, &mut state.borrow_mut(), value));
+                    let status = match imported {
+                        Ok(warnings) if warnings.is_empty() => "Settings imported".to_string(),
+                        Ok(warnings) => {
+                            for warning in &warnings {
+                                eprintln!("[on_settings_imported] {}", warning);
+                            }
+                            format!("Settings imported ({} skipped)", warnings.len())
+                        }
+                        Err(e) => e,
+                    };
+                    app.set_settings_status(SharedString::from(status));
+                }
+            });
+        }
+
+        // Wire up layout lock toggle - always available, even while locked
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_layout_lock_toggled(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    state.settings.layout_locked = !state.settings.layout_locked;
+                    app.set_layout_locked(state.settings.layout_locked);
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up fretboard interactions
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_fret_clicked(move |string, fret| {
+                let Some(app) = app_weak.upgrade() else { return };
+                let mut state = state.borrow_mut();
+                // In hold mode the press and release already played the note
+                if state.settings.click_mode == ClickMode::Hold {
+                    return;
+                }
+                let sound = state.fret_clicked(string as u8, fret as u8, std::time::Instant::now());
+                if sound.note_set_changed {
+                    app.set_note_set_text(note_set_text(&state.note_set));
+                }
+                sound.play(state.audio.as_mut());
+            });
+        }
+
+        // Wire up hold-to-sustain: the note sounds from press until release
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_fret_pressed(move |string, fret| {
+                let Some(app) = app_weak.upgrade() else { return };
+                let mut state = state.borrow_mut();
+                if state.settings.click_mode != ClickMode::Hold {
+                    return;
+                }
+                let sound = state.fret_clicked(string as u8, fret as u8, std::time::Instant::now());
+                if sound.note_set_changed {
+                    app.set_note_set_text(note_set_text(&state.note_set));
+                }
+                sound.hold(state.audio.as_mut());
+            });
+        }
+        {
+            let state = state.clone();
+            app.on_fret_released(move || {
+                state.borrow_mut().fret_released(std::time::Instant::now());
+            });
+        }
+
+        // Wire up chord playback: the custom note set if there is one, else the tonic triad
+        {
+            let state = state.clone();
+            app.on_play_chord(move || {
+                let mut state = state.borrow_mut();
+                let frequencies: Vec<f32> = state.chord_notes().iter()
+                    .map(|&n| music_theory::calculate_frequency_with_offsets(n, &state.settings.note_cents, state.settings.reference_a4))
+                    .collect();
+                state.audio.play_notes(&frequencies);
+            });
+        }
+
+        // Wire up the piano in the Keys pane
+        {
+            let state = state.clone();
+            app.on_piano_key_pressed(move |index| {
+                let mut state = state.borrow_mut();
+                let Ok(index) = usize::try_from(index) else { return };
+                let (note, _) = app_state::piano_key_note(index);
+                let frequency = state.playback_frequency(note);
+                state.audio.play_note(frequency);
+            });
+        }
+
+        // Wire up capo position
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_capo_changed(move |fret| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let capo = app_state::clamp_capo(fret);
+                    if state.settings.capo != capo {
+                        state.settings.capo = capo;
+                        app.set_capo(capo as i32);
+                        update_fret_data(&app, &mut state);
+                        // A question asked before may now sit behind the capo
+                        if state.drill.question().is_some() {
+                            state.next_drill_question();
+                            sync_drill_to_ui(&app, &state, None);
+                        }
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up fretboard zoom
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_zoom_stepped(move |steps| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let zoom = app_state::clamp_zoom(state.settings.zoom + steps as f32 * app_state::ZOOM_STEP);
+                    if state.settings.zoom != zoom {
+                        state.settings.zoom = zoom;
+                        app.set_zoom(zoom);
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up the dark/light theme toggle
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_theme_toggled(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    state.settings.theme = state.settings.theme.toggled();
+                    app.set_theme(state.settings.theme.to_int());
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up left-handed view
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_left_handed_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    state.settings.left_handed = enabled;
+                    app.set_left_handed(enabled);
+                    update_fret_data(&app, &mut state);
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up scale playback, starting on the focused string (or the lowest)
+        {
+            let state = state.clone();
+            app.on_play_scale(move || {
+                state.borrow_mut().play_scale(false);
+            });
+        }
+        {
+            let state = state.clone();
+            app.on_loop_scale(move || {
+                state.borrow_mut().play_scale(true);
+            });
+        }
+        {
+            let state = state.clone();
+            app.on_stop_scale(move || {
+                // Dropping the playback cuts the run or loop off
+                state.borrow_mut().scale_playback = None;
+            });
+        }
+
+        // Wire up double-click action setting
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_double_click_action_cycled(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let action = state.settings.double_click_action.next();
+                    state.settings.double_click_action = action;
+                    app.set_double_click_action_name(SharedString::from(action.name()));
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up click mode setting
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_click_mode_cycled(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let mode = state.settings.click_mode.next();
+                    state.settings.click_mode = mode;
+                    // Don't leave a held note ringing with nothing to release it
+                    state.audio.stop_note();
+                    app.set_click_mode_name(SharedString::from(mode.name()));
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up tension detune toggle
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_tension_detune_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    state.borrow_mut().settings.tension_detune = enabled;
+                    app.set_tension_detune(enabled);
+                }
+            });
+        }
+
+        // Wire up legato scale playback
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_legato_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    state.borrow_mut().settings.legato = enabled;
+                    app.set_legato(enabled);
+                }
+            });
+        }
+
+        // Wire up the drone under the scale
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_drone_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    state.borrow_mut().set_drone(enabled);
+                    app.set_drone(enabled);
+                }
+            });
+        }
+
+        // Wire up oscillator waveform setting
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_waveform_cycled(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let waveform = state.settings.waveform.next();
+                    state.settings.waveform = waveform;
+                    state.audio.set_waveform(waveform);
+                    app.set_waveform_name(SharedString::from(waveform.name()));
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up master volume
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_volume_changed(move |gain| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let gain = audio::clamp_volume(gain);
+                    state.settings.volume = gain;
+                    state.audio.set_volume(gain);
+                    state.metronome.set_volume(gain);
+                    app.set_volume(gain);
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up note duration
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_note_duration_changed(move |ms| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let ms = audio::clamp_note_duration_ms(ms.max(0) as u64);
+                    state.settings.note_duration_ms = ms;
+                    state.audio.set_note_duration(ms);
+                    app.set_note_duration_ms(ms as i32);
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up reference pitch (A4)
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_reference_a4_changed(move |hz| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let reference = music_theory::clamp_reference_a4(hz as f32);
+                    state.settings.reference_a4 = reference;
+                    app.set_reference_a4(reference.round() as i32);
+                    // The fret frequencies shown are at the reference pitch
+                    update_fret_data(&app, &mut state);
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up microtonal offsets: playback, the drone and the tuner all follow them
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_note_cents_changed(move |pitch_class, cents| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let key = Key::from_int(pitch_class);
+                    state.settings.note_cents.set(key, cents.clamp(-50, 50) as f32);
+                    app.set_note_cents(note_cents_model(&state.settings.note_cents));
+                    if state.drone {
+                        state.set_drone(true);
+                    }
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up chord sheet loading
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_chord_sheet_opened(move |path| {
+                if let Some(app) = app_weak.upgrade() {
+                    open_chord_sheet(&app, &mut state.borrow_mut(), &path);
+                }
+            });
+        }
+
+        // Wire up chord sheet transposition (semitones relative to the file)
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_chord_sheet_transposed(move |steps| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    // Past an octave the chords repeat
+                    state.sheet_transpose = (state.sheet_transpose + steps).clamp(-11, 11);
+                    render_chord_sheet(&app, &state);
+                }
+            });
+        }
+
+        // Wire up the Video Panel: the URL is saved with the layout, Open hands it to the system
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_video_url_edited(move |url| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    state.settings.video_url = url.trim().to_string();
+                    app.set_video_url(SharedString::from(state.settings.video_url.as_str()));
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_video_opened(move |url| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let url = url.trim().to_string();
+                    if url.is_empty() {
+                        app.set_video_status(SharedString::from("Enter a video URL or file path"));
+                        return;
+                    }
+                    if state.settings.video_url != url {
+                        state.settings.video_url = url.clone();
+                        app.set_video_url(SharedString::from(url.as_str()));
+                        let _ = save_layout(&state.settings);
+                    }
+                    match open_in_system(&url) {
+                        Ok(()) => app.set_video_status(SharedString::from(format!("Opened {}", url))),
+                        Err(e) => app.set_video_status(SharedString::from(format!("Could not open {}: {}", url, e))),
+                    }
+                }
+            });
+        }
+
+        // Wire up chord-shape audition from the Chord Sheet
+        {
+            let state = state.clone();
+            app.on_chord_shape_strummed(move |index| {
+                let mut state = state.borrow_mut();
+                let Some(shape) = chords::CHORD_SHAPES.get(index.max(0) as usize) else { return };
+                let shape = shape.capoed(state.settings.capo);
+                let tuning = state.settings.current_tuning();
+                let reference_a4 = state.settings.reference_a4;
+                state.audio.play_chord_shape(&shape, &tuning, 30, reference_a4);
+            });
+        }
+
+        // Wire up audio calibration tone
+        {
+            let state = state.clone();
+            app.on_play_test_tone(move || {
+                state.borrow_mut().audio.play_test_tone(std::time::Duration::from_secs(2));
+            });
+        }
+
+        // Wire up the tuner
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_tuner_toggled(move || {
+                let Some(app) = app_weak.upgrade() else { return };
+                let mut state = state.borrow_mut();
+                if state.tuner.is_running() {
+                    state.tuner.stop();
+                    app.set_tuner_running(false);
+                    app.set_tuner_note(SharedString::from(""));
+                    app.set_tuner_cents(0.0);
+                    return;
+                }
+                // Readings arrive on the audio input thread; hop back to the UI thread
+                let reading_weak = app.as_weak();
+                let (reference_a4, offsets) = (state.settings.reference_a4, state.settings.note_cents);
+                let started = state.tuner.start(reference_a4, offsets, move |note, cents| {
+                    let _ = reading_weak.upgrade_in_event_loop(move |app| {
+                        app.set_tuner_note(SharedString::from(note.name()));
+                        app.set_tuner_cents(cents);
+                    });
+                });
+                match started {
+                    Ok(()) => {
+                        app.set_tuner_running(true);
+                        app.set_tuner_status(SharedString::from(""));
+                    }
+                    Err(e) => app.set_tuner_status(SharedString::from(e.to_string())),
+                }
+            });
+        }
+
+        // Wire up metronome controls
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_toggled(move || {
+                let Some(app) = app_weak.upgrade() else { return };
+                let mut state = state.borrow_mut();
+                let metronome = &mut state.metronome;
+                let run = METRONOME_RUN.fetch_add(1, Ordering::SeqCst) + 1;
+                if metronome.is_running() {
+                    metronome.stop();
+                    app.set_metronome_running(false);
+                    app.set_metronome_beat(0);
+                } else {
+                    // Ticks arrive on the timer thread; hop back to the UI thread
+                    let tick_weak = app.as_weak();
+                    // Followed on every tick so a speed trainer's ramp shows up
+                    let bpm = metronome.shared_bpm();
+                    metronome.start(move |tick, onset| {
+                        let bpm = bpm.load(Ordering::SeqCst);
+                        let _ = tick_weak.upgrade_in_event_loop(move |app| {
+                            app.set_metronome_bpm(bpm as i32);
+                            // Hold the flash until the click comes out of the speakers
+                            let delay = metronome::flash_delay(onset, std::time::Instant::now());
+                            let flash_weak = app.as_weak();
+                            slint::Timer::single_shot(delay, move || {
+                                let Some(app) = flash_weak.upgrade() else { return };
+                                // A flash still pending when the metronome stopped or restarted is dropped
+                                if METRONOME_RUN.load(Ordering::SeqCst) != run {
+                                    return;
+                                }
+                                app.set_metronome_beat(tick.beat as i32 + 1);
+                                app.set_metronome_sub_beat(tick.subdivision as i32);
+                                app.set_metronome_accent(tick.accent.to_int());
+                            });
+                        });
+                    });
+                    app.set_metronome_running(true);
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_tempo_changed(move |bpm| {
+                if let Some(app) = app_weak.upgrade() {
+                    let state = state.borrow();
+                    let metronome = &state.metronome;
+                    metronome.set_tempo(bpm.max(0) as u32);
+                    app.set_metronome_bpm(metronome.bpm() as i32);
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_tapped(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    if let Some(bpm) = state.tap_tempo.tap(std::time::Instant::now()) {
+                        state.metronome.set_tempo(bpm);
+                        app.set_metronome_bpm(state.metronome.bpm() as i32);
+                    }
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_time_signature_selected(move |beats_per_bar, beat_unit| {
+                if let Some(app) = app_weak.upgrade() {
+                    let time_signature = TimeSignature::new(beats_per_bar.max(1) as u32, beat_unit.max(1) as u32);
+                    app.set_metronome_beats_per_bar(time_signature.beats_per_bar as i32);
+                    app.set_metronome_beat_unit(time_signature.beat_unit as i32);
+                    restart_metronome_after(&app, &state, |state| {
+                        state.metronome.set_time_signature(time_signature);
+                        sync_metronome_accents(&app, &state.metronome);
+                    });
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_subdivision_selected(move |subdivision_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let subdivision = Subdivision::from_int(subdivision_int);
+                    app.set_metronome_subdivision(subdivision.to_int());
+                    restart_metronome_after(&app, &state, |state| state.metronome.set_subdivision(subdivision));
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_swing_selected(move |percent| {
+                if let Some(app) = app_weak.upgrade() {
+                    app.set_metronome_swing_percent(percent);
+                    restart_metronome_after(&app, &state, |state| state.metronome.set_swing_ratio(percent as f32 / 100.0));
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_latency_changed(move |ms| {
+                if let Some(app) = app_weak.upgrade() {
+                    // Restarted so the flashes pick up the new offset
+                    restart_metronome_after(&app, &state, |state| {
+                        let ms = metronome::clamp_output_latency_ms(ms.max(0) as u32);
+                        state.settings.output_latency_ms = ms;
+                        state.metronome.set_output_latency(std::time::Duration::from_millis(ms as u64));
+                        app.set_metronome_latency_ms(ms as i32);
+                        let _ = save_layout(&state.settings);
+                    });
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_click_sound_selected(move |sound_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let click_sound = audio::ClickSound::from_int(sound_int);
+                    app.set_metronome_click_sound(click_sound.to_int());
+                    restart_metronome_after(&app, &state, |state| state.metronome.set_click_sound(click_sound));
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_trainer_changed(move |settings| {
+                if let Some(app) = app_weak.upgrade() {
+                    let trainer = TempoTrainer::new(
+                        settings.start_bpm.max(0) as u32,
+                        settings.increment.max(0) as u32,
+                        settings.bars_per_step.max(0) as u32,
+                        settings.target_bpm.max(0) as u32,
+                        if settings.repeat { RampEnd::Loop } else { RampEnd::Hold },
+                    );
+                    // Show the values as clamped; they're kept while the trainer is off
+                    app.set_metronome_trainer(TrainerSettings {
+                        enabled: settings.enabled,
+                        start_bpm: trainer.start_bpm as i32,
+                        increment: trainer.increment as i32,
+                        bars_per_step: trainer.bars_per_step as i32,
+                        target_bpm: trainer.target_bpm as i32,
+                        repeat: settings.repeat,
+                    });
+                    // Restarted so the ramp begins again from its start tempo
+                    restart_metronome_after(&app, &state, |state| state.metronome.set_tempo_trainer(settings.enabled.then_some(trainer)));
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_silent_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    // Picked up from the next click, so a running metronome carries on
+                    let state = state.borrow();
+                    state.metronome.set_silent(enabled);
+                    app.set_metronome_silent(state.metronome.is_silent());
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_count_in_selected(move |bars| {
+                if let Some(app) = app_weak.upgrade() {
+                    // Only matters when starting, so a running metronome carries on
+                    let mut state = state.borrow_mut();
+                    state.metronome.set_count_in_bars(bars.max(0) as u32);
+                    app.set_metronome_count_in_bars(state.metronome.count_in_bars() as i32);
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_metronome_accent_toggled(move |beat| {
+                if let Some(app) = app_weak.upgrade() {
+                    restart_metronome_after(&app, &state, |state| {
+                        state.metronome.toggle_accent(beat.max(0) as u32);
+                        sync_metronome_accents(&app, &state.metronome);
+                    });
+                }
+            });
+        }
+
+        // Wire up the practice session timer; each finished run is appended to the log
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_practice_toggled(move || {
+                let Some(app) = app_weak.upgrade() else { return };
+                let mut state = state.borrow_mut();
+                if state.practice.is_running() {
+                    if let Err(e) = end_practice_session(&mut state) {
+                        eprintln!("Could not log practice session: {}", e);
+                    }
+                    app.set_practice_running(false);
+                    app.set_practice_today(SharedString::from(practice_today_text()));
+                } else {
+                    state.practice.start(std::time::Instant::now());
+                    app.set_practice_running(true);
+                }
+            });
+        }
+
+        // Wire up key selection
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_key_selected(move |key_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let key = Key::from_int(key_int);
+                    if state.settings.key != key {
+                        state.settings.key = key;
+                        app.set_selected_key(key_int);
+                        // Move a sounding drone to the new tonic
+                        if state.drone {
+                            state.set_drone(true);
+                        }
+                        update_fret_data(&app, &mut state);
+                        update_piano_keys(&app, &state);
+                    }
+                }
+            });
+        }
+
+        // Wire up instrument selection
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_instrument_selected(move |instrument_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let instrument = Instrument::from_int(instrument_int);
+                    if state.settings.instrument != instrument {
+                        state.settings.instrument = instrument;
+                        // A focused string the new instrument doesn't have falls back to all strings
+                        if state.settings.focused_string >= instrument.string_count() as i32 {
+                            state.settings.focused_string = -1;
+                            app.set_focused_string(-1);
+                        }
+                        app.set_selected_instrument(instrument_int);
+                        update_fret_data(&app, &mut state);
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up tuning selection
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_tuning_selected(move |tuning_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let tuning = TuningPreset::from_int(tuning_int);
+                    if state.settings.tuning != tuning {
+                        state.settings.tuning = tuning;
+                        app.set_selected_tuning(tuning_int);
+                        update_fret_data(&app, &mut state);
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up movable-root offset
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_root_offset_changed(move |offset| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    // One octave either way covers every transposition
+                    let offset = offset.clamp(-12, 12);
+                    if state.settings.root_offset_frets != offset {
+                        state.settings.root_offset_frets = offset;
+                        app.set_root_offset_frets(offset);
+                        update_fret_data(&app, &mut state);
+                    }
+                }
+            });
+        }
+
+        // Wire up SVG export of the current scale
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_svg_exported(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let state = state.borrow();
+                    let status = match export_svg(&state.settings) {
+                        Ok(path) => format!("Saved {}", path.display()),
+                        Err(e) => format!("Export failed: {}", e),
+                    };
+                    app.set_export_status(SharedString::from(status));
+                }
+            });
+        }
+
+        // Wire up recording of fret presses and MIDI export of the take
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_recording_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let now = std::time::Instant::now();
+                    if enabled {
+                        state.recorder.start(now);
+                    } else {
+                        state.recorder.stop(now);
+                    }
+                    app.set_recording(state.recorder.is_recording());
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_midi_exported(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let state = state.borrow();
+                    let status = if state.recorder.take().is_empty() {
+                        "Nothing recorded yet".to_string()
+                    } else {
+                        match export_recording(&state) {
+                            Ok(path) => format!("Saved {}", path.display()),
+                            Err(e) => format!("Export failed: {}", e),
+                        }
+                    };
+                    app.set_export_status(SharedString::from(status));
+                }
+            });
+        }
+
+        // Wire up note-name / scale-degree labels (cells carry both, so no regeneration)
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_show_degrees_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    state.borrow_mut().settings.show_degrees = enabled;
+                    app.set_show_degrees(enabled);
+                }
+            });
+        }
+
+        // Wire up octave color-coding (cells carry their octave, so no regeneration)
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_octave_colors_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    state.borrow_mut().settings.octave_colors = enabled;
+                    app.set_octave_colors(enabled);
+                }
+            });
+        }
+
+        // Wire up neck length
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_fret_count_changed(move |frets| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let fret_count = app_state::clamp_fret_count(frets);
+                    app.set_fret_count(fret_count as i32);
+                    if state.settings.fret_count != fret_count {
+                        state.settings.fret_count = fret_count;
+                        update_fret_data(&app, &mut state);
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up the fret window
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_fret_window_changed(move |enabled, low, high| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let low = low.clamp(0, app_state::MAX_FRET_COUNT as i32);
+                    let high = high.clamp(low, app_state::MAX_FRET_COUNT as i32);
+                    app.set_fret_window_enabled(enabled);
+                    app.set_fret_window_low(low);
+                    app.set_fret_window_high(high);
+                    let window = enabled.then_some((low as u8, high as u8));
+                    if state.settings.fret_window != window {
+                        state.settings.fret_window = window;
+                        update_fret_data(&app, &mut state);
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up single-string focus
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_focused_string_selected(move |string| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let string = string.clamp(-1, state.settings.instrument.string_count() as i32 - 1);
+                    if state.settings.focused_string != string {
+                        state.settings.focused_string = string;
+                        app.set_focused_string(string);
+                        update_fret_data(&app, &mut state);
+                    }
+                }
+            });
+        }
+
+        // Wire up scale position (box) practice
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_scale_position_selected(move |position| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let position = position.clamp(-1, state.settings.scale.intervals().len() as i32 - 1);
+                    if state.settings.scale_position != position {
+                        state.settings.scale_position = position;
+                        app.set_scale_position(position);
+                        update_fret_data(&app, &mut state);
+                    }
+                }
+            });
+        }
+
+        // Wire up scale / chord highlighting
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_highlight_chord_selected(move |quality| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let chord_highlight = (quality >= 0).then(|| ChordQuality::from_int(quality));
+                    if state.settings.chord_highlight != chord_highlight {
+                        state.settings.chord_highlight = chord_highlight;
+                        app.set_highlight_chord(chord_highlight.map_or(-1, ChordQuality::to_int));
+                        update_fret_data(&app, &mut state);
+                    }
+                }
+            });
+        }
+
+        // Wire up find-note mode
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_find_note_changed(move |enabled, note| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let find_note = enabled.then(|| Key::from_int(note.rem_euclid(12)));
+                    // The picker keeps its note while the mode is off
+                    app.set_find_note(note);
+                    app.set_find_note_enabled(enabled);
+                    if state.settings.find_note != find_note {
+                        state.settings.find_note = find_note;
+                        update_fret_data(&app, &mut state);
+                    }
+                }
+            });
+        }
+
+        // Wire up the note drill: a marked fret to name, with the answer typed in
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_drill_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    if enabled {
+                        state.next_drill_question();
+                    } else {
+                        state.drill.reset();
+                    }
+                    sync_drill_to_ui(&app, &state, None);
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_drill_answered(move |text| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let best = state.settings.drill_best_streak;
+                    let Some(answer) = state.answer_drill(&text) else { return };
+                    // Sound the note asked about, to tie the name to the pitch
+                    let frequency = state.playback_frequency(answer.note);
+                    state.audio.play_note(frequency);
+                    sync_drill_to_ui(&app, &state, Some(answer));
+                    if state.settings.drill_best_streak != best {
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+
+        // Wire up the interval quiz: two notes sounded together, named from the buttons
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_interval_quiz_toggled(move |enabled| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    if enabled {
+                        state.interval_quiz.next_question();
+                        play_interval_question(&mut state);
+                    } else {
+                        state.interval_quiz.reset();
+                    }
+                    app.set_interval_quiz_active(enabled);
+                    app.set_interval_quiz_status(SharedString::from(state.interval_quiz_status(None)));
+                }
+            });
+        }
+        {
+            let state = state.clone();
+            app.on_interval_quiz_replayed(move || {
+                play_interval_question(&mut state.borrow_mut());
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_interval_quiz_answered(move |semitones| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let Some(answer) = state.answer_interval_quiz(music_theory::Interval::from_semitones(semitones)) else { return };
+                    play_interval_question(&mut state);
+                    app.set_interval_quiz_status(SharedString::from(state.interval_quiz_status(Some(answer))));
+                }
+            });
+        }
+
+        // Wire up double-stop playback
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_harmony_interval_selected(move |interval| {
+                if let Some(app) = app_weak.upgrade() {
+                    let harmony = (interval >= 0).then(|| HarmonyInterval::from_int(interval));
+                    state.borrow_mut().settings.harmony = harmony;
+                    app.set_harmony_interval(harmony.map_or(-1, HarmonyInterval::to_int));
+                }
+            });
+        }
+
+        // Wire up fret-marker scheme
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_marker_scheme_selected(move |scheme_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    // Switching to custom starts from the frets currently marked
+                    let scheme = match MarkerScheme::from_int(scheme_int) {
+                        MarkerScheme::Custom(_) => MarkerScheme::Custom(music_theory::get_marked_frets(&state.settings.marker_scheme)),
+                        scheme => scheme,
+                    };
+                    if state.settings.marker_scheme.to_int() != scheme_int {
+                        app.set_selected_marker_scheme(scheme_int);
+                        app.set_custom_marker_text(custom_marker_text(&scheme));
+                        state.settings.marker_scheme = scheme;
+                        update_fret_data(&app, &mut state);
+                        let _ = save_layout(&state.settings);
+                    }
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_custom_markers_edited(move |text| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    state.settings.marker_scheme = MarkerScheme::Custom(music_theory::parse_fret_list(&text));
+                    app.set_selected_marker_scheme(state.settings.marker_scheme.to_int());
+                    app.set_custom_marker_text(custom_marker_text(&state.settings.marker_scheme));
+                    update_fret_data(&app, &mut state);
+                    let _ = save_layout(&state.settings);
+                }
+            });
+        }
+
+        // Wire up scale selection
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_scale_selected(move |scale_int| {
+                if let Some(app) = app_weak.upgrade() {
+                    let mut state = state.borrow_mut();
+                    let scale = Scale::from_int(scale_int);
+                    if state.settings.scale != scale {
+                        state.settings.scale = scale;
+                        // Scales with fewer notes have fewer positions
+                        if state.settings.scale_position >= scale.intervals().len() as i32 {
+                            state.settings.scale_position = -1;
+                            app.set_scale_position(-1);
+                        }
+                        app.set_selected_scale(scale_int);
+                        update_fret_data(&app, &mut state);
+                        update_piano_keys(&app, &state);
+                    }
+                }
+            });
+        }
+
+        // Wire up relative / parallel key switching; goes through the key and scale
+        // handlers so the board and piano follow
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_relative_key(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    // Release the borrow before the handlers below take their own
+                    let target = {
+                        let settings = &state.borrow().settings;
+                        music_theory::relative_key(settings.key, settings.scale)
+                    };
+                    if let Some((key, scale)) = target {
+                        app.invoke_key_selected(key.to_int());
+                        app.invoke_scale_selected(scale.to_int());
+                    }
+                }
+            });
+        }
+        {
+            let app_weak = app.as_weak();
+            let state = state.clone();
+            app.on_parallel_key(move || {
+                if let Some(app) = app_weak.upgrade() {
+                    let scale = music_theory::parallel_scale(state.borrow().settings.scale);
+                    if let Some(scale) = scale {
+                        app.invoke_scale_selected(scale.to_int());
+                    }
+                }
+            });
+        }
+
+
+        eprintln!("[STEP 6/10] ✓ Callbacks set up");
+    }
+
+    eprintln!("[STEP 7/10] All initialization complete");
+    eprintln!("[STEP 8/10] About to call app.run()...");
     eprintln!("[STEP 8/10] ⚠ This is typically where crashes occur!");
     
     let result = app.run();
@@ -376,8 +2526,16 @@ fn run_app(disable_audio: bool, disable_layout: bool, disable_callbacks: bool) -
     
     // Cleanup
     eprintln!("[STEP 10/10] Cleaning up...");
-    if let Some(ref audio_player) = audio_player {
-        audio_player.cleanup();
+    {
+        let mut state = state.borrow_mut();
+        if !disable_layout {
+            save_all_on_exit(&app, &mut state);
+        }
+        state.metronome.stop();
+        // A session still running when the window closes is logged too
+        let _ = end_practice_session(&mut state);
+        state.scale_playback = None;
+        state.audio.cleanup();
     }
     std::thread::sleep(std::time::Duration::from_millis(50));
     
@@ -388,3 +2546,381 @@ fn run_app(disable_audio: bool, disable_layout: bool, disable_callbacks: bool) -
 fn pane_id_from(i: i32) -> PaneId {
     match i { 0 => PaneId::TopLeft, 1 => PaneId::TopRight, 2 => PaneId::BottomLeft, 3 => PaneId::BottomRight, _ => PaneId::TopLeft }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_path_resolves_and_creates_dir() {
+        // Resolving the real config directory doesn't create anything
+        config_base_dir().expect("config directory should resolve on this platform");
+
+        let base = scratch_dir("layout-path");
+        let path = layout_path_in(&base).unwrap();
+        assert!(path.ends_with("guitar-practice-dashboard/layout.json"));
+        assert!(path.parent().unwrap().is_dir());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    // Fresh scratch directory per test so runs don't see each other's presets
+    fn scratch_dir(test: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gpd-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_preset_round_trip() {
+        let dir = scratch_dir("preset-round-trip");
+        let saved = Settings {
+            main_kind: 4,
+            layout_locked: true,
+            double_click_action: DoubleClickAction::Sustain,
+            click_mode: ClickMode::Hold,
+            marker_scheme: MarkerScheme::Custom(vec![5, 9]),
+            waveform: audio::Waveform::Square,
+            volume: 0.4,
+            note_duration_ms: 1200,
+            output_latency_ms: 40,
+            reference_a4: 415.0,
+            left_handed: true,
+            capo: 3,
+            ..Settings::default()
+        };
+        save_preset_in(&dir, &saved, "Scales practice").unwrap();
+        save_preset_in(&dir, &Settings::default(), "Song learning").unwrap();
+        assert_eq!(list_presets_in(&dir), vec!["Scales practice", "Song learning"]);
+
+        let mut loaded = Settings::default();
+        load_preset_in(&dir, &mut loaded, "Scales practice").unwrap();
+        assert_eq!(loaded, saved);
+
+        delete_preset_in(&dir, "Scales practice").unwrap();
+        assert_eq!(list_presets_in(&dir), vec!["Song learning"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_settings_bundle_round_trip() {
+        let source_dir = scratch_dir("bundle-export");
+        let mut exported = AppState::new(Box::<audio::RecordingPlayer>::default());
+        exported.settings = Settings {
+            main_kind: 1,
+            key: Key::Ds,
+            scale: Scale::Dorian,
+            tuning: TuningPreset::DropD,
+            volume: 0.6,
+            capo: 2,
+            ..Settings::default()
+        };
+        exported.metronome.set_tempo(132);
+        exported.metronome.set_time_signature(TimeSignature::new(3, 4));
+        exported.metronome.set_accented_beats(vec![0, 2]);
+        exported.metronome.set_count_in_bars(2);
+        exported.metronome.set_swing_ratio(0.625);
+        exported.metronome.set_click_sound(audio::ClickSound::Woodblock);
+        exported.metronome.set_silent(true);
+        save_preset_in(&source_dir, &Settings { left_handed: true, ..Settings::default() }, "Lefty").unwrap();
+
+        let bundle = export_bundle(&exported, read_presets_in(&source_dir));
+        assert_eq!(bundle["version"], SETTINGS_BUNDLE_VERSION);
+        // Through text and back, as it would be on disk
+        let bundle: serde_json::Value = serde_json::from_str(&bundle.to_string()).unwrap();
+
+        let target_dir = scratch_dir("bundle-import");
+        let mut imported = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let warnings = import_bundle_in(&target_dir, &mut imported, bundle).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(imported.settings, exported.settings);
+        let metronome = &imported.metronome;
+        assert_eq!((metronome.bpm(), metronome.time_signature()), (132, TimeSignature::new(3, 4)));
+        assert_eq!(metronome.accented_beats(), &[0, 2]);
+        assert_eq!(metronome.count_in_bars(), 2);
+        assert_eq!(metronome.swing_ratio(), 0.625);
+        assert_eq!(metronome.click_sound(), audio::ClickSound::Woodblock);
+        assert!(metronome.is_silent());
+
+        let mut lefty = Settings::default();
+        load_preset_in(&target_dir, &mut lefty, "Lefty").unwrap();
+        assert!(lefty.left_handed);
+        let _ = std::fs::remove_dir_all(&source_dir);
+        let _ = std::fs::remove_dir_all(&target_dir);
+    }
+
+    #[test]
+    fn test_first_run_gets_default_layout() {
+        let dir = scratch_dir("first-run");
+        let path = dir.join("layout.json");
+        let mut settings = Settings::default();
+        load_layout_in(&path, &mut settings).unwrap();
+        assert_eq!(settings.main_kind, kind_to_tag(ComponentKind::Fretboard));
+        // Everything else is as a fresh Settings would have it
+        assert_eq!(settings, Settings { main_kind: settings.main_kind, ..Settings::default() });
+
+        // Once a layout is saved, it's what loads
+        std::fs::write(&path, br#"{"main_kind":1}"#).unwrap();
+        load_layout_in(&path, &mut settings).unwrap();
+        assert_eq!(settings.main_kind, kind_to_tag(ComponentKind::Metronome));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_save_all_writes_key_and_scale() {
+        let dir = scratch_dir("save-all");
+        let path = dir.join("last-settings.json");
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        state.settings.key = Key::Fs;
+        state.settings.scale = Scale::Phrygian;
+        save_all_in(&path, &state).unwrap();
+
+        let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved["key"], Key::Fs.to_int());
+        assert_eq!(saved["scale"], Scale::Phrygian.to_int());
+        // A fresh start reads them back
+        let mut restored = AppState::new(Box::<audio::RecordingPlayer>::default());
+        import_bundle_in(&dir, &mut restored, saved).unwrap();
+        assert_eq!((restored.settings.key, restored.settings.scale), (Key::Fs, Scale::Phrygian));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_settings_bundle_skips_bad_fields() {
+        let dir = scratch_dir("bundle-bad-fields");
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        let bundle = serde_json::json!({
+            "version": 1,
+            "layout": { "capo": 4, "volume": "loud", "sparkles": true },
+            "key": 40,
+            "scale": 13,
+            "metronome": { "bpm": 90 },
+        });
+        let mut warnings = import_bundle_in(&dir, &mut state, bundle).unwrap();
+        warnings.sort();
+        // The good values land; the bad ones keep what was there
+        assert_eq!(state.settings.capo, 4);
+        assert_eq!(state.settings.volume, audio::DEFAULT_VOLUME);
+        assert_eq!(state.settings.key, Key::C);
+        assert_eq!(state.settings.scale, Scale::MelodicMinor);
+        assert_eq!(state.metronome.bpm(), 90);
+        assert_eq!(state.metronome.time_signature(), TimeSignature::default());
+        assert_eq!(warnings, vec![
+            "Ignored invalid setting key",
+            "Ignored invalid setting layout.volume",
+            "Ignored unknown setting layout.sparkles",
+        ]);
+
+        // A bare layout (layout.json or a preset file) predates versioning and still imports
+        let warnings = import_bundle_in(&dir, &mut state, serde_json::json!({ "main_kind": 6, "left_handed": true })).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(state.settings.main_kind, 6);
+        assert!(state.settings.left_handed);
+        assert!(import_bundle_in(&dir, &mut state, serde_json::json!([1, 2])).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_preset_names_and_missing_files() {
+        let dir = scratch_dir("preset-names");
+        // Path separators and reserved characters can't escape the presets directory
+        assert_eq!(preset_file_stem("../a/b:c?").as_deref(), Some("_a_b_c_"));
+        assert_eq!(preset_file_stem("  ..  "), None);
+        assert!(save_preset_in(&dir, &Settings::default(), "").is_err());
+
+        save_preset_in(&dir, &Settings::default(), "a/b").unwrap();
+        assert_eq!(list_presets_in(&dir), vec!["a_b"]);
+
+        // Missing presets fail to load without touching the settings, and delete quietly
+        let mut settings = Settings { main_kind: 2, ..Settings::default() };
+        assert!(load_preset_in(&dir, &mut settings, "nope").is_err());
+        assert_eq!(settings.main_kind, 2);
+        assert!(delete_preset_in(&dir, "nope").is_ok());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_layout_restores_instrument_and_tuning() {
+        let saved = Settings { instrument: Instrument::Guitar6, tuning: TuningPreset::DropD, ..Settings::default() };
+        let json = serde_json::to_vec(&Layout::from_settings(&saved)).unwrap();
+
+        let mut state = AppState::new(Box::<audio::RecordingPlayer>::default());
+        serde_json::from_slice::<Layout>(&json).unwrap().apply_to(&mut state.settings);
+        assert_eq!(state.settings.instrument, Instrument::Guitar6);
+        assert_eq!(state.settings.tuning, TuningPreset::DropD);
+        let open_strings: Vec<&str> = state.board().iter().map(|cells| cells[0].note_name.as_str()).collect();
+        assert_eq!(open_strings, vec!["D2", "A2", "D3", "G3", "B3", "E4"]);
+
+        // The preset applies to whichever instrument was saved
+        let saved = Settings { instrument: Instrument::Bass4, tuning: TuningPreset::DropD, ..Settings::default() };
+        let json = serde_json::to_vec(&Layout::from_settings(&saved)).unwrap();
+        serde_json::from_slice::<Layout>(&json).unwrap().apply_to(&mut state.settings);
+        let open_strings: Vec<&str> = state.board().iter().map(|cells| cells[0].note_name.as_str()).collect();
+        assert_eq!(open_strings, vec!["D1", "A1", "D2", "G2"]);
+
+        // Layouts saved before these fields existed come back in standard tuning
+        let settings = from_layout_json(br#"{"main_kind":4}"#);
+        assert_eq!(settings.tuning, TuningPreset::Standard);
+        assert_eq!(settings.volume, audio::DEFAULT_VOLUME);
+        assert_eq!(settings.note_duration_ms, audio::DEFAULT_NOTE_DURATION_MS);
+        assert_eq!(settings.reference_a4, music_theory::DEFAULT_REFERENCE_A4);
+    }
+
+    // Settings as they come back from a saved layout
+    fn round_trip(saved: &Settings) -> Settings {
+        let json = serde_json::to_vec(&Layout::from_settings(saved)).unwrap();
+        from_layout_json(&json)
+    }
+
+    fn from_layout_json(json: &[u8]) -> Settings {
+        let mut settings = Settings::default();
+        serde_json::from_slice::<Layout>(json).unwrap().apply_to(&mut settings);
+        settings
+    }
+
+    #[test]
+    fn test_layout_round_trips_window_geometry() {
+        let window = PixelRect { x: -1200, y: 40, width: 1100, height: 700 };
+        assert_eq!(round_trip(&Settings { window: Some(window), ..Settings::default() }).window, Some(window));
+        assert_eq!(from_layout_json(br#"{"main_kind":0}"#).window, None);
+    }
+
+    #[test]
+    fn test_layout_round_trips_fret_window() {
+        let saved = Settings { fret_window: Some((5, 9)), ..Settings::default() };
+        assert_eq!(round_trip(&saved).fret_window, Some((5, 9)));
+        assert_eq!(from_layout_json(br#"{"main_kind":0}"#).fret_window, None);
+    }
+
+    #[test]
+    fn test_layout_round_trips_zoom() {
+        assert_eq!(round_trip(&Settings { zoom: 1.5, ..Settings::default() }).zoom, 1.5);
+
+        // Out-of-range values are clamped; older layouts come back unzoomed
+        assert_eq!(from_layout_json(br#"{"zoom":5.0}"#).zoom, app_state::MAX_ZOOM);
+        assert_eq!(from_layout_json(br#"{"zoom":0.1}"#).zoom, app_state::MIN_ZOOM);
+        assert_eq!(from_layout_json(br#"{"main_kind":0}"#).zoom, 1.0);
+        assert_eq!(app_state::clamp_zoom(f32::NAN), 1.0);
+    }
+
+    #[test]
+    fn test_layout_round_trips_theme() {
+        assert_eq!(round_trip(&Settings { theme: Theme::Light, ..Settings::default() }).theme, Theme::Light);
+
+        // Older layouts come back dark
+        assert_eq!(from_layout_json(br#"{"main_kind":0}"#).theme, Theme::Dark);
+        assert_eq!(Theme::Dark.toggled(), Theme::Light);
+    }
+
+    #[test]
+    fn test_layout_round_trips_video_url() {
+        let saved = Settings { main_kind: 3, video_url: "https://www.youtube.com/watch?v=abc123&t=42".to_string(), ..Settings::default() };
+        assert_eq!(round_trip(&saved).video_url, saved.video_url);
+
+        // Older layouts have no video
+        assert_eq!(from_layout_json(br#"{"main_kind":3}"#).video_url, "");
+    }
+
+    #[test]
+    fn test_swap_panes_exchanges_kinds_and_titles() {
+        let mut settings = Settings { main_kind: kind_to_tag(ComponentKind::VideoPanel), video_url: "lesson.mp4".to_string(), ..Settings::default() };
+        settings.panes[PaneId::BottomRight as usize].kind = kind_to_tag(ComponentKind::Metronome);
+
+        settings.swap_panes(PaneId::TopLeft as usize, PaneId::BottomRight as usize);
+        let panes = settings.pane_states();
+        assert_eq!(title_for(panes[PaneId::TopLeft as usize].kind), "Metronome");
+        assert_eq!(title_for(panes[PaneId::BottomRight as usize].kind), "Video Panel");
+        // The drawn pane shows the Metronome now; the video went with its pane
+        assert_eq!(settings.main_kind, kind_to_tag(ComponentKind::Metronome));
+        assert_eq!(settings.video_url, "");
+        assert_eq!(panes[PaneId::BottomRight as usize].settings, serde_json::json!({ "video_url": "lesson.mp4" }));
+
+        // The swap is saved with the layout
+        let restored = round_trip(&settings);
+        assert_eq!(restored.pane_states(), panes);
+        assert_eq!(restored.main_kind, kind_to_tag(ComponentKind::Metronome));
+
+        assert!(matches!(pane_for_shortcut("4"), Some(PaneId::BottomRight)));
+        assert!(pane_for_shortcut("5").is_none());
+        assert!(pane_for_shortcut("0").is_none());
+    }
+
+    #[test]
+    fn test_fretboard_panes_keep_their_own_key_scale_and_tuning() {
+        let fretboard = kind_to_tag(ComponentKind::Fretboard);
+        let mut settings = Settings { main_kind: fretboard, key: Key::E, scale: Scale::NaturalMinor, tuning: TuningPreset::DropD, ..Settings::default() };
+        settings.panes[PaneId::TopRight as usize] = PaneState {
+            kind: fretboard,
+            settings: serde_json::json!({ "key": Key::A.to_int(), "scale": Scale::Major.to_int(), "tuning": TuningPreset::Standard.to_int() }),
+        };
+
+        settings.swap_panes(PaneId::TopLeft as usize, PaneId::TopRight as usize);
+        assert_eq!((settings.key, settings.scale, settings.tuning), (Key::A, Scale::Major, TuningPreset::Standard));
+        let restored = round_trip(&settings);
+        assert_eq!((restored.key, restored.scale, restored.tuning), (Key::A, Scale::Major, TuningPreset::Standard));
+
+        settings.swap_panes(PaneId::TopLeft as usize, PaneId::TopRight as usize);
+        assert_eq!((settings.key, settings.scale, settings.tuning), (Key::E, Scale::NaturalMinor, TuningPreset::DropD));
+
+        // A pane without a key of its own leaves the shared one alone
+        settings.panes[PaneId::BottomLeft as usize].kind = kind_to_tag(ComponentKind::Metronome);
+        settings.swap_panes(PaneId::TopLeft as usize, PaneId::BottomLeft as usize);
+        assert_eq!(settings.key, Key::E);
+    }
+
+    #[test]
+    fn test_old_flat_layout_migrates_into_panes() {
+        let fretboard = kind_to_tag(ComponentKind::Fretboard);
+        let old = from_layout_json(format!(r#"{{"main_kind":{},"tuning":{}}}"#, fretboard, TuningPreset::DropD.to_int()).as_bytes());
+        let panes = old.pane_states();
+        assert_eq!(panes[PaneId::TopLeft as usize].kind, fretboard);
+        assert_eq!(panes[PaneId::TopLeft as usize].settings["tuning"], TuningPreset::DropD.to_int());
+        assert_eq!(panes[PaneId::TopLeft as usize].settings["key"], Settings::default().key.to_int());
+        assert!(panes[1..].iter().all(|pane| *pane == PaneState::default()));
+
+        let old = from_layout_json(br#"{"main_kind":3,"video_url":"lesson.mp4"}"#);
+        assert_eq!(
+            old.pane_states()[PaneId::TopLeft as usize],
+            PaneState { kind: 3, settings: serde_json::json!({ "video_url": "lesson.mp4" }) }
+        );
+
+        // Saving a migrated layout writes the panes, and loads back the same
+        assert_eq!(round_trip(&old).pane_states(), old.pane_states());
+    }
+
+    #[test]
+    fn test_shortcut_keys_follow_component_names() {
+        for (i, (kind, _)) in COMPONENT_NAMES.iter().enumerate() {
+            let shortcut = component_for_shortcut(&(i + 1).to_string()).unwrap();
+            assert_eq!(kind_to_tag(shortcut), kind_to_tag(kind.clone()));
+        }
+        assert!(matches!(component_for_shortcut("1"), Some(ComponentKind::Metronome)));
+        assert!(matches!(component_for_shortcut("4"), Some(ComponentKind::Fretboard)));
+        assert!(matches!(component_for_shortcut("7"), Some(ComponentKind::Tuner)));
+
+        // Digits past the last component, 0, and anything else aren't shortcuts
+        assert!(component_for_shortcut("8").is_none());
+        assert!(component_for_shortcut("0").is_none());
+        assert!(component_for_shortcut("12").is_none());
+        assert!(component_for_shortcut("a").is_none());
+        assert!(component_for_shortcut("").is_none());
+    }
+
+    #[test]
+    fn test_pane_change_permitted_when_unlocked() {
+        assert!(is_pane_change_permitted(false, 0, 4));
+        assert!(is_pane_change_permitted(false, 4, 0));
+        assert!(is_pane_change_permitted(false, 4, 4));
+    }
+
+    #[test]
+    fn test_pane_change_blocked_when_locked() {
+        // Neither switching nor closing is allowed
+        assert!(!is_pane_change_permitted(true, 0, 4));
+        assert!(!is_pane_change_permitted(true, 4, 6));
+        assert!(!is_pane_change_permitted(true, 4, 0));
+
+        // Re-applying the current component is harmless
+        assert!(is_pane_change_permitted(true, 4, 4));
+    }
+}
@@ -0,0 +1,891 @@
+// Metronome: a timer thread that ticks once per beat and plays a click
+
+use crate::audio::{AudioPlayer, ClickPlayer, ClickSound, NotePlayer, Volume};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const MIN_BPM: u32 = 20;
+pub const MAX_BPM: u32 = 300;
+pub const DEFAULT_BPM: u32 = 100;
+// Share of each pair of clicks the first one takes: 0.5 is straight, 2/3 the
+// triplet-based 2:1 swing, and 0.75 about as hard as swing gets
+pub const STRAIGHT: f32 = 0.5;
+pub const MAX_SWING: f32 = 0.75;
+// Time from queueing a click to hearing it, set by the player to match their output device
+pub const MAX_OUTPUT_LATENCY_MS: u32 = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+}
+
+impl TimeSignature {
+    pub fn new(beats_per_bar: u32, beat_unit: u32) -> Self {
+        TimeSignature {
+            beats_per_bar: beats_per_bar.max(1),
+            beat_unit: beat_unit.max(1),
+        }
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        TimeSignature::new(4, 4)
+    }
+}
+
+// How many clicks each beat is split into
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Subdivision {
+    #[default]
+    Quarter,
+    Eighth,
+    Triplet,
+    Sixteenth,
+}
+
+impl Subdivision {
+    pub fn from_int(value: i32) -> Subdivision {
+        match value {
+            1 => Subdivision::Eighth,
+            2 => Subdivision::Triplet,
+            3 => Subdivision::Sixteenth,
+            _ => Subdivision::Quarter,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            Subdivision::Quarter => 0,
+            Subdivision::Eighth => 1,
+            Subdivision::Triplet => 2,
+            Subdivision::Sixteenth => 3,
+        }
+    }
+
+    pub fn clicks_per_beat(self) -> u32 {
+        match self {
+            Subdivision::Quarter => 1,
+            Subdivision::Eighth => 2,
+            Subdivision::Triplet => 3,
+            Subdivision::Sixteenth => 4,
+        }
+    }
+}
+
+// How loud a click is: accented beats, other beats, and the clicks between beats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Accent {
+    Weak,
+    Normal,
+    Strong,
+}
+
+impl Accent {
+    pub fn to_int(self) -> i32 {
+        match self {
+            Accent::Weak => 0,
+            Accent::Normal => 1,
+            Accent::Strong => 2,
+        }
+    }
+
+    // Click volume relative to the metronome volume
+    pub fn gain(self) -> f32 {
+        match self {
+            Accent::Weak => 0.5,
+            Accent::Normal | Accent::Strong => 1.0,
+        }
+    }
+}
+
+// Emitted once per click; beat is 0-based within the bar and subdivision is
+// 0-based within the beat (0 = on the beat)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tick {
+    pub beat: u32,
+    pub subdivision: u32,
+    pub accent: Accent,
+    pub is_downbeat: bool,
+    // One of the clicks counting the player in before the pattern starts
+    pub count_in: bool,
+}
+
+pub fn clamp_bpm(bpm: u32) -> u32 {
+    bpm.clamp(MIN_BPM, MAX_BPM)
+}
+
+pub fn clamp_output_latency_ms(ms: u32) -> u32 {
+    ms.min(MAX_OUTPUT_LATENCY_MS)
+}
+
+// When a click queued at queued_at is heard, after the output device's latency
+pub fn click_onset(queued_at: Instant, output_latency: Duration) -> Instant {
+    queued_at + output_latency
+}
+
+// How long to hold a beat's flash so it lands with the click; zero if the click
+// has already been heard
+pub fn flash_delay(onset: Instant, now: Instant) -> Duration {
+    onset.saturating_duration_since(now)
+}
+
+pub fn clamp_swing(ratio: f32) -> f32 {
+    if ratio.is_nan() {
+        return STRAIGHT;
+    }
+    ratio.clamp(STRAIGHT, MAX_SWING)
+}
+
+// Time from one click to the next within a beat of the given length. With an even
+// number of clicks per beat they're swung in pairs: the first of each pair takes
+// swing_ratio of the pair's time and the second the rest. Triplets stay even.
+pub fn click_interval(beat: Duration, clicks_per_beat: u32, subdivision: u32, swing_ratio: f32) -> Duration {
+    let clicks_per_beat = clicks_per_beat.max(1);
+    if !clicks_per_beat.is_multiple_of(2) {
+        return beat / clicks_per_beat;
+    }
+    let pair = beat * 2 / clicks_per_beat;
+    let ratio = clamp_swing(swing_ratio) as f64;
+    if subdivision.is_multiple_of(2) {
+        pair.mul_f64(ratio)
+    } else {
+        pair.mul_f64(1.0 - ratio)
+    }
+}
+
+// Time between beats, where the BPM counts the time signature's beat unit
+pub fn beat_interval(bpm: u32) -> Duration {
+    Duration::from_secs_f64(60.0 / clamp_bpm(bpm) as f64)
+}
+
+// Tick for the n-th click since the metronome started; accented_beats are the
+// 0-based beats of the bar that get the strong click
+pub fn subdivided_tick_for(
+    click_index: u64,
+    time_signature: TimeSignature,
+    subdivision: Subdivision,
+    accented_beats: &[u32],
+) -> Tick {
+    let per_beat = subdivision.clicks_per_beat() as u64;
+    let beat = ((click_index / per_beat) % time_signature.beats_per_bar as u64) as u32;
+    let subdivision = (click_index % per_beat) as u32;
+    let accent = if subdivision > 0 {
+        Accent::Weak
+    } else if accented_beats.contains(&beat) {
+        Accent::Strong
+    } else {
+        Accent::Normal
+    };
+    Tick {
+        beat,
+        subdivision,
+        accent,
+        is_downbeat: beat == 0 && subdivision == 0,
+        count_in: false,
+    }
+}
+
+// Count-in before the pattern: one accented click per beat for the given number
+// of bars, ignoring any subdivision. Zero bars is no count-in.
+pub fn count_in_ticks(bars: u32, time_signature: TimeSignature) -> Vec<Tick> {
+    (0..bars * time_signature.beats_per_bar)
+        .map(|i| {
+            let beat = i % time_signature.beats_per_bar;
+            Tick {
+                beat,
+                subdivision: 0,
+                accent: Accent::Strong,
+                is_downbeat: beat == 0,
+                count_in: true,
+            }
+        })
+        .collect()
+}
+
+// Every click the metronome plays once started: the count-in, then the pattern forever
+pub fn click_schedule(
+    count_in_bars: u32,
+    time_signature: TimeSignature,
+    subdivision: Subdivision,
+    accented_beats: Vec<u32>,
+) -> impl Iterator<Item = Tick> {
+    let pattern = (0..).map(move |i| subdivided_tick_for(i, time_signature, subdivision, &accented_beats));
+    count_in_ticks(count_in_bars, time_signature).into_iter().chain(pattern)
+}
+
+// What a tempo ramp does once it reaches its target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RampEnd {
+    // Stay at the target tempo
+    #[default]
+    Hold,
+    // Drop back to the start tempo and climb again
+    Loop,
+}
+
+// Speed trainer: starts at start_bpm and raises the tempo by `increment` every
+// bars_per_step bars until it reaches target_bpm
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TempoTrainer {
+    pub start_bpm: u32,
+    pub increment: u32,
+    pub bars_per_step: u32,
+    pub target_bpm: u32,
+    pub end: RampEnd,
+    bars: u64,
+}
+
+impl TempoTrainer {
+    pub fn new(start_bpm: u32, increment: u32, bars_per_step: u32, target_bpm: u32, end: RampEnd) -> Self {
+        TempoTrainer {
+            start_bpm: clamp_bpm(start_bpm),
+            increment,
+            bars_per_step: bars_per_step.max(1),
+            target_bpm: clamp_bpm(target_bpm),
+            end,
+            bars: 0,
+        }
+    }
+
+    // Tempo once the given number of bars have been played. A target below the
+    // start (or no increment) just holds the start tempo.
+    pub fn tempo_after(&self, bars: u64) -> u32 {
+        if self.increment == 0 || self.target_bpm <= self.start_bpm {
+            return self.start_bpm;
+        }
+        // Steps it takes to get from the start to the target (the last may be partial)
+        let steps_to_target = (self.target_bpm - self.start_bpm).div_ceil(self.increment) as u64;
+        let mut steps = bars / self.bars_per_step as u64;
+        if self.end == RampEnd::Loop {
+            // The target gets its full share of bars before dropping back
+            steps %= steps_to_target + 1;
+        }
+        let raised = self.start_bpm as u64 + steps * self.increment as u64;
+        raised.min(self.target_bpm as u64) as u32
+    }
+
+    pub fn tempo(&self) -> u32 {
+        self.tempo_after(self.bars)
+    }
+
+    // Count one more finished bar; returns the new tempo if this bar changed it
+    pub fn bar_completed(&mut self) -> Option<u32> {
+        let before = self.tempo();
+        self.bars += 1;
+        let after = self.tempo();
+        (after != before).then_some(after)
+    }
+
+    pub fn reset(&mut self) {
+        self.bars = 0;
+    }
+}
+
+// A pause longer than this between taps starts a new tempo
+pub const TAP_RESET: Duration = Duration::from_secs(2);
+// Only the most recent taps count, so the tempo follows a player who speeds up
+const MAX_TAPS: usize = 8;
+// Intervals further than this fraction from the median are ignored as mis-taps
+const TAP_OUTLIER_TOLERANCE: f64 = 0.4;
+
+// Infers a tempo from the spacing of recent taps
+#[derive(Default)]
+pub struct TapTempo {
+    taps: Vec<Instant>,
+}
+
+impl TapTempo {
+    pub fn new() -> Self {
+        TapTempo::default()
+    }
+
+    // Register a tap; returns the tempo once there are at least two taps
+    pub fn tap(&mut self, now: Instant) -> Option<u32> {
+        if let Some(&last) = self.taps.last() {
+            if now.saturating_duration_since(last) > TAP_RESET {
+                self.taps.clear();
+            }
+        }
+        self.taps.push(now);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+        self.bpm()
+    }
+
+    // Average of the tap intervals that agree with the median, as BPM
+    pub fn bpm(&self) -> Option<u32> {
+        let mut intervals: Vec<f64> = self.taps
+            .windows(2)
+            .map(|pair| pair[1].saturating_duration_since(pair[0]).as_secs_f64())
+            .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+        intervals.sort_by(|a, b| a.total_cmp(b));
+        let median = intervals[intervals.len() / 2];
+        let steady: Vec<f64> = intervals
+            .into_iter()
+            .filter(|i| (i - median).abs() <= median * TAP_OUTLIER_TOLERANCE)
+            .collect();
+        let average = steady.iter().sum::<f64>() / steady.len() as f64;
+        if average <= 0.0 {
+            return None;
+        }
+        Some(clamp_bpm((60.0 / average).round() as u32))
+    }
+}
+
+pub struct Metronome {
+    bpm: Arc<AtomicU32>,
+    time_signature: TimeSignature,
+    subdivision: Subdivision,
+    swing_ratio: f32,
+    output_latency: Duration,
+    // 0-based beats of the bar that get the strong click
+    accented_beats: Vec<u32>,
+    count_in_bars: u32,
+    trainer: Option<TempoTrainer>,
+    click_enabled: bool,
+    // Visual only: ticks keep coming for the beat flash, but nothing is heard
+    silent: Arc<AtomicBool>,
+    click_sound: ClickSound,
+    volume: Volume,
+    stop_tx: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Metronome {
+    pub fn new(bpm: u32, time_signature: TimeSignature) -> Self {
+        Metronome {
+            bpm: Arc::new(AtomicU32::new(clamp_bpm(bpm))),
+            time_signature,
+            subdivision: Subdivision::default(),
+            swing_ratio: STRAIGHT,
+            output_latency: Duration::ZERO,
+            accented_beats: vec![0],
+            count_in_bars: 0,
+            trainer: None,
+            click_enabled: true,
+            silent: Arc::new(AtomicBool::new(false)),
+            click_sound: ClickSound::default(),
+            volume: Volume::default(),
+            stop_tx: None,
+            thread: None,
+        }
+    }
+
+    pub fn bpm(&self) -> u32 {
+        self.bpm.load(Ordering::SeqCst)
+    }
+
+    // The live tempo, for following a speed trainer's changes from another thread
+    pub fn shared_bpm(&self) -> Arc<AtomicU32> {
+        Arc::clone(&self.bpm)
+    }
+
+    // Takes effect from the next beat if the metronome is running
+    pub fn set_tempo(&self, bpm: u32) {
+        self.bpm.store(clamp_bpm(bpm), Ordering::SeqCst);
+    }
+
+    pub fn time_signature(&self) -> TimeSignature {
+        self.time_signature
+    }
+
+    // Takes effect the next time the metronome is started
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.time_signature = time_signature;
+    }
+
+    pub fn subdivision(&self) -> Subdivision {
+        self.subdivision
+    }
+
+    // Takes effect the next time the metronome is started
+    pub fn set_subdivision(&mut self, subdivision: Subdivision) {
+        self.subdivision = subdivision;
+    }
+
+    pub fn swing_ratio(&self) -> f32 {
+        self.swing_ratio
+    }
+
+    // Swing for eighths and sixteenths, clamped to straight (0.5) up to 0.75; takes
+    // effect the next time the metronome is started
+    pub fn set_swing_ratio(&mut self, ratio: f32) {
+        self.swing_ratio = clamp_swing(ratio);
+    }
+
+    // Added to each click's queue time to give the onset passed to on_tick; clamped to
+    // MAX_OUTPUT_LATENCY_MS. Takes effect the next time the metronome is started
+    pub fn set_output_latency(&mut self, latency: Duration) {
+        self.output_latency = latency.min(Duration::from_millis(MAX_OUTPUT_LATENCY_MS as u64));
+    }
+
+    pub fn accented_beats(&self) -> &[u32] {
+        &self.accented_beats
+    }
+
+    // Accent or un-accent a 0-based beat of the bar; takes effect the next time
+    // the metronome is started. Beats past the end of the bar are ignored.
+    pub fn toggle_accent(&mut self, beat: u32) {
+        if beat >= self.time_signature.beats_per_bar {
+            return;
+        }
+        if let Some(i) = self.accented_beats.iter().position(|&b| b == beat) {
+            self.accented_beats.remove(i);
+        } else {
+            self.accented_beats.push(beat);
+        }
+    }
+
+    // Replace the whole accent pattern; takes effect the next time the metronome is started
+    pub fn set_accented_beats(&mut self, beats: Vec<u32>) {
+        self.accented_beats = beats;
+    }
+
+    // Whether each beat of the bar is accented, for display
+    pub fn accent_pattern(&self) -> Vec<bool> {
+        (0..self.time_signature.beats_per_bar).map(|beat| self.accented_beats.contains(&beat)).collect()
+    }
+
+    pub fn count_in_bars(&self) -> u32 {
+        self.count_in_bars
+    }
+
+    // Bars of clicks to play before the pattern; takes effect the next time the
+    // metronome is started
+    pub fn set_count_in_bars(&mut self, bars: u32) {
+        self.count_in_bars = bars;
+    }
+
+    // Ramp the tempo while running, or None for a steady tempo; takes effect the
+    // next time the metronome is started, which begins at the trainer's start tempo
+    pub fn set_tempo_trainer(&mut self, trainer: Option<TempoTrainer>) {
+        self.trainer = trainer;
+    }
+
+    // Whether the timer thread plays an audible click on each beat
+    pub fn set_click_enabled(&mut self, enabled: bool) {
+        self.click_enabled = enabled;
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.silent.load(Ordering::SeqCst)
+    }
+
+    // Visual-only mode: on_tick still fires for every click, count-in and
+    // subdivisions included, but no click is played. Takes effect from the next click
+    pub fn set_silent(&self, silent: bool) {
+        self.silent.store(silent, Ordering::SeqCst);
+    }
+
+    pub fn click_sound(&self) -> ClickSound {
+        self.click_sound
+    }
+
+    // Takes effect the next time the metronome is started
+    pub fn set_click_sound(&mut self, click_sound: ClickSound) {
+        self.click_sound = click_sound;
+    }
+
+    // Click loudness, 0.0-1.0; takes effect from the next beat
+    pub fn set_volume(&self, gain: f32) {
+        self.volume.set(gain);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    // Start ticking; on_tick is called from the timer thread once per click, with the
+    // moment the click is meant to be heard (queued time plus the output latency)
+    pub fn start<F>(&mut self, on_tick: F)
+    where
+        F: Fn(Tick, Instant) + Send + 'static,
+    {
+        self.stop();
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let mut trainer = self.trainer;
+        if let Some(ref mut trainer) = trainer {
+            trainer.reset();
+            self.set_tempo(trainer.tempo());
+        }
+        let timing = self.click_timing(trainer);
+        let clicks = click_schedule(self.count_in_bars, self.time_signature, self.subdivision, self.accented_beats.clone());
+        let click_enabled = self.click_enabled;
+        let click_sound = self.click_sound;
+
+        let thread = std::thread::spawn(move || {
+            // The audio stream isn't Send, so the timer thread opens its own player.
+            // If there's no audio device the metronome still ticks silently.
+            let mut player = if click_enabled { AudioPlayer::new().ok() } else { None };
+            if let Some(ref mut player) = player {
+                player.set_click_sound(click_sound);
+            }
+            run_clicks(player.as_mut(), clicks, timing, &stop_rx, on_tick);
+            if let Some(ref player) = player {
+                player.stop();
+            }
+        });
+
+        self.stop_tx = Some(stop_tx);
+        self.thread = Some(thread);
+    }
+
+    // What the timer thread needs from the current setup; the trainer is its own copy
+    fn click_timing(&self, trainer: Option<TempoTrainer>) -> ClickTiming {
+        ClickTiming {
+            bpm: Arc::clone(&self.bpm),
+            trainer,
+            subdivision: self.subdivision,
+            swing_ratio: self.swing_ratio,
+            output_latency: self.output_latency,
+            volume: self.volume.clone(),
+            silent: Arc::clone(&self.silent),
+        }
+    }
+
+    // Stop ticking and wait for the timer thread to finish
+    pub fn stop(&mut self) {
+        if let Some(stop_tx) = self.stop_tx.take() {
+            let _ = stop_tx.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// How the timer thread paces and sounds the clicks, taken from the metronome at start
+struct ClickTiming {
+    bpm: Arc<AtomicU32>,
+    trainer: Option<TempoTrainer>,
+    subdivision: Subdivision,
+    swing_ratio: f32,
+    output_latency: Duration,
+    volume: Volume,
+    silent: Arc<AtomicBool>,
+}
+
+// The timer thread's loop: sound each click (unless silent), report it to on_tick,
+// then wait for the next one, until the schedule runs out or stop_rx fires
+fn run_clicks<P, F>(
+    mut player: Option<&mut P>,
+    clicks: impl Iterator<Item = Tick>,
+    mut timing: ClickTiming,
+    stop_rx: &Receiver<()>,
+    on_tick: F,
+) where
+    P: ClickPlayer,
+    F: Fn(Tick, Instant),
+{
+    let mut next_click = Instant::now();
+    let mut pattern_started = false;
+    for tick in clicks {
+        // Each pattern downbeat after the first ends a bar of the ramp
+        if tick.is_downbeat && !tick.count_in {
+            if pattern_started {
+                if let Some(raised) = timing.trainer.as_mut().and_then(TempoTrainer::bar_completed) {
+                    timing.bpm.store(raised, Ordering::SeqCst);
+                }
+            }
+            pattern_started = true;
+        }
+        let queued_at = Instant::now();
+        // With no click to line up with, the flash isn't held back for the output latency
+        let silent = timing.silent.load(Ordering::SeqCst);
+        let latency = if silent { Duration::ZERO } else { timing.output_latency };
+        if let Some(player) = player.as_deref_mut().filter(|_| !silent) {
+            player.click(tick.accent == Accent::Strong, timing.volume.get() * tick.accent.gain());
+        }
+        on_tick(tick, click_onset(queued_at, latency));
+
+        // Schedule from the previous deadline so clicks don't drift;
+        // the count-in clicks once per beat whatever the subdivision
+        let clicks_per_beat = if tick.count_in { 1 } else { timing.subdivision.clicks_per_beat() };
+        let beat = beat_interval(timing.bpm.load(Ordering::SeqCst));
+        next_click += click_interval(beat, clicks_per_beat, tick.subdivision, timing.swing_ratio);
+        let wait = next_click.saturating_duration_since(Instant::now());
+        match stop_rx.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            _ => break,
+        }
+    }
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Metronome::new(DEFAULT_BPM, TimeSignature::default())
+    }
+}
+
+impl Drop for Metronome {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_clamp_bpm() {
+        assert_eq!(clamp_bpm(5), MIN_BPM);
+        assert_eq!(clamp_bpm(120), 120);
+        assert_eq!(clamp_bpm(1000), MAX_BPM);
+
+        let metronome = Metronome::default();
+        metronome.set_tempo(999);
+        assert_eq!(metronome.bpm(), MAX_BPM);
+    }
+
+    #[test]
+    fn test_beat_interval() {
+        assert_eq!(beat_interval(60), Duration::from_secs(1));
+        assert_eq!(beat_interval(120), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_swung_eighths() {
+        let beat = beat_interval(60);
+        // 2:1 swing: the eighth on the beat takes two thirds, the "and" one third
+        let first = click_interval(beat, 2, 0, 2.0 / 3.0);
+        let second = click_interval(beat, 2, 1, 2.0 / 3.0);
+        assert!((first.as_secs_f64() - 2.0 / 3.0).abs() < 1e-6);
+        assert!((second.as_secs_f64() - 1.0 / 3.0).abs() < 1e-6);
+        assert!(((first + second).as_secs_f64() - 1.0).abs() < 1e-6);
+
+        // Straight is even, and so are triplets and quarters whatever the swing
+        assert_eq!(click_interval(beat, 2, 1, STRAIGHT), Duration::from_millis(500));
+        assert_eq!(click_interval(beat, 3, 0, 0.7), beat / 3);
+        assert_eq!(click_interval(beat, 1, 0, 0.7), beat);
+        // Sixteenths swing in pairs within each half beat
+        assert_eq!(click_interval(beat, 4, 2, 0.75), Duration::from_millis(375));
+        assert_eq!(click_interval(beat, 4, 3, 0.75), Duration::from_millis(125));
+
+        let mut metronome = Metronome::default();
+        assert_eq!(metronome.swing_ratio(), STRAIGHT);
+        metronome.set_swing_ratio(0.9);
+        assert_eq!(metronome.swing_ratio(), MAX_SWING);
+        metronome.set_swing_ratio(0.2);
+        assert_eq!(metronome.swing_ratio(), STRAIGHT);
+    }
+
+    #[test]
+    fn test_flash_delay_compensates_latency() {
+        let queued_at = Instant::now();
+        let latency = Duration::from_millis(80);
+        let onset = click_onset(queued_at, latency);
+        // Straight after queueing, the flash waits out the whole latency
+        assert_eq!(flash_delay(onset, queued_at), latency);
+        // The hop to the UI thread took 30ms, so 50ms remain
+        assert_eq!(flash_delay(onset, queued_at + Duration::from_millis(30)), Duration::from_millis(50));
+        // Running late, or with no latency set, it flashes at once
+        assert_eq!(flash_delay(onset, queued_at + Duration::from_millis(120)), Duration::ZERO);
+        assert_eq!(flash_delay(click_onset(queued_at, Duration::ZERO), queued_at), Duration::ZERO);
+
+        assert_eq!(clamp_output_latency_ms(2000), MAX_OUTPUT_LATENCY_MS);
+        // Too long a latency is clamped before it reaches the onsets on_tick sees
+        let mut metronome = Metronome::default();
+        metronome.set_output_latency(Duration::from_secs(2));
+        let max = Duration::from_millis(MAX_OUTPUT_LATENCY_MS as u64);
+        let clicks = click_schedule(0, TimeSignature::default(), Subdivision::Quarter, vec![0]).take(1);
+        // With the sender gone the run ends after the click instead of waiting out the beat
+        let (_, stop_rx) = mpsc::channel::<()>();
+        let onsets = std::cell::RefCell::new(Vec::new());
+        let before = Instant::now();
+        run_clicks(None::<&mut crate::audio::RecordingPlayer>, clicks, metronome.click_timing(None), &stop_rx, |_, onset| {
+            onsets.borrow_mut().push(onset)
+        });
+        let onset = onsets.into_inner()[0];
+        assert!(onset >= before + max && onset <= Instant::now() + max);
+    }
+
+    #[test]
+    fn test_downbeats_follow_time_signature() {
+        let three_four = TimeSignature::new(3, 4);
+        let tick = |i| subdivided_tick_for(i, three_four, Subdivision::Quarter, &[0]);
+        let downbeats: Vec<bool> = (0..7).map(|i| tick(i).is_downbeat).collect();
+        assert_eq!(downbeats, vec![true, false, false, true, false, false, true]);
+        assert_eq!(tick(5).beat, 2);
+    }
+
+    #[test]
+    fn test_eighth_note_bar() {
+        let four_four = TimeSignature::new(4, 4);
+        let bar: Vec<Tick> = (0..8).map(|i| subdivided_tick_for(i, four_four, Subdivision::Eighth, &[0, 2])).collect();
+        assert_eq!(bar.len(), 8);
+
+        // Beats 1 and 3 accented, 2 and 4 normal, the "ands" weak
+        let accents: Vec<Accent> = bar.iter().map(|t| t.accent).collect();
+        use Accent::*;
+        assert_eq!(accents, vec![Strong, Weak, Normal, Weak, Strong, Weak, Normal, Weak]);
+        let positions: Vec<(u32, u32)> = bar.iter().map(|t| (t.beat, t.subdivision)).collect();
+        assert_eq!(positions, vec![(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1), (3, 0), (3, 1)]);
+        assert!(bar[0].is_downbeat && !bar[1].is_downbeat);
+
+        // The next bar starts over
+        assert_eq!(subdivided_tick_for(8, four_four, Subdivision::Eighth, &[0, 2]), bar[0]);
+        assert!(bar.iter().all(|t| !t.count_in));
+
+        // Triplets: three clicks per beat
+        assert_eq!(subdivided_tick_for(5, four_four, Subdivision::Triplet, &[0]).beat, 1);
+        assert_eq!(subdivided_tick_for(5, four_four, Subdivision::Triplet, &[0]).subdivision, 2);
+    }
+
+    #[test]
+    fn test_accent_pattern() {
+        let mut metronome = Metronome::default();
+        assert_eq!(metronome.accent_pattern(), vec![true, false, false, false]);
+        metronome.toggle_accent(2);
+        assert_eq!(metronome.accent_pattern(), vec![true, false, true, false]);
+        metronome.toggle_accent(0);
+        assert_eq!(metronome.accented_beats(), &[2]);
+        // There's no fifth beat in 4/4 to accent
+        metronome.toggle_accent(4);
+        assert_eq!(metronome.accented_beats(), &[2]);
+
+        // A shorter bar hides accents past its end
+        metronome.set_time_signature(TimeSignature::new(2, 4));
+        assert_eq!(metronome.accent_pattern(), vec![false, false]);
+        metronome.set_accented_beats(vec![1]);
+        assert_eq!(metronome.accent_pattern(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_count_in_precedes_pattern() {
+        let four_four = TimeSignature::new(4, 4);
+        let clicks: Vec<Tick> = click_schedule(2, four_four, Subdivision::Eighth, vec![0]).take(10).collect();
+        // Two bars of accented quarter-note clicks, then the pattern from its downbeat
+        assert!(clicks[..8].iter().all(|t| t.count_in && t.accent == Accent::Strong && t.subdivision == 0));
+        let beats: Vec<u32> = clicks[..8].iter().map(|t| t.beat).collect();
+        assert_eq!(beats, vec![0, 1, 2, 3, 0, 1, 2, 3]);
+        assert_eq!(clicks[8], subdivided_tick_for(0, four_four, Subdivision::Eighth, &[0]));
+        assert!(!clicks[9].count_in);
+
+        // 3/4 counts three beats a bar; zero bars starts straight away
+        assert_eq!(count_in_ticks(1, TimeSignature::new(3, 4)).len(), 3);
+        let first = click_schedule(0, four_four, Subdivision::Quarter, vec![0]).next().unwrap();
+        assert!(!first.count_in && first.is_downbeat);
+    }
+
+    #[test]
+    fn test_tempo_trainer_schedule() {
+        // 60 BPM, +10 every 4 bars: 70 after bar 4, 80 after bar 8, 90 after bar 12
+        let mut trainer = TempoTrainer::new(60, 10, 4, 100, RampEnd::Hold);
+        assert_eq!(trainer.tempo_after(12), 90);
+        let changes: Vec<(u64, u32)> = (1..=12)
+            .filter_map(|bar| trainer.bar_completed().map(|bpm| (bar, bpm)))
+            .collect();
+        assert_eq!(changes, vec![(4, 70), (8, 80), (12, 90)]);
+        assert_eq!(trainer.tempo(), 90);
+
+        // Holding stays at the target; a step that would overshoot stops on it
+        assert_eq!(trainer.tempo_after(400), 100);
+        assert_eq!(TempoTrainer::new(60, 15, 2, 100, RampEnd::Hold).tempo_after(6), 100);
+
+        // Looping drops back once the target has had its bars
+        let looping = TempoTrainer::new(60, 10, 4, 80, RampEnd::Loop);
+        let tempos: Vec<u32> = [0, 4, 8, 11, 12, 16].iter().map(|&bars| looping.tempo_after(bars)).collect();
+        assert_eq!(tempos, vec![60, 70, 80, 80, 60, 70]);
+
+        // Nothing to climb: the start tempo holds
+        assert_eq!(TempoTrainer::new(120, 10, 4, 100, RampEnd::Loop).tempo_after(40), 120);
+        assert_eq!(TempoTrainer::new(60, 0, 4, 100, RampEnd::Hold).tempo_after(40), 60);
+        // A reset starts the count over: back to 60, and 70 again four bars later
+        trainer.reset();
+        assert_eq!(trainer.tempo(), 60);
+        assert_eq!((1..=4).filter_map(|_| trainer.bar_completed()).collect::<Vec<_>>(), vec![70]);
+    }
+
+    #[test]
+    fn test_tap_tempo() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut tapper = TapTempo::new();
+
+        // One tap isn't a tempo yet
+        assert_eq!(tapper.tap(at(0)), None);
+        assert_eq!(tapper.tap(at(500)), Some(120));
+        assert_eq!(tapper.tap(at(1000)), Some(120));
+        assert_eq!(tapper.tap(at(1500)), Some(120));
+
+        // A hesitant tap is ignored rather than dragging the tempo down
+        tapper.tap(at(2400));
+        assert_eq!(tapper.tap(at(2900)), Some(120));
+
+        // A long pause starts over
+        assert_eq!(tapper.tap(at(6000)), None);
+        assert_eq!(tapper.tap(at(7000)), Some(60));
+
+        // Absurdly fast taps are clamped to the metronome's range
+        let mut tapper = TapTempo::new();
+        tapper.tap(at(10_000));
+        assert_eq!(tapper.tap(at(10_010)), Some(MAX_BPM));
+    }
+
+    #[test]
+    fn test_silent_mode_ticks_without_clicking() {
+        let timing = |silent: bool| ClickTiming {
+            bpm: Arc::new(AtomicU32::new(MAX_BPM)),
+            trainer: None,
+            subdivision: Subdivision::Sixteenth,
+            swing_ratio: STRAIGHT,
+            output_latency: Duration::ZERO,
+            volume: Volume::default(),
+            silent: Arc::new(AtomicBool::new(silent)),
+        };
+        // A bar of 2/4 count-in, then the first beat in sixteenths
+        let clicks = || click_schedule(1, TimeSignature::new(2, 4), Subdivision::Sixteenth, vec![0]).take(6);
+        let (_stop_tx, stop_rx) = mpsc::channel::<()>();
+
+        let mut player = crate::audio::RecordingPlayer::default();
+        let ticks = std::cell::RefCell::new(Vec::new());
+        run_clicks(Some(&mut player), clicks(), timing(true), &stop_rx, |tick, _| ticks.borrow_mut().push(tick));
+        let ticks = ticks.into_inner();
+        assert_eq!(ticks.len(), 6);
+        assert_eq!(ticks.iter().filter(|t| t.count_in).count(), 2);
+        assert_eq!(ticks[2..].iter().map(|t| t.subdivision).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert!(player.clicks.is_empty());
+        assert!(player.played.borrow().is_empty());
+
+        // The same run with sound clicks once per tick
+        run_clicks(Some(&mut player), clicks(), timing(false), &stop_rx, |_, _| {});
+        assert_eq!(player.clicks, vec![true, true, true, false, false, false]);
+    }
+
+    #[test]
+    fn test_start_stop_joins_thread() {
+        let ticks = Arc::new(Mutex::new(Vec::new()));
+        let mut metronome = Metronome::new(MAX_BPM, TimeSignature::new(2, 4));
+        metronome.set_click_enabled(false);
+
+        let recorded = Arc::clone(&ticks);
+        metronome.start(move |tick, _| recorded.lock().unwrap().push(tick));
+        assert!(metronome.is_running());
+        std::thread::sleep(Duration::from_millis(450));
+        metronome.stop();
+        assert!(!metronome.is_running());
+
+        // No more ticks arrive once stopped
+        let count = ticks.lock().unwrap().len();
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(ticks.lock().unwrap().len(), count);
+
+        // 300 BPM = a beat every 200ms: beats at 0, 200 and 400ms
+        let ticks = ticks.lock().unwrap();
+        assert!(ticks.len() >= 2);
+        assert!(ticks[0].is_downbeat);
+        assert!(!ticks[1].is_downbeat);
+    }
+}
@@ -0,0 +1,265 @@
+// Standard MIDI File export of recorded practice sessions
+
+use crate::music_theory::Note;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Ticks per quarter note written in the MThd header
+pub const TICKS_PER_QUARTER: u16 = 480;
+
+const NOTE_ON_VELOCITY: u8 = 100;
+const NOTE_OFF_VELOCITY: u8 = 64;
+
+// A single note captured during a session, timed from the start of the recording
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedNote {
+    pub note: Note,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+// Captures the notes played while recording is switched on. The take stays
+// available for export after recording stops, until the next one starts.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    started: Option<Instant>,
+    // Set while recording; `take` keeps its notes either way
+    recording: bool,
+    take: Vec<RecordedNote>,
+    // Index in `take` of a note still held down, its length not yet known
+    held: Option<usize>,
+}
+
+impl Recorder {
+    // Start a new take, dropping the last one
+    pub fn start(&mut self, now: Instant) {
+        *self = Recorder { started: Some(now), recording: true, ..Recorder::default() };
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        self.release(now);
+        self.recording = false;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn take(&self) -> &[RecordedNote] {
+        &self.take
+    }
+
+    // A note of known length, e.g. a fret click
+    pub fn record(&mut self, note: Note, at: Instant, duration: Duration) {
+        let Some(started) = self.started.filter(|_| self.recording) else { return };
+        self.take.push(RecordedNote { note, start: at.saturating_duration_since(started), duration });
+    }
+
+    // A note held down, lasting until release
+    pub fn hold(&mut self, note: Note, at: Instant) {
+        self.release(at);
+        self.record(note, at, Duration::ZERO);
+        if self.recording {
+            self.held = Some(self.take.len() - 1);
+        }
+    }
+
+    pub fn release(&mut self, at: Instant) {
+        let (Some(index), Some(started)) = (self.held.take(), self.started) else { return };
+        let note = &mut self.take[index];
+        note.duration = at.saturating_duration_since(started).saturating_sub(note.start);
+    }
+}
+
+// Write the recording as a format 0 Standard MIDI File at the given tempo (BPM)
+pub fn export_midi(events: &[RecordedNote], path: &Path, tempo: f32) -> std::io::Result<()> {
+    std::fs::write(path, encode_midi(events, tempo))
+}
+
+// Encode the recording as the bytes of a format 0 Standard MIDI File
+pub fn encode_midi(events: &[RecordedNote], tempo: f32) -> Vec<u8> {
+    let tempo = tempo.max(1.0);
+
+    // (tick, is_note_on, midi note) for every note boundary
+    let mut messages: Vec<(u32, bool, u8)> = Vec::with_capacity(events.len() * 2);
+    for event in events {
+        let midi = event.note.to_midi().clamp(0, 127) as u8;
+        let start = seconds_to_ticks(event.start.as_secs_f32(), tempo);
+        let end = seconds_to_ticks((event.start + event.duration).as_secs_f32(), tempo);
+        messages.push((start, true, midi));
+        messages.push((end.max(start), false, midi));
+    }
+    // Note-offs sort before note-ons at the same tick so repeated notes retrigger
+    messages.sort_by_key(|&(tick, is_on, _)| (tick, is_on));
+
+    let mut track = Vec::new();
+
+    // Tempo meta event: microseconds per quarter note
+    let micros_per_quarter = (60_000_000.0 / tempo).round() as u32;
+    write_var_len(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x51, 0x03]);
+    track.extend_from_slice(&micros_per_quarter.to_be_bytes()[1..]);
+
+    let mut last_tick = 0;
+    for (tick, is_on, midi) in messages {
+        write_var_len(&mut track, tick - last_tick);
+        last_tick = tick;
+        if is_on {
+            track.extend_from_slice(&[0x90, midi, NOTE_ON_VELOCITY]);
+        } else {
+            track.extend_from_slice(&[0x80, midi, NOTE_OFF_VELOCITY]);
+        }
+    }
+
+    // End of track
+    write_var_len(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+
+    let mut data = Vec::with_capacity(22 + track.len());
+    data.extend_from_slice(b"MThd");
+    data.extend_from_slice(&6u32.to_be_bytes());
+    data.extend_from_slice(&0u16.to_be_bytes()); // Format 0
+    data.extend_from_slice(&1u16.to_be_bytes()); // One track
+    data.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    data.extend_from_slice(b"MTrk");
+    data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    data.extend_from_slice(&track);
+    data
+}
+
+fn seconds_to_ticks(seconds: f32, tempo: f32) -> u32 {
+    (seconds * tempo / 60.0 * TICKS_PER_QUARTER as f32).round() as u32
+}
+
+// MIDI variable-length quantity: 7 bits per byte, high bit set on all but the last
+fn write_var_len(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music_theory::Key;
+
+    fn read_var_len(data: &[u8], pos: &mut usize) -> u32 {
+        let mut value = 0;
+        loop {
+            let byte = data[*pos];
+            *pos += 1;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    // Count (note-on, note-off) messages in the single track
+    fn count_note_events(data: &[u8]) -> (usize, usize) {
+        let mut pos = 22; // MThd (14 bytes) + MTrk header (8 bytes)
+        let (mut ons, mut offs) = (0, 0);
+        while pos < data.len() {
+            read_var_len(data, &mut pos);
+            match data[pos] {
+                0xFF => {
+                    pos += 2;
+                    let len = read_var_len(data, &mut pos) as usize;
+                    pos += len;
+                }
+                0x90 => { ons += 1; pos += 3; }
+                0x80 => { offs += 1; pos += 3; }
+                other => panic!("unexpected status byte {:#x}", other),
+            }
+        }
+        (ons, offs)
+    }
+
+    fn small_recording() -> Vec<RecordedNote> {
+        let quarter = Duration::from_millis(500); // One beat at 120 BPM
+        [Key::C, Key::E, Key::G]
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| RecordedNote {
+                note: Note::new(key, 4),
+                start: quarter * i as u32,
+                duration: quarter,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recorder_times_notes_from_the_start() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut recorder = Recorder::default();
+        // Nothing is captured until recording starts
+        recorder.record(Note::new(Key::C, 4), start, ms(300));
+        assert!(recorder.take().is_empty());
+
+        recorder.start(start);
+        recorder.record(Note::new(Key::C, 4), start + ms(100), ms(300));
+        recorder.hold(Note::new(Key::E, 4), start + ms(500));
+        recorder.release(start + ms(1250));
+        recorder.hold(Note::new(Key::G, 4), start + ms(1500));
+        // Stopping ends the held note; later notes aren't captured
+        recorder.stop(start + ms(2000));
+        recorder.record(Note::new(Key::A, 4), start + ms(2500), ms(300));
+        assert!(!recorder.is_recording());
+
+        let timing: Vec<(Duration, Duration)> = recorder.take().iter().map(|n| (n.start, n.duration)).collect();
+        assert_eq!(timing, vec![(ms(100), ms(300)), (ms(500), ms(750)), (ms(1500), ms(500))]);
+
+        // A new take starts empty
+        recorder.start(start + ms(3000));
+        assert!(recorder.take().is_empty());
+    }
+
+    #[test]
+    fn test_var_len_encoding() {
+        let mut out = Vec::new();
+        write_var_len(&mut out, 0);
+        write_var_len(&mut out, 0x7F);
+        write_var_len(&mut out, 0x80);
+        write_var_len(&mut out, 480);
+        assert_eq!(out, vec![0x00, 0x7F, 0x81, 0x00, 0x83, 0x60]);
+    }
+
+    #[test]
+    fn test_export_midi_file() {
+        let path = std::env::temp_dir().join("guitar-practice-dashboard-test.mid");
+        export_midi(&small_recording(), &path, 120.0).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // Valid MThd header: format 0, one track, our division
+        assert_eq!(&data[0..4], b"MThd");
+        assert_eq!(&data[4..8], &6u32.to_be_bytes());
+        assert_eq!(&data[8..10], &0u16.to_be_bytes());
+        assert_eq!(&data[10..12], &1u16.to_be_bytes());
+        assert_eq!(&data[12..14], &TICKS_PER_QUARTER.to_be_bytes());
+        assert_eq!(&data[14..18], b"MTrk");
+
+        // Track length matches the remaining bytes
+        let track_len = u32::from_be_bytes([data[18], data[19], data[20], data[21]]) as usize;
+        assert_eq!(track_len, data.len() - 22);
+
+        assert_eq!(count_note_events(&data), (3, 3));
+    }
+
+    #[test]
+    fn test_export_midi_timing() {
+        let data = encode_midi(&small_recording(), 120.0);
+        // Tempo meta event for 120 BPM = 500000 us per quarter
+        assert_eq!(&data[23..29], &[0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]);
+        // First note-on (C4 = 60) at tick 0
+        assert_eq!(&data[29..33], &[0x00, 0x90, 60, NOTE_ON_VELOCITY]);
+        // Its note-off one quarter (480 ticks) later
+        assert_eq!(&data[33..38], &[0x83, 0x60, 0x80, 60, NOTE_OFF_VELOCITY]);
+    }
+}
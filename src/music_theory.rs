@@ -1,5 +1,7 @@
 // Music theory calculations for guitar fretboard
 
+use once_cell::sync::Lazy;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Key {
     C,
@@ -68,6 +70,51 @@ impl Key {
             Key::B => "B",
         }
     }
+
+    // Flat spelling of the note name (e.g. "Bb" instead of "A#")
+    pub fn flat_name(self) -> &'static str {
+        match self {
+            Key::Cs => "Db",
+            Key::Ds => "Eb",
+            Key::Fs => "Gb",
+            Key::Gs => "Ab",
+            Key::As => "Bb",
+            _ => self.name(),
+        }
+    }
+
+    pub fn name_with(self, spelling: NoteSpelling) -> &'static str {
+        match spelling {
+            NoteSpelling::Sharp => self.name(),
+            NoteSpelling::Flat => self.flat_name(),
+        }
+    }
+
+    // Name of this note spelled the way it would be in the given key
+    pub fn name_in_key(self, tonic: Key) -> &'static str {
+        self.name_with(NoteSpelling::for_key(tonic))
+    }
+
+    // Sharps/flats: the black keys on a piano
+    pub fn is_accidental(self) -> bool {
+        matches!(self, Key::Cs | Key::Ds | Key::Fs | Key::Gs | Key::As)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoteSpelling {
+    Sharp,
+    Flat,
+}
+
+impl NoteSpelling {
+    // Flat keys (F, Bb, Eb, Ab, Db) use flats; C and the sharp keys use sharps
+    pub fn for_key(tonic: Key) -> NoteSpelling {
+        match tonic {
+            Key::F | Key::As | Key::Ds | Key::Gs | Key::Cs => NoteSpelling::Flat,
+            _ => NoteSpelling::Sharp,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -82,12 +129,68 @@ impl Note {
     }
 
     pub fn name(self) -> String {
-        format!("{}{}", self.note.name(), self.octave)
+        self.name_with(NoteSpelling::Sharp)
+    }
+
+    pub fn name_with(self, spelling: NoteSpelling) -> String {
+        format!("{}{}", self.note.name_with(spelling), self.octave)
+    }
+
+    pub fn name_in_key(self, tonic: Key) -> String {
+        self.name_with(NoteSpelling::for_key(tonic))
     }
 
     pub fn semitone_value(self) -> i32 {
         self.note.to_int() + (self.octave * 12)
     }
+
+    // Note the given number of semitones away (negative = down)
+    pub fn transpose(self, semitones: i32) -> Note {
+        let value = self.semitone_value() + semitones;
+        Note::new(Key::from_int(value.rem_euclid(12)), value.div_euclid(12))
+    }
+
+    // MIDI note number (C4 = 60, A4 = 69)
+    pub fn to_midi(self) -> i32 {
+        self.semitone_value() + 12
+    }
+
+    // Inverse of to_midi; MIDI 0 is C-1
+    pub fn from_midi(midi: i32) -> Note {
+        Note::new(Key::C, -1).transpose(midi)
+    }
+
+    // Parse a typed note name like "C", "F#4" or "Bb3"; flats map onto the sharp-named
+    // keys, and a missing octave means octave 4. Cb and B# cross into the neighbouring
+    // octave the way they sound (Cb4 is B3). Octaves outside -1..=9 (past the MIDI
+    // range) are rejected, so the semitone math can't overflow.
+    pub fn parse(s: &str) -> Option<Note> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        let natural = match chars.next()?.to_ascii_uppercase() {
+            'C' => Key::C,
+            'D' => Key::D,
+            'E' => Key::E,
+            'F' => Key::F,
+            'G' => Key::G,
+            'A' => Key::A,
+            'B' => Key::B,
+            _ => return None,
+        };
+        let rest = chars.as_str();
+        let (shift, octave) = if let Some(octave) = rest.strip_prefix('#') {
+            (1, octave)
+        } else if let Some(octave) = rest.strip_prefix('b') {
+            (-1, octave)
+        } else {
+            (0, rest)
+        };
+        let octave = if octave.is_empty() { 4 } else { octave.parse().ok()? };
+        if !(-1..=9).contains(&octave) {
+            return None;
+        }
+        Some(Note::new(natural, octave).transpose(shift))
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -98,9 +201,39 @@ pub enum Scale {
     MinorPentatonic,
     MajorBlues,
     MinorBlues,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    HarmonicMinor,
+    MelodicMinor,
+    WholeTone,
+    HalfWholeDiminished,
+    WholeHalfDiminished,
 }
 
 impl Scale {
+    // Every scale, in tag order
+    pub const ALL: [Scale; 16] = [
+        Scale::Major,
+        Scale::NaturalMinor,
+        Scale::MajorPentatonic,
+        Scale::MinorPentatonic,
+        Scale::MajorBlues,
+        Scale::MinorBlues,
+        Scale::Dorian,
+        Scale::Phrygian,
+        Scale::Lydian,
+        Scale::Mixolydian,
+        Scale::Locrian,
+        Scale::HarmonicMinor,
+        Scale::MelodicMinor,
+        Scale::WholeTone,
+        Scale::HalfWholeDiminished,
+        Scale::WholeHalfDiminished,
+    ];
+
     pub fn from_int(value: i32) -> Scale {
         match value {
             1 => Scale::Major,
@@ -109,6 +242,16 @@ impl Scale {
             4 => Scale::MinorPentatonic,
             5 => Scale::MajorBlues,
             6 => Scale::MinorBlues,
+            7 => Scale::Dorian,
+            8 => Scale::Phrygian,
+            9 => Scale::Lydian,
+            10 => Scale::Mixolydian,
+            11 => Scale::Locrian,
+            12 => Scale::HarmonicMinor,
+            13 => Scale::MelodicMinor,
+            14 => Scale::WholeTone,
+            15 => Scale::HalfWholeDiminished,
+            16 => Scale::WholeHalfDiminished,
             _ => Scale::Major,
         }
     }
@@ -121,6 +264,16 @@ impl Scale {
             Scale::MinorPentatonic => 4,
             Scale::MajorBlues => 5,
             Scale::MinorBlues => 6,
+            Scale::Dorian => 7,
+            Scale::Phrygian => 8,
+            Scale::Lydian => 9,
+            Scale::Mixolydian => 10,
+            Scale::Locrian => 11,
+            Scale::HarmonicMinor => 12,
+            Scale::MelodicMinor => 13,
+            Scale::WholeTone => 14,
+            Scale::HalfWholeDiminished => 15,
+            Scale::WholeHalfDiminished => 16,
         }
     }
 
@@ -132,11 +285,21 @@ impl Scale {
             Scale::MinorPentatonic => "Minor Pentatonic",
             Scale::MajorBlues => "Major Blues",
             Scale::MinorBlues => "Minor Blues",
+            Scale::Dorian => "Dorian",
+            Scale::Phrygian => "Phrygian",
+            Scale::Lydian => "Lydian",
+            Scale::Mixolydian => "Mixolydian",
+            Scale::Locrian => "Locrian",
+            Scale::HarmonicMinor => "Harmonic Minor",
+            Scale::MelodicMinor => "Melodic Minor",
+            Scale::WholeTone => "Whole Tone",
+            Scale::HalfWholeDiminished => "Diminished (Half-Whole)",
+            Scale::WholeHalfDiminished => "Diminished (Whole-Half)",
         }
     }
 
     // Returns semitone intervals from root for the scale
-    fn intervals(self) -> &'static [i32] {
+    pub fn intervals(self) -> &'static [i32] {
         match self {
             Scale::Major => &[0, 2, 4, 5, 7, 9, 11], // W-W-H-W-W-W-H
             Scale::NaturalMinor => &[0, 2, 3, 5, 7, 8, 10], // W-H-W-W-H-W-W
@@ -144,35 +307,308 @@ impl Scale {
             Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
             Scale::MajorBlues => &[0, 3, 4, 7, 9],
             Scale::MinorBlues => &[0, 3, 5, 6, 7, 10],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10], // W-H-W-W-W-H-W
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10], // H-W-W-W-H-W-W
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11], // W-W-W-H-W-W-H
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10], // W-W-H-W-W-H-W
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10], // H-W-W-H-W-W-W
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11], // W-H-W-W-H-A2-H
+            Scale::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11], // W-H-W-W-W-W-H (ascending)
+            Scale::WholeTone => &[0, 2, 4, 6, 8, 10], // W-W-W-W-W-W
+            Scale::HalfWholeDiminished => &[0, 1, 3, 4, 6, 7, 9, 10], // H-W-H-W-H-W-H-W
+            Scale::WholeHalfDiminished => &[0, 2, 3, 5, 6, 8, 9, 11], // W-H-W-H-W-H-W-H
+        }
+    }
+}
+
+// Open-string notes for each string (index 0 = lowest string); the number of
+// strings depends on the instrument
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tuning {
+    pub strings: &'static [Note],
+}
+
+impl Tuning {
+    // Standard guitar tuning (E2, A2, D3, G3, B3, E4)
+    pub const STANDARD: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::E, octave: 2 }, // String 6 (low E)
+            Note { note: Key::A, octave: 2 }, // String 5
+            Note { note: Key::D, octave: 3 }, // String 4
+            Note { note: Key::G, octave: 3 }, // String 3
+            Note { note: Key::B, octave: 3 }, // String 2
+            Note { note: Key::E, octave: 4 }, // String 1 (high E)
+        ],
+    };
+
+    // Drop D (D2, A2, D3, G3, B3, E4)
+    pub const DROP_D: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::D, octave: 2 },
+            Note { note: Key::A, octave: 2 },
+            Note { note: Key::D, octave: 3 },
+            Note { note: Key::G, octave: 3 },
+            Note { note: Key::B, octave: 3 },
+            Note { note: Key::E, octave: 4 },
+        ],
+    };
+
+    // DADGAD (D2, A2, D3, G3, A3, D4)
+    pub const DADGAD: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::D, octave: 2 },
+            Note { note: Key::A, octave: 2 },
+            Note { note: Key::D, octave: 3 },
+            Note { note: Key::G, octave: 3 },
+            Note { note: Key::A, octave: 3 },
+            Note { note: Key::D, octave: 4 },
+        ],
+    };
+
+    // Open G (D2, G2, D3, G3, B3, D4)
+    pub const OPEN_G: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::D, octave: 2 },
+            Note { note: Key::G, octave: 2 },
+            Note { note: Key::D, octave: 3 },
+            Note { note: Key::G, octave: 3 },
+            Note { note: Key::B, octave: 3 },
+            Note { note: Key::D, octave: 4 },
+        ],
+    };
+
+    // Half step down (Eb2, Ab2, Db3, Gb3, Bb3, Eb4)
+    pub const HALF_STEP_DOWN: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::Ds, octave: 2 },
+            Note { note: Key::Gs, octave: 2 },
+            Note { note: Key::Cs, octave: 3 },
+            Note { note: Key::Fs, octave: 3 },
+            Note { note: Key::As, octave: 3 },
+            Note { note: Key::Ds, octave: 4 },
+        ],
+    };
+
+    // 7-string guitar: standard six plus a low B (B1, E2, A2, D3, G3, B3, E4)
+    pub const SEVEN_STRING: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::B, octave: 1 },
+            Note { note: Key::E, octave: 2 },
+            Note { note: Key::A, octave: 2 },
+            Note { note: Key::D, octave: 3 },
+            Note { note: Key::G, octave: 3 },
+            Note { note: Key::B, octave: 3 },
+            Note { note: Key::E, octave: 4 },
+        ],
+    };
+
+    // 7-string Drop A (A1, E2, A2, D3, G3, B3, E4)
+    pub const SEVEN_STRING_DROP_A: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::A, octave: 1 },
+            Note { note: Key::E, octave: 2 },
+            Note { note: Key::A, octave: 2 },
+            Note { note: Key::D, octave: 3 },
+            Note { note: Key::G, octave: 3 },
+            Note { note: Key::B, octave: 3 },
+            Note { note: Key::E, octave: 4 },
+        ],
+    };
+
+    // 7-string half step down (Bb1, Eb2, Ab2, Db3, Gb3, Bb3, Eb4)
+    pub const SEVEN_STRING_HALF_STEP_DOWN: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::As, octave: 1 },
+            Note { note: Key::Ds, octave: 2 },
+            Note { note: Key::Gs, octave: 2 },
+            Note { note: Key::Cs, octave: 3 },
+            Note { note: Key::Fs, octave: 3 },
+            Note { note: Key::As, octave: 3 },
+            Note { note: Key::Ds, octave: 4 },
+        ],
+    };
+
+    // 4-string bass (E1, A1, D2, G2)
+    pub const BASS: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::E, octave: 1 },
+            Note { note: Key::A, octave: 1 },
+            Note { note: Key::D, octave: 2 },
+            Note { note: Key::G, octave: 2 },
+        ],
+    };
+
+    // Bass Drop D (D1, A1, D2, G2)
+    pub const BASS_DROP_D: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::D, octave: 1 },
+            Note { note: Key::A, octave: 1 },
+            Note { note: Key::D, octave: 2 },
+            Note { note: Key::G, octave: 2 },
+        ],
+    };
+
+    // Bass half step down (Eb1, Ab1, Db2, Gb2)
+    pub const BASS_HALF_STEP_DOWN: Tuning = Tuning {
+        strings: &[
+            Note { note: Key::Ds, octave: 1 },
+            Note { note: Key::Gs, octave: 1 },
+            Note { note: Key::Cs, octave: 2 },
+            Note { note: Key::Fs, octave: 2 },
+        ],
+    };
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::STANDARD
+    }
+}
+
+// Named tunings selectable from the UI
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TuningPreset {
+    Standard,
+    DropD,
+    Dadgad,
+    OpenG,
+    HalfStepDown,
+}
+
+impl TuningPreset {
+    pub fn from_int(value: i32) -> TuningPreset {
+        match value {
+            0 => TuningPreset::Standard,
+            1 => TuningPreset::DropD,
+            2 => TuningPreset::Dadgad,
+            3 => TuningPreset::OpenG,
+            4 => TuningPreset::HalfStepDown,
+            _ => TuningPreset::Standard,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            TuningPreset::Standard => 0,
+            TuningPreset::DropD => 1,
+            TuningPreset::Dadgad => 2,
+            TuningPreset::OpenG => 3,
+            TuningPreset::HalfStepDown => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TuningPreset::Standard => "Standard",
+            TuningPreset::DropD => "Drop D",
+            TuningPreset::Dadgad => "DADGAD",
+            TuningPreset::OpenG => "Open G",
+            TuningPreset::HalfStepDown => "Half Step Down",
+        }
+    }
+
+    // The preset on a 6-string guitar
+    pub fn tuning(self) -> Tuning {
+        match self {
+            TuningPreset::Standard => Tuning::STANDARD,
+            TuningPreset::DropD => Tuning::DROP_D,
+            TuningPreset::Dadgad => Tuning::DADGAD,
+            TuningPreset::OpenG => Tuning::OPEN_G,
+            TuningPreset::HalfStepDown => Tuning::HALF_STEP_DOWN,
+        }
+    }
+
+    // The preset on the given instrument. Drop tunings lower the lowest string a
+    // whole step; DADGAD and Open G only exist for 6-string guitar, so other
+    // instruments stay in standard tuning for those.
+    pub fn tuning_for(self, instrument: Instrument) -> Tuning {
+        match instrument {
+            Instrument::Guitar6 => self.tuning(),
+            Instrument::Guitar7 => match self {
+                TuningPreset::DropD => Tuning::SEVEN_STRING_DROP_A,
+                TuningPreset::HalfStepDown => Tuning::SEVEN_STRING_HALF_STEP_DOWN,
+                _ => Tuning::SEVEN_STRING,
+            },
+            Instrument::Bass4 => match self {
+                TuningPreset::DropD => Tuning::BASS_DROP_D,
+                TuningPreset::HalfStepDown => Tuning::BASS_HALF_STEP_DOWN,
+                _ => Tuning::BASS,
+            },
         }
     }
 }
 
-// Standard guitar tuning (E2, A2, D3, G3, B3, E4)
-// Returns base note for each string (6 strings, index 0 = low E)
-// Use const fn to create static array
-const fn make_base_notes() -> [Note; 6] {
-    [
-        Note { note: Key::E, octave: 2 }, // String 6 (low E)
-        Note { note: Key::A, octave: 2 }, // String 5
-        Note { note: Key::D, octave: 3 }, // String 4
-        Note { note: Key::G, octave: 3 }, // String 3
-        Note { note: Key::B, octave: 3 }, // String 2
-        Note { note: Key::E, octave: 4 }, // String 1 (high E)
-    ]
+// Fretted instruments the board can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Instrument {
+    #[default]
+    Guitar6,
+    Guitar7,
+    Bass4,
 }
 
-static BASE_NOTES: [Note; 6] = make_base_notes();
+impl Instrument {
+    pub fn from_int(value: i32) -> Instrument {
+        match value {
+            1 => Instrument::Guitar7,
+            2 => Instrument::Bass4,
+            _ => Instrument::Guitar6,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            Instrument::Guitar6 => 0,
+            Instrument::Guitar7 => 1,
+            Instrument::Bass4 => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Instrument::Guitar6 => "6-String Guitar",
+            Instrument::Guitar7 => "7-String Guitar",
+            Instrument::Bass4 => "4-String Bass",
+        }
+    }
+
+    pub fn standard_tuning(self) -> Tuning {
+        TuningPreset::Standard.tuning_for(self)
+    }
+
+    pub fn string_count(self) -> u8 {
+        self.standard_tuning().strings.len() as u8
+    }
+}
 
-pub fn get_string_base_notes() -> &'static [Note; 6] {
-    &BASE_NOTES
+// Open-string notes for standard tuning
+pub fn get_string_base_notes() -> &'static [Note] {
+    Tuning::STANDARD.strings
 }
 
 // Get the note at a specific string and fret position
-// string: 0-5 (0 = low E, 5 = high E)
+// string: 0 = lowest string, up to the tuning's string count
 // fret: 0-23 (0 = open string)
-pub fn get_note_at_position(string: u8, fret: u8) -> Note {
-    let base = BASE_NOTES[string as usize];
+// Panics if the tuning has no such string; use try_get_note_at_position when the
+// index comes from outside the current tuning (e.g. after switching instruments)
+pub fn get_note_at_position(string: u8, fret: u8, tuning: &Tuning) -> Note {
+    try_get_note_at_position(string, fret, tuning)
+        .unwrap_or_else(|| panic!("no string {} in a {}-string tuning", string, tuning.strings.len()))
+}
+
+// Like get_note_at_position, or None if the tuning has no such string
+pub fn try_get_note_at_position(string: u8, fret: u8, tuning: &Tuning) -> Option<Note> {
+    tuning.strings.get(string as usize).map(|&open| note_above(open, fret))
+}
+
+// Like get_note_at_position, with a capo at the given fret (0 = no capo).
+// Frets behind the capo can't be played, so they sound as the capo fret.
+pub fn get_note_at_position_with_capo(string: u8, fret: u8, tuning: &Tuning, capo: u8) -> Note {
+    get_note_at_position(string, fret.max(capo), tuning)
+}
+
+// Note a given number of frets above an open string
+fn note_above(base: Note, fret: u8) -> Note {
     let semitones = base.note.to_int() + (base.octave * 12) + fret as i32;
     
     let note_value = semitones % 12;
@@ -181,6 +617,54 @@ pub fn get_note_at_position(string: u8, fret: u8) -> Note {
     Note::new(Key::from_int(note_value), octave)
 }
 
+// Every in-scale (string, fret) position within the fret range, ordered by
+// string (low to high) and then by fret. The fretboard builds its cells one at a
+// time, so only the tests walk the whole list so far.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn scale_positions(
+    key: Key,
+    scale: Scale,
+    tuning: &Tuning,
+    fret_range: std::ops::RangeInclusive<u8>,
+) -> Vec<(u8, u8)> {
+    let mut positions = Vec::new();
+    for (string, &base) in tuning.strings.iter().enumerate() {
+        for fret in fret_range.clone() {
+            if is_note_in_scale(note_above(base, fret), key, scale) {
+                positions.push((string as u8, fret));
+            }
+        }
+    }
+    positions
+}
+
+// Fret bounds of one practice position ("box") of the scale on a 6-string
+// guitar in standard tuning. Position 0 starts from the root on the low E string
+// (below the 12th fret) and each later position starts on the next scale note;
+// every string then takes the next two notes going up (three for scales of more
+// than five notes), the way box and three-notes-per-string shapes are fingered.
+pub fn scale_position_range(key: Key, scale: Scale, position_index: usize) -> (u8, u8) {
+    let strings = Tuning::STANDARD.strings;
+    let intervals = scale.intervals();
+    let len = intervals.len();
+    let per_string = if len > 5 { 3 } else { 2 };
+    let root_fret = (key.to_int() - strings[0].note.to_int()).rem_euclid(12);
+    // The nth scale note up from the root, in frets along the low string
+    let step = |n: usize| root_fret + intervals[n % len] + 12 * (n / len) as i32;
+
+    let first = position_index % len;
+    let (mut low, mut high) = (i32::MAX, i32::MIN);
+    for (string, open) in strings.iter().enumerate() {
+        let string_offset = open.semitone_value() - strings[0].semitone_value();
+        for n in 0..per_string {
+            let fret = step(first + string * per_string + n) - string_offset;
+            low = low.min(fret);
+            high = high.max(fret);
+        }
+    }
+    (low.max(0) as u8, high as u8)
+}
+
 // Get all notes in a scale for a given key
 pub fn get_notes_in_scale(key: Key, scale: Scale) -> Vec<Note> {
     let intervals = scale.intervals();
@@ -199,6 +683,31 @@ pub fn get_notes_in_scale(key: Key, scale: Scale) -> Vec<Note> {
     notes
 }
 
+// One pass through the scale from the root in the given octave, ascending.
+// Notes above the root may spill into the next octave (e.g. A major from A4 ends on G#5).
+pub fn scale_notes_one_octave(key: Key, scale: Scale, start_octave: i32) -> Vec<Note> {
+    let root = Note::new(key, start_octave);
+    scale.intervals().iter().map(|&interval| root.transpose(interval)).collect()
+}
+
+// Root, third and fifth of the scale built on the key, starting in the given octave.
+// Scales without a natural third/fifth (e.g. Locrian) use the altered one they contain.
+pub fn tonic_triad(key: Key, scale: Scale, octave: i32) -> Vec<Note> {
+    let intervals = scale.intervals();
+    let pick = |candidates: &[i32]| {
+        candidates
+            .iter()
+            .copied()
+            .find(|i| intervals.contains(i))
+            .unwrap_or(candidates[0])
+    };
+    let third = pick(&[4, 3]);
+    let fifth = pick(&[7, 6, 8]);
+
+    let root = Note::new(key, octave);
+    vec![root, root.transpose(third), root.transpose(fifth)]
+}
+
 // Check if a note is in the given scale
 // Optimized to avoid creating large vectors
 pub fn is_note_in_scale(note: Note, key: Key, scale: Scale) -> bool {
@@ -212,24 +721,675 @@ pub fn is_note_in_scale(note: Note, key: Key, scale: Scale) -> bool {
     })
 }
 
+// Second voice played above a clicked fret, for double stops
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HarmonyInterval {
+    MinorThird,
+    MajorThird,
+    Fifth,
+    Octave,
+}
+
+impl HarmonyInterval {
+    pub fn from_int(value: i32) -> HarmonyInterval {
+        match value {
+            0 => HarmonyInterval::MinorThird,
+            2 => HarmonyInterval::Fifth,
+            3 => HarmonyInterval::Octave,
+            _ => HarmonyInterval::MajorThird,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            HarmonyInterval::MinorThird => 0,
+            HarmonyInterval::MajorThird => 1,
+            HarmonyInterval::Fifth => 2,
+            HarmonyInterval::Octave => 3,
+        }
+    }
+
+    pub fn semitones(self) -> i32 {
+        match self {
+            HarmonyInterval::MinorThird => 3,
+            HarmonyInterval::MajorThird => 4,
+            HarmonyInterval::Fifth => 7,
+            HarmonyInterval::Octave => 12,
+        }
+    }
+}
+
+// The note sounded with a fretted note for a double stop
+pub fn harmony_note(note: Note, interval: HarmonyInterval) -> Note {
+    note.transpose(interval.semitones())
+}
+
+// Chord types, as semitone intervals above the root
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Dom7,
+    Maj7,
+    Min7,
+    Dim,
+    Aug,
+    Sus2,
+    Sus4,
+}
+
+impl ChordQuality {
+    pub fn from_int(value: i32) -> ChordQuality {
+        match value {
+            1 => ChordQuality::Minor,
+            2 => ChordQuality::Dom7,
+            3 => ChordQuality::Maj7,
+            4 => ChordQuality::Min7,
+            5 => ChordQuality::Dim,
+            6 => ChordQuality::Aug,
+            7 => ChordQuality::Sus2,
+            8 => ChordQuality::Sus4,
+            _ => ChordQuality::Major,
+        }
+    }
+
+    pub fn to_int(self) -> i32 {
+        match self {
+            ChordQuality::Major => 0,
+            ChordQuality::Minor => 1,
+            ChordQuality::Dom7 => 2,
+            ChordQuality::Maj7 => 3,
+            ChordQuality::Min7 => 4,
+            ChordQuality::Dim => 5,
+            ChordQuality::Aug => 6,
+            ChordQuality::Sus2 => 7,
+            ChordQuality::Sus4 => 8,
+        }
+    }
+
+    fn intervals(self) -> &'static [i32] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Dom7 => &[0, 4, 7, 10],
+            ChordQuality::Maj7 => &[0, 4, 7, 11],
+            ChordQuality::Min7 => &[0, 3, 7, 10],
+            ChordQuality::Dim => &[0, 3, 6],
+            ChordQuality::Aug => &[0, 4, 8],
+            ChordQuality::Sus2 => &[0, 2, 7],
+            ChordQuality::Sus4 => &[0, 5, 7],
+        }
+    }
+
+    // Suffix appended to the root in chord symbols (C, Cm, C7, Cmaj7...)
+    pub fn suffix(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Dom7 => "7",
+            ChordQuality::Maj7 => "maj7",
+            ChordQuality::Min7 => "m7",
+            ChordQuality::Dim => "dim",
+            ChordQuality::Aug => "aug",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord {
+    pub root: Key,
+    pub quality: ChordQuality,
+}
+
+impl Chord {
+    pub fn new(root: Key, quality: ChordQuality) -> Chord {
+        Chord { root, quality }
+    }
+
+    // Chord highlighting goes through is_note_in_chord directly; these are the
+    // value-style helpers the theory tests use
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn notes(self) -> Vec<Key> {
+        build_chord(self.root, self.quality)
+    }
+
+    // Chord symbol, e.g. "Am7"
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn name(self) -> String {
+        format!("{}{}", self.root.name(), self.quality.suffix())
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn contains(self, note: Note) -> bool {
+        is_note_in_chord(note, &self)
+    }
+}
+
+// Pitch classes of the chord, root first
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn build_chord(root: Key, quality: ChordQuality) -> Vec<Key> {
+    quality
+        .intervals()
+        .iter()
+        .map(|&interval| Key::from_int((root.to_int() + interval).rem_euclid(12)))
+        .collect()
+}
+
+// Every key and scale that has all of the given notes (octaves ignored), keys from
+// C up, scales in tag order within each key. "What scale fits these notes?"
+// The UI suggests keys with the looser suggest_key_scale instead.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn scales_containing(notes: &[Note]) -> Vec<(Key, Scale)> {
+    (0..12)
+        .map(Key::from_int)
+        .flat_map(|key| Scale::ALL.iter().map(move |&scale| (key, scale)))
+        .filter(|&(key, scale)| notes.iter().all(|&note| is_note_in_scale(note, key, scale)))
+        .collect()
+}
+
+// Black-key notes in the scale: how many sharps or flats it takes to write
+pub fn scale_accidentals(key: Key, scale: Scale) -> usize {
+    scale.intervals()
+        .iter()
+        .filter(|&&interval| Key::from_int((key.to_int() + interval).rem_euclid(12)).is_accidental())
+        .count()
+}
+
+// Every key and scale scored by the fraction of the played notes (repeats counted)
+// that are in it, best first, leaving out those with none. Unlike scales_containing
+// a stray note doesn't rule a scale out. Equal scores go to the scale with fewer
+// accidentals, then keys from C up and scales in tag order.
+pub fn suggest_key_scale(played: &[Note]) -> Vec<(Key, Scale, f32)> {
+    if played.is_empty() {
+        return Vec::new();
+    }
+    let mut scored: Vec<(Key, Scale, f32)> = (0..12)
+        .map(Key::from_int)
+        .flat_map(|key| Scale::ALL.iter().map(move |&scale| (key, scale)))
+        .map(|(key, scale)| {
+            let fitting = played.iter().filter(|&&note| is_note_in_scale(note, key, scale)).count();
+            (key, scale, fitting as f32 / played.len() as f32)
+        })
+        .filter(|&(_, _, score)| score > 0.0)
+        .collect();
+    // Stable, so the key and tag order stands among full ties
+    scored.sort_by(|a, b| {
+        b.2.total_cmp(&a.2).then_with(|| scale_accidentals(a.0, a.1).cmp(&scale_accidentals(b.0, b.1)))
+    });
+    scored
+}
+
+// Triads built on each degree of the scale by stacking its own thirds (every other
+// note), e.g. C major gives C Dm Em F G Am Bdim. Only seven-note scales harmonize
+// this way; others give an empty list. Not shown in the Scales pane yet.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn diatonic_chords(key: Key, scale: Scale) -> Vec<Chord> {
+    let intervals = scale.intervals();
+    let len = intervals.len();
+    if len != 7 {
+        return Vec::new();
+    }
+    (0..len)
+        .filter_map(|degree| {
+            let root = intervals[degree];
+            let third = (intervals[(degree + 2) % len] - root).rem_euclid(12);
+            let fifth = (intervals[(degree + 4) % len] - root).rem_euclid(12);
+            let quality = match (third, fifth) {
+                (4, 7) => ChordQuality::Major,
+                (3, 7) => ChordQuality::Minor,
+                (3, 6) => ChordQuality::Dim,
+                (4, 8) => ChordQuality::Aug,
+                _ => return None,
+            };
+            Some(Chord::new(Key::from_int((key.to_int() + root).rem_euclid(12)), quality))
+        })
+        .collect()
+}
+
+// Simple intervals within an octave, by size in semitones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interval {
+    Unison,
+    MinorSecond,
+    MajorSecond,
+    MinorThird,
+    MajorThird,
+    PerfectFourth,
+    Tritone,
+    PerfectFifth,
+    MinorSixth,
+    MajorSixth,
+    MinorSeventh,
+    MajorSeventh,
+    Octave,
+}
+
+impl Interval {
+    pub fn from_semitones(semitones: i32) -> Interval {
+        match semitones {
+            0 => Interval::Unison,
+            1 => Interval::MinorSecond,
+            2 => Interval::MajorSecond,
+            3 => Interval::MinorThird,
+            4 => Interval::MajorThird,
+            5 => Interval::PerfectFourth,
+            6 => Interval::Tritone,
+            7 => Interval::PerfectFifth,
+            8 => Interval::MinorSixth,
+            9 => Interval::MajorSixth,
+            10 => Interval::MinorSeventh,
+            11 => Interval::MajorSeventh,
+            _ => Interval::Octave,
+        }
+    }
+
+    pub fn semitones(self) -> i32 {
+        match self {
+            Interval::Unison => 0,
+            Interval::MinorSecond => 1,
+            Interval::MajorSecond => 2,
+            Interval::MinorThird => 3,
+            Interval::MajorThird => 4,
+            Interval::PerfectFourth => 5,
+            Interval::Tritone => 6,
+            Interval::PerfectFifth => 7,
+            Interval::MinorSixth => 8,
+            Interval::MajorSixth => 9,
+            Interval::MinorSeventh => 10,
+            Interval::MajorSeventh => 11,
+            Interval::Octave => 12,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interval::Unison => "Unison",
+            Interval::MinorSecond => "Minor 2nd",
+            Interval::MajorSecond => "Major 2nd",
+            Interval::MinorThird => "Minor 3rd",
+            Interval::MajorThird => "Major 3rd",
+            Interval::PerfectFourth => "Perfect 4th",
+            Interval::Tritone => "Tritone",
+            Interval::PerfectFifth => "Perfect 5th",
+            Interval::MinorSixth => "Minor 6th",
+            Interval::MajorSixth => "Major 6th",
+            Interval::MinorSeventh => "Minor 7th",
+            Interval::MajorSeventh => "Major 7th",
+            Interval::Octave => "Octave",
+        }
+    }
+}
+
+// Interval between two notes in either order. Compound intervals reduce to
+// their simple form (C4 -> E5 is a major third), except whole octaves, which
+// stay Octave rather than Unison; octaves_between gives the octave count.
+pub fn interval_between(a: Note, b: Note) -> Interval {
+    let distance = (b.semitone_value() - a.semitone_value()).abs();
+    if distance > 0 && distance % 12 == 0 {
+        Interval::Octave
+    } else {
+        Interval::from_semitones(distance % 12)
+    }
+}
+
+// Whole octaves spanned between two notes in either order. The interval quiz
+// never goes past an octave, so only the tests call this for now.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn octaves_between(a: Note, b: Note) -> i32 {
+    (b.semitone_value() - a.semitone_value()).abs() / 12
+}
+
+// Major keys around the circle of fifths, clockwise from C. The key picker
+// lists keys chromatically, so nothing walks the circle yet.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn circle_of_fifths() -> [Key; 12] {
+    std::array::from_fn(|i| Key::from_int((i as i32 * 7).rem_euclid(12)))
+}
+
+// Minor key sharing the major key's signature, a minor third below
+pub fn relative_minor(key: Key) -> Key {
+    Key::from_int((key.to_int() - 3).rem_euclid(12))
+}
+
+// Major key sharing the minor key's signature, a minor third above
+pub fn relative_major(key: Key) -> Key {
+    Key::from_int((key.to_int() + 3).rem_euclid(12))
+}
+
+// The same notes heard from the other tonic: major scales go to their relative
+// minor and minor scales to their relative major. The harmonic and melodic minors
+// go to the relative major of their natural minor. None for the other modes and for
+// the blues scales, whose major and minor forms here don't share their notes.
+pub fn relative_key(key: Key, scale: Scale) -> Option<(Key, Scale)> {
+    match scale {
+        Scale::Major => Some((relative_minor(key), Scale::NaturalMinor)),
+        Scale::MajorPentatonic => Some((relative_minor(key), Scale::MinorPentatonic)),
+        Scale::NaturalMinor | Scale::HarmonicMinor | Scale::MelodicMinor => Some((relative_major(key), Scale::Major)),
+        Scale::MinorPentatonic => Some((relative_major(key), Scale::MajorPentatonic)),
+        _ => None,
+    }
+}
+
+// Same tonic, major swapped for minor or back; None for the other modes
+pub fn parallel_scale(scale: Scale) -> Option<Scale> {
+    match scale {
+        Scale::Major => Some(Scale::NaturalMinor),
+        Scale::MajorPentatonic => Some(Scale::MinorPentatonic),
+        Scale::MajorBlues => Some(Scale::MinorBlues),
+        Scale::NaturalMinor | Scale::HarmonicMinor | Scale::MelodicMinor => Some(Scale::Major),
+        Scale::MinorPentatonic => Some(Scale::MajorPentatonic),
+        Scale::MinorBlues => Some(Scale::MajorBlues),
+        _ => None,
+    }
+}
+
+// Sharps (positive) or flats (negative) in the major key's signature, spelled
+// the same way as NoteSpelling::for_key (so Db has 5 flats, F# 6 sharps)
+pub fn key_signature_accidentals(key: Key) -> i32 {
+    let steps = (key.to_int() * 7).rem_euclid(12);
+    match NoteSpelling::for_key(key) {
+        NoteSpelling::Sharp => steps,
+        NoteSpelling::Flat => steps - 12,
+    }
+}
+
+// Check if a note is one of the chord's tones (any octave)
+pub fn is_note_in_chord(note: Note, chord: &Chord) -> bool {
+    let root = chord.root.to_int();
+    let note_value = note.note.to_int();
+    chord.quality.intervals().iter().any(|&interval| (root + interval) % 12 == note_value)
+}
+
+// Chord-tone label (1, b3, 5, b7, ...) of a note relative to the chord root;
+// None for notes outside the chord
+pub fn chord_degree(note: Note, chord: &Chord) -> Option<&'static str> {
+    if !is_note_in_chord(note, chord) {
+        return None;
+    }
+    let label = match (note.note.to_int() - chord.root.to_int()).rem_euclid(12) {
+        0 => "1",
+        2 => "2",
+        3 => "b3",
+        4 => "3",
+        5 => "4",
+        6 => "b5",
+        7 => "5",
+        8 => "#5",
+        10 => "b7",
+        _ => "7",
+    };
+    Some(label)
+}
+
+// Whether the note is the tonic of the key (any octave)
+pub fn is_root_note(note: Note, key: Key) -> bool {
+    note.note == key
+}
+
+// Whether the note is the target note name in any octave, for finding it on the neck
+pub fn matches_pitch_class(note: Note, key: Key) -> bool {
+    note.note == key
+}
+
+// Concert pitch; orchestras often tune to 442Hz and baroque ensembles to 415Hz
+pub const DEFAULT_REFERENCE_A4: f32 = 440.0;
+pub const MIN_REFERENCE_A4: f32 = 400.0;
+pub const MAX_REFERENCE_A4: f32 = 480.0;
+
+pub fn clamp_reference_a4(reference: f32) -> f32 {
+    if reference.is_nan() { DEFAULT_REFERENCE_A4 } else { reference.clamp(MIN_REFERENCE_A4, MAX_REFERENCE_A4) }
+}
+
+// Concert-pitch frequency of every MIDI note (0-127), worked out once since
+// playback asks for the same few notes over and over
+static MIDI_FREQUENCIES: Lazy<[f32; 128]> = Lazy::new(|| {
+    std::array::from_fn(|midi| equal_tempered(Note::from_midi(midi as i32), DEFAULT_REFERENCE_A4))
+});
+
 // Calculate frequency in Hz for a note using A4 = 440Hz standard tuning
 pub fn calculate_frequency(note: Note) -> f32 {
-    // A4 = 440Hz is at semitone 69 (MIDI standard)
+    calculate_frequency_with_reference(note, DEFAULT_REFERENCE_A4)
+}
+
+// Equal-tempered frequency with A4 tuned to the given reference (e.g. 432Hz).
+// Concert pitch within the MIDI range comes from the table, anything else is computed.
+pub fn calculate_frequency_with_reference(note: Note, reference: f32) -> f32 {
+    let cached = (reference == DEFAULT_REFERENCE_A4)
+        .then(|| usize::try_from(note.to_midi()).ok().and_then(|midi| MIDI_FREQUENCIES.get(midi)))
+        .flatten();
+    match cached {
+        Some(&frequency) => frequency,
+        None => equal_tempered(note, reference),
+    }
+}
+
+fn equal_tempered(note: Note, reference: f32) -> f32 {
+    // A4 is at semitone 69 (MIDI standard)
     let a4_semitone = Note::new(Key::A, 4).semitone_value();
     let note_semitone = note.semitone_value();
     
     let semitones_above_a4 = note_semitone - a4_semitone;
-    440.0 * 2.0_f32.powf(semitones_above_a4 as f32 / 12.0)
+    reference * 2.0_f32.powf(semitones_above_a4 as f32 / 12.0)
 }
 
-// Get fret positions that should have markers (dots)
-pub fn get_marked_frets() -> Vec<u8> {
-    vec![3, 5, 7, 9, 12, 15, 17, 19, 21]
+// Frequencies of one octave of the scale, ascending from the root (for drones and
+// demos). Scale playback follows the focused string instead, with cents offsets.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn scale_frequencies(key: Key, scale: Scale, octave: i32, reference: f32) -> Vec<f32> {
+    scale_notes_one_octave(key, scale, octave)
+        .into_iter()
+        .map(|note| calculate_frequency_with_reference(note, reference))
+        .collect()
+}
+
+// Shift a frequency by the given number of cents (100 cents = 1 semitone)
+pub fn apply_cents(freq: f32, cents: f32) -> f32 {
+    freq * 2.0_f32.powf(cents / 1200.0)
+}
+
+// Optional per-pitch-class cents offsets for microtonal tunings (e.g. quarter tones)
+// All zeros (the default) is standard 12-TET
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CentsOffsets {
+    pub cents: [f32; 12],
+}
+
+impl CentsOffsets {
+    pub fn get(&self, key: Key) -> f32 {
+        self.cents[key.to_int() as usize]
+    }
+
+    pub fn set(&mut self, key: Key, cents: f32) {
+        self.cents[key.to_int() as usize] = cents;
+    }
+}
+
+// Like calculate_frequency_with_reference, but with the note's microtonal offset applied
+pub fn calculate_frequency_with_offsets(note: Note, offsets: &CentsOffsets, reference: f32) -> f32 {
+    apply_cents(calculate_frequency_with_reference(note, reference), offsets.get(note.note))
+}
+
+// Scale degree (1-7) of a note relative to the key root, by semitone interval.
+// Altered intervals map onto their natural degree (e.g. b3 and 3 are both 3).
+pub fn degree_number(note: Note, key: Key) -> u8 {
+    let interval = (note.note.to_int() - key.to_int()).rem_euclid(12);
+    match interval {
+        0 => 1,
+        1 | 2 => 2,
+        3 | 4 => 3,
+        5 | 6 => 4,
+        7 => 5,
+        8 | 9 => 6,
+        _ => 7,
+    }
+}
+
+// Degree label (1, b3, 5, ...) of an in-scale note relative to the key root;
+// None for notes outside the scale
+pub fn scale_degree(note: Note, key: Key, scale: Scale) -> Option<&'static str> {
+    let interval = (note.note.to_int() - key.to_int()).rem_euclid(12);
+    if !scale.intervals().contains(&interval) {
+        return None;
+    }
+    let label = match interval {
+        0 => "1",
+        1 => "b2",
+        2 => "2",
+        3 => "b3",
+        4 => "3",
+        5 => "4",
+        6 if matches!(scale, Scale::Lydian | Scale::WholeTone) => "#4",
+        6 => "b5",
+        7 => "5",
+        8 if scale == Scale::WholeTone => "#5",
+        8 => "b6",
+        9 => "6",
+        10 => "b7",
+        _ => "7",
+    };
+    Some(label)
+}
+
+// How bright a scale sounds: +1 for every degree raised relative to major, -1 for
+// every degree lowered. The church modes run Lydian (+1) down to Locrian (-5).
+// Meant for ordering a mode picker; the scale list keeps tag order for now.
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn scale_brightness(scale: Scale) -> i32 {
+    const MAJOR: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+    let intervals = scale.intervals();
+    intervals
+        .iter()
+        .map(|&interval| {
+            // The tritone is a raised 4th when the scale has no perfect 4th (Lydian),
+            // otherwise a flat 5th
+            if interval == 6 {
+                return if intervals.contains(&5) { -1 } else { 1 };
+            }
+            // Anything not in major sits a semitone below its major degree
+            if MAJOR.contains(&interval) { 0 } else { -1 }
+        })
+        .sum()
+}
+
+// Detune applied to tension tones in the ear-training mode, kept subtle
+pub const TENSION_DETUNE_CENTS: f32 = 6.0;
+
+// Chord tones (1, 3, 5) stay in tune; everything else is a tension
+pub fn detune_cents_for_degree(degree: u8) -> f32 {
+    match degree {
+        1 | 3 | 5 => 0.0,
+        _ => TENSION_DETUNE_CENTS,
+    }
+}
+
+// Which frets get inlay markers (dots)
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum MarkerScheme {
+    #[default]
+    Standard,
+    Minimal, // Octave markers only (12 and 24)
+    Custom(Vec<u8>),
+}
+
+impl MarkerScheme {
+    // Custom schemes keep their fret list; from_int(2) starts with an empty one
+    pub fn from_int(value: i32) -> MarkerScheme {
+        match value {
+            1 => MarkerScheme::Minimal,
+            2 => MarkerScheme::Custom(Vec::new()),
+            _ => MarkerScheme::Standard,
+        }
+    }
+
+    pub fn to_int(&self) -> i32 {
+        match self {
+            MarkerScheme::Standard => 0,
+            MarkerScheme::Minimal => 1,
+            MarkerScheme::Custom(_) => 2,
+        }
+    }
+
+    // Display name; the marker picker in Fretboard.slint has its labels inline
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarkerScheme::Standard => "Standard",
+            MarkerScheme::Minimal => "Minimal",
+            MarkerScheme::Custom(_) => "Custom",
+        }
+    }
+}
+
+// The inlay at a fret: none, one dot, or the double dot marking an octave
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MarkerKind {
+    #[default]
+    None,
+    Single,
+    Double,
+}
+
+impl MarkerKind {
+    pub fn to_int(self) -> i32 {
+        match self {
+            MarkerKind::None => 0,
+            MarkerKind::Single => 1,
+            MarkerKind::Double => 2,
+        }
+    }
+}
+
+// Standard inlays, which depend only on the fret: single dots at 3, 5, 7 and 9,
+// double dots at 12, repeating every octave up the neck (15-21, 24, ...)
+pub fn fret_marker_kind(fret: u8) -> MarkerKind {
+    match fret % 12 {
+        0 if fret > 0 => MarkerKind::Double,
+        3 | 5 | 7 | 9 => MarkerKind::Single,
+        _ => MarkerKind::None,
+    }
+}
+
+// Parse a user-entered fret list such as "3, 7 12"; anything that isn't a fret number is ignored
+pub fn parse_fret_list(text: &str) -> Vec<u8> {
+    let mut frets: Vec<u8> = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    frets.sort_unstable();
+    frets.dedup();
+    frets
+}
+
+// Get fret positions that should have markers (dots), up to the 24th fret
+pub fn get_marked_frets(scheme: &MarkerScheme) -> Vec<u8> {
+    match scheme {
+        MarkerScheme::Standard => (1..=24).filter(|&fret| fret_marker_kind(fret) != MarkerKind::None).collect(),
+        MarkerScheme::Minimal => vec![12, 24],
+        MarkerScheme::Custom(frets) => frets.clone(),
+    }
+}
+
+// The inlay a fret gets under the scheme. Octave frets (12, 24) that are marked
+// at all get the double dot, whichever scheme marks them.
+// Called for every cell on a re-mark, so it looks the fret up without allocating.
+pub fn marker_kind(fret: u8, scheme: &MarkerScheme) -> MarkerKind {
+    let marked = match scheme {
+        MarkerScheme::Standard => return fret_marker_kind(fret),
+        MarkerScheme::Minimal => fret == 12 || fret == 24,
+        MarkerScheme::Custom(frets) => frets.contains(&fret),
+    };
+    match fret % 12 {
+        _ if !marked => MarkerKind::None,
+        0 if fret > 0 => MarkerKind::Double,
+        _ => MarkerKind::Single,
+    }
 }
 
 // Check if a fret should have a marker dot
-pub fn is_fret_marked(fret: u8) -> bool {
-    get_marked_frets().contains(&fret)
+pub fn is_fret_marked(fret: u8, scheme: &MarkerScheme) -> bool {
+    marker_kind(fret, scheme) != MarkerKind::None
 }
 
 #[cfg(test)]
@@ -250,6 +1410,34 @@ mod tests {
         assert_eq!(Key::E.to_int(), 4);
     }
 
+    fn major_scale_names(tonic: Key) -> Vec<&'static str> {
+        get_notes_in_scale(tonic, Scale::Major)
+            .iter()
+            .take(7)
+            .map(|n| n.note.name_in_key(tonic))
+            .collect()
+    }
+
+    #[test]
+    fn test_spelling_c_major_has_no_accidentals() {
+        let names = major_scale_names(Key::C);
+        assert_eq!(names, vec!["C", "D", "E", "F", "G", "A", "B"]);
+    }
+
+    #[test]
+    fn test_spelling_f_major_uses_flats() {
+        let names = major_scale_names(Key::F);
+        assert_eq!(names, vec!["F", "G", "A", "Bb", "C", "D", "E"]);
+        assert_eq!(Note::new(Key::As, 3).name_in_key(Key::F), "Bb3");
+    }
+
+    #[test]
+    fn test_spelling_e_major_uses_sharps() {
+        let names = major_scale_names(Key::E);
+        assert_eq!(names, vec!["E", "F#", "G#", "A", "B", "C#", "D#"]);
+        assert!(names.iter().all(|n| !n.ends_with('b')));
+    }
+
     #[test]
     fn test_scale_intervals() {
         // Major scale: W-W-H-W-W-W-H (whole, whole, half, whole, whole, whole, half)
@@ -261,21 +1449,69 @@ mod tests {
     #[test]
     fn test_get_note_at_position() {
         // String 0 (low E), fret 0 = E2
-        let note = get_note_at_position(0, 0);
+        let note = get_note_at_position(0, 0, &Tuning::STANDARD);
         assert_eq!(note.note, Key::E);
         assert_eq!(note.octave, 2);
 
         // String 0, fret 5 = A2 (5 semitones up from E)
-        let note = get_note_at_position(0, 5);
+        let note = get_note_at_position(0, 5, &Tuning::STANDARD);
         assert_eq!(note.note, Key::A);
         assert_eq!(note.octave, 2);
 
         // String 5 (high E), fret 0 = E4
-        let note = get_note_at_position(5, 0);
+        let note = get_note_at_position(5, 0, &Tuning::STANDARD);
         assert_eq!(note.note, Key::E);
         assert_eq!(note.octave, 4);
     }
 
+    #[test]
+    fn test_try_get_note_at_position() {
+        for string in 0..6 {
+            assert_eq!(try_get_note_at_position(string, 3, &Tuning::STANDARD), Some(get_note_at_position(string, 3, &Tuning::STANDARD)));
+        }
+        // Six strings: index 6 is past the high E
+        assert_eq!(try_get_note_at_position(6, 0, &Tuning::STANDARD), None);
+        assert_eq!(try_get_note_at_position(255, 0, &Tuning::STANDARD), None);
+        // The same index is a real string on a seven-string, and off the end of a bass
+        assert_eq!(try_get_note_at_position(6, 0, &Tuning::SEVEN_STRING), Some(Note::new(Key::E, 4)));
+        assert_eq!(try_get_note_at_position(4, 0, &Tuning::BASS), None);
+    }
+
+    #[test]
+    fn test_capo_positions() {
+        let tuning = Tuning::STANDARD;
+        // Capo 2: the "open" low E sounds F#2, and so does anything behind the capo
+        assert_eq!(get_note_at_position_with_capo(0, 0, &tuning, 2), Note::new(Key::Fs, 2));
+        assert_eq!(get_note_at_position_with_capo(0, 1, &tuning, 2), Note::new(Key::Fs, 2));
+        assert_eq!(get_note_at_position_with_capo(0, 5, &tuning, 2), Note::new(Key::A, 2));
+        assert_eq!(get_note_at_position_with_capo(0, 5, &tuning, 0), get_note_at_position(0, 5, &tuning));
+    }
+
+    #[test]
+    fn test_instrument_tunings() {
+        let open = |tuning: Tuning| tuning.strings.iter().map(|n| n.name()).collect::<Vec<_>>();
+        assert_eq!(open(Instrument::Bass4.standard_tuning()), vec!["E1", "A1", "D2", "G2"]);
+        assert_eq!(Instrument::Bass4.string_count(), 4);
+
+        // The 7-string is the 6-string with a low B underneath
+        let seven = Instrument::Guitar7.standard_tuning();
+        assert_eq!(seven.strings[0], Note::new(Key::B, 1));
+        assert_eq!(&seven.strings[1..], Tuning::STANDARD.strings);
+        assert_eq!(Instrument::Guitar7.string_count(), 7);
+
+        // Presets follow the instrument; six-string-only ones fall back to standard
+        assert_eq!(open(TuningPreset::DropD.tuning_for(Instrument::Bass4)), vec!["D1", "A1", "D2", "G2"]);
+        assert_eq!(TuningPreset::DropD.tuning_for(Instrument::Guitar7).strings[0], Note::new(Key::A, 1));
+        assert_eq!(TuningPreset::Dadgad.tuning_for(Instrument::Bass4), Tuning::BASS);
+        assert_eq!(TuningPreset::OpenG.tuning_for(Instrument::Guitar6), Tuning::OPEN_G);
+        let half_down = TuningPreset::HalfStepDown.tuning_for(Instrument::Guitar7);
+        assert!(half_down.strings.iter().zip(seven.strings).all(|(low, std)| low.transpose(1) == *std));
+
+        for tag in 0..3 {
+            assert_eq!(Instrument::from_int(tag).to_int(), tag);
+        }
+    }
+
     #[test]
     fn test_is_note_in_scale() {
         // C Major scale contains: C, D, E, F, G, A, B
@@ -297,6 +1533,47 @@ mod tests {
         assert!(!is_note_in_scale(Note { note: Key::Fs, octave: 4 }, key, scale));
     }
 
+    #[test]
+    fn test_is_note_in_scale_d_dorian() {
+        // D Dorian contains: D, E, F, G, A, B, C (white keys from D)
+        let key = Key::D;
+        let scale = Scale::Dorian;
+
+        for note in [Key::D, Key::E, Key::F, Key::G, Key::A, Key::B, Key::C] {
+            assert!(is_note_in_scale(Note::new(note, 3), key, scale));
+        }
+
+        // Notes NOT in D Dorian
+        for note in [Key::Cs, Key::Ds, Key::Fs, Key::Gs, Key::As] {
+            assert!(!is_note_in_scale(Note::new(note, 3), key, scale));
+        }
+    }
+
+    #[test]
+    fn test_is_note_in_scale_g_mixolydian() {
+        // G Mixolydian contains: G, A, B, C, D, E, F (white keys from G)
+        let key = Key::G;
+        let scale = Scale::Mixolydian;
+
+        for note in [Key::G, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F] {
+            assert!(is_note_in_scale(Note::new(note, 3), key, scale));
+        }
+
+        // F# (the major 7th) is NOT in G Mixolydian
+        assert!(!is_note_in_scale(Note::new(Key::Fs, 3), key, scale));
+        assert!(!is_note_in_scale(Note::new(Key::Gs, 3), key, scale));
+    }
+
+    #[test]
+    fn test_scale_int_round_trip() {
+        for tag in 1..=11 {
+            assert_eq!(Scale::from_int(tag).to_int(), tag);
+        }
+        // Existing tags must stay stable for saved layouts
+        assert_eq!(Scale::from_int(1), Scale::Major);
+        assert_eq!(Scale::from_int(6), Scale::MinorBlues);
+    }
+
     #[test]
     fn test_calculate_frequency() {
         // A4 = 440 Hz (concert pitch)
@@ -310,6 +1587,140 @@ mod tests {
         assert!((freq - 261.63).abs() < 0.5);
     }
 
+    #[test]
+    fn test_frequency_table_matches_computed() {
+        assert_eq!(calculate_frequency(Note::new(Key::A, 4)), 440.0);
+        for midi in [0, 21, 40, 60, 64, 88, 108, 127] {
+            let note = Note::from_midi(midi);
+            let computed = DEFAULT_REFERENCE_A4 * 2.0_f32.powf((midi - 69) as f32 / 12.0);
+            assert!((calculate_frequency(note) - computed).abs() / computed < 1e-6, "MIDI {}", midi);
+        }
+        // Beyond the MIDI range it's still computed, an octave at a time
+        let high = Note::from_midi(139);
+        assert!((calculate_frequency(high) / calculate_frequency(Note::from_midi(127)) - 2.0).abs() < 1e-5);
+        assert!((calculate_frequency(Note::from_midi(-12)) * 2.0 - calculate_frequency(Note::from_midi(0))).abs() < 1e-6);
+        // Other references bypass the table
+        assert!((calculate_frequency_with_reference(Note::new(Key::A, 4), 432.0) - 432.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_reference_pitch() {
+        let a4 = Note::new(Key::A, 4);
+        assert_eq!(calculate_frequency_with_reference(a4, 442.0), 442.0);
+        assert_eq!(calculate_frequency_with_reference(a4, 415.0), 415.0);
+
+        // Every other note moves by the same ratio
+        let c4 = Note::new(Key::C, 4);
+        let shifted = calculate_frequency_with_reference(c4, 442.0);
+        assert!((shifted / calculate_frequency(c4) - 442.0 / 440.0).abs() < 1e-6);
+        assert!((shifted - 262.82).abs() < 0.01);
+
+        assert_eq!(clamp_reference_a4(442.0), 442.0);
+        assert_eq!(clamp_reference_a4(0.0), MIN_REFERENCE_A4);
+        assert_eq!(clamp_reference_a4(f32::NAN), DEFAULT_REFERENCE_A4);
+    }
+
+    #[test]
+    fn test_tonic_triad_frequencies() {
+        let c_major = tonic_triad(Key::C, Scale::Major, 3);
+        assert_eq!(c_major, vec![Note::new(Key::C, 3), Note::new(Key::E, 3), Note::new(Key::G, 3)]);
+
+        let frequencies: Vec<f32> = c_major.iter().map(|&n| calculate_frequency(n)).collect();
+        let expected = [130.81, 164.81, 196.00];
+        for (freq, want) in frequencies.iter().zip(expected) {
+            assert!((freq - want).abs() < 0.05);
+        }
+
+        // Minor third, and the diminished fifth for Locrian
+        assert_eq!(tonic_triad(Key::A, Scale::NaturalMinor, 3)[1], Note::new(Key::C, 4));
+        assert_eq!(tonic_triad(Key::B, Scale::Locrian, 3)[2], Note::new(Key::F, 4));
+    }
+
+    #[test]
+    fn test_apply_cents() {
+        // 0 cents is identity
+        assert_eq!(apply_cents(440.0, 0.0), 440.0);
+
+        // +50 cents (a quarter tone) raises by 2^(50/1200)
+        let raised = apply_cents(440.0, 50.0);
+        assert!((raised / 440.0 - 2.0_f32.powf(50.0 / 1200.0)).abs() < 1e-6);
+        assert!((raised - 452.89).abs() < 0.01);
+
+        // +100 cents is one semitone
+        let a_sharp = calculate_frequency(Note::new(Key::As, 4));
+        assert!((apply_cents(440.0, 100.0) - a_sharp).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_cents_offsets_default_is_12tet() {
+        let offsets = CentsOffsets::default();
+        let a4 = Note::new(Key::A, 4);
+        assert_eq!(calculate_frequency_with_offsets(a4, &offsets, DEFAULT_REFERENCE_A4), calculate_frequency(a4));
+
+        let mut offsets = CentsOffsets::default();
+        offsets.set(Key::E, -50.0);
+        let e4 = Note::new(Key::E, 4);
+        let expected = apply_cents(calculate_frequency(e4), -50.0);
+        assert_eq!(calculate_frequency_with_offsets(e4, &offsets, DEFAULT_REFERENCE_A4), expected);
+        // Other pitch classes are unaffected
+        assert_eq!(calculate_frequency_with_offsets(a4, &offsets, DEFAULT_REFERENCE_A4), calculate_frequency(a4));
+        assert_eq!(calculate_frequency_with_offsets(a4, &offsets, 442.0), 442.0);
+    }
+
+    #[test]
+    fn test_note_transpose() {
+        assert_eq!(Note::new(Key::B, 3).transpose(1), Note::new(Key::C, 4));
+        assert_eq!(Note::new(Key::C, 4).transpose(-1), Note::new(Key::B, 3));
+        assert_eq!(Note::new(Key::E, 2).transpose(12), Note::new(Key::E, 3));
+        assert_eq!(Note::new(Key::A, 4).transpose(0), Note::new(Key::A, 4));
+    }
+
+    #[test]
+    fn test_note_to_midi() {
+        assert_eq!(Note::new(Key::C, 4).to_midi(), 60);
+        assert_eq!(Note::new(Key::A, 4).to_midi(), 69);
+        assert_eq!(Note::new(Key::E, 2).to_midi(), 40);
+
+        assert_eq!(Note::from_midi(60), Note::new(Key::C, 4));
+        assert_eq!(Note::from_midi(69), Note::new(Key::A, 4));
+        assert_eq!(Note::from_midi(0), Note::new(Key::C, -1));
+        for midi in 0..=127 {
+            assert_eq!(Note::from_midi(midi).to_midi(), midi);
+        }
+        // Same reference point as the frequency calculation
+        assert_eq!(calculate_frequency(Note::from_midi(69)), DEFAULT_REFERENCE_A4);
+    }
+
+    #[test]
+    fn test_note_parse() {
+        assert_eq!(Note::parse("C"), Some(Note::new(Key::C, 4)));
+        assert_eq!(Note::parse("F#4"), Some(Note::new(Key::Fs, 4)));
+        assert_eq!(Note::parse("Bb3"), Some(Note::new(Key::As, 3)));
+        assert_eq!(Note::parse("Bb3"), Note::parse("A#3"));
+        assert_eq!(Note::parse("Gb2"), Some(Note::new(Key::Fs, 2)));
+        assert_eq!(Note::parse(" e2 "), Some(Note::new(Key::E, 2)));
+        assert_eq!(Note::parse("C-1"), Some(Note::from_midi(0)));
+        // Flat of C is the B below
+        assert_eq!(Note::parse("Cb4"), Some(Note::new(Key::B, 3)));
+
+        assert_eq!(Note::parse("H9"), None);
+        assert_eq!(Note::parse(""), None);
+        assert_eq!(Note::parse("C##"), None);
+        assert_eq!(Note::parse("F#x"), None);
+        // Octaves past the MIDI range are refused rather than overflowing
+        assert_eq!(Note::parse("C9"), Some(Note::new(Key::C, 9)));
+        assert_eq!(Note::parse("C10"), None);
+        assert_eq!(Note::parse("C-2"), None);
+        assert_eq!(Note::parse("C999999999"), None);
+        assert_eq!(Note::parse("B#2147483647"), None);
+        // Round-trips the names the app prints
+        for midi in 0..=127 {
+            let note = Note::from_midi(midi);
+            assert_eq!(Note::parse(&note.name()), Some(note));
+            assert_eq!(Note::parse(&note.name_with(NoteSpelling::Flat)), Some(note));
+        }
+    }
+
     #[test]
     fn test_get_string_base_notes() {
         let base_notes = get_string_base_notes();
@@ -321,26 +1732,435 @@ mod tests {
         assert_eq!(base_notes[5].octave, 4);
     }
 
+    #[test]
+    fn test_scale_positions() {
+        let positions = scale_positions(Key::C, Scale::Major, &Tuning::STANDARD, 0..=3);
+
+        // Every position resolves to an in-scale note
+        for &(string, fret) in &positions {
+            let note = get_note_at_position(string, fret, &Tuning::STANDARD);
+            assert!(is_note_in_scale(note, Key::C, Scale::Major));
+        }
+
+        // By hand: E(E F G) A(A B C) D(D E F) G(G A) B(B C D) E(E F G)
+        assert_eq!(positions.len(), 17);
+        assert_eq!(&positions[..3], &[(0, 0), (0, 1), (0, 3)]);
+        assert!(!positions.contains(&(3, 1))); // G#
+    }
+
+    #[test]
+    fn test_a_minor_pentatonic_boxes() {
+        let boxes: Vec<(u8, u8)> = (0..5).map(|i| scale_position_range(Key::A, Scale::MinorPentatonic, i)).collect();
+        assert_eq!(boxes, vec![(5, 8), (7, 10), (9, 13), (12, 15), (14, 17)]);
+        // Positions wrap around after the last one
+        assert_eq!(scale_position_range(Key::A, Scale::MinorPentatonic, 5), (5, 8));
+
+        // E minor starts at the nut
+        assert_eq!(scale_position_range(Key::E, Scale::MinorPentatonic, 0), (0, 3));
+        // Seven-note scales use three notes per string and span a little wider
+        assert_eq!(scale_position_range(Key::G, Scale::Major, 0), (3, 8));
+    }
+
+    #[test]
+    fn test_drop_d_lowers_only_low_string() {
+        let drop_d = TuningPreset::DropD.tuning();
+        let note = get_note_at_position(0, 0, &drop_d);
+        assert_eq!(note, Note::new(Key::D, 2));
+
+        // The other five strings match standard tuning
+        for string in 1..6 {
+            assert_eq!(
+                get_note_at_position(string, 0, &drop_d),
+                get_note_at_position(string, 0, &Tuning::STANDARD)
+            );
+        }
+
+        // Fret 2 on the dropped string is back to E2
+        assert_eq!(get_note_at_position(0, 2, &drop_d), Note::new(Key::E, 2));
+    }
+
+    #[test]
+    fn test_tuning_preset_int_round_trip() {
+        for tag in 0..5 {
+            assert_eq!(TuningPreset::from_int(tag).to_int(), tag);
+        }
+        assert_eq!(TuningPreset::from_int(0).tuning(), Tuning::default());
+    }
+
     #[test]
     fn test_octave_wraparound() {
         // Test that going up 12 frets wraps around the octave
-        let note1 = get_note_at_position(0, 0);  // E2 (string 0, open)
-        let note2 = get_note_at_position(0, 12); // E3 (same note, octave up)
+        let note1 = get_note_at_position(0, 0, &Tuning::STANDARD);  // E2 (string 0, open)
+        let note2 = get_note_at_position(0, 12, &Tuning::STANDARD); // E3 (same note, octave up)
         assert_eq!(note1.note, note2.note);
         assert_eq!(note2.octave, note1.octave + 1);
     }
 
+    #[test]
+    fn test_build_chord() {
+        assert_eq!(build_chord(Key::C, ChordQuality::Major), vec![Key::C, Key::E, Key::G]);
+        assert_eq!(build_chord(Key::A, ChordQuality::Min7), vec![Key::A, Key::C, Key::E, Key::G]);
+        assert_eq!(build_chord(Key::G, ChordQuality::Dom7), vec![Key::G, Key::B, Key::D, Key::F]);
+        // Wraps past B
+        assert_eq!(build_chord(Key::B, ChordQuality::Dim), vec![Key::B, Key::D, Key::F]);
+
+        let chord = Chord::new(Key::A, ChordQuality::Min7);
+        assert_eq!(chord.name(), "Am7");
+        assert_eq!(chord.notes(), vec![Key::A, Key::C, Key::E, Key::G]);
+        assert!(chord.contains(Note::new(Key::G, 2)));
+        assert!(!chord.contains(Note::new(Key::B, 2)));
+    }
+
+    #[test]
+    fn test_interval_between() {
+        let c4 = Note::new(Key::C, 4);
+        assert_eq!(interval_between(c4, Note::new(Key::E, 4)), Interval::MajorThird);
+        assert_eq!(interval_between(c4, Note::new(Key::G, 4)), Interval::PerfectFifth);
+        assert_eq!(interval_between(c4, Note::new(Key::C, 5)), Interval::Octave);
+        assert_eq!(interval_between(c4, c4), Interval::Unison);
+
+        // Order doesn't matter, and across the octave boundary
+        assert_eq!(interval_between(Note::new(Key::G, 4), c4), Interval::PerfectFifth);
+        assert_eq!(interval_between(Note::new(Key::B, 3), c4), Interval::MinorSecond);
+
+        // Compound intervals reduce, keeping the octave count separately
+        let e5 = Note::new(Key::E, 5);
+        assert_eq!(interval_between(c4, e5), Interval::MajorThird);
+        assert_eq!(octaves_between(c4, e5), 1);
+        assert_eq!(interval_between(c4, Note::new(Key::C, 6)), Interval::Octave);
+        assert_eq!(octaves_between(c4, Note::new(Key::C, 6)), 2);
+
+        for semitones in 0..=12 {
+            assert_eq!(Interval::from_semitones(semitones).semitones(), semitones);
+        }
+    }
+
+    #[test]
+    fn test_circle_of_fifths() {
+        let circle = circle_of_fifths();
+        assert_eq!(circle[0], Key::C);
+        assert_eq!(circle[1], Key::G);
+        assert_eq!(circle[11], Key::F);
+        // Every key appears once
+        for key in 0..12 {
+            assert!(circle.contains(&Key::from_int(key)));
+        }
+
+        assert_eq!(key_signature_accidentals(Key::C), 0);
+        assert_eq!(key_signature_accidentals(Key::G), 1);
+        assert_eq!(key_signature_accidentals(Key::F), -1);
+        assert_eq!(key_signature_accidentals(Key::Fs), 6);
+        assert_eq!(key_signature_accidentals(Key::Cs), -5);
+
+        assert_eq!(relative_minor(Key::C), Key::A);
+        assert_eq!(relative_minor(Key::G), Key::E);
+        assert_eq!(relative_minor(Key::Ds), Key::C);
+        assert_eq!(relative_major(Key::A), Key::C);
+        assert_eq!(relative_major(Key::B), Key::D);
+    }
+
+    #[test]
+    fn test_relative_and_parallel_keys() {
+        assert_eq!(relative_key(Key::C, Scale::Major), Some((Key::A, Scale::NaturalMinor)));
+        assert_eq!(relative_key(Key::A, Scale::NaturalMinor), Some((Key::C, Scale::Major)));
+        assert_eq!(relative_key(Key::E, Scale::HarmonicMinor), Some((Key::G, Scale::Major)));
+        assert_eq!(relative_key(Key::D, Scale::Dorian), None);
+        assert_eq!(relative_key(Key::C, Scale::MajorBlues), None);
+
+        // Relative keys share their notes, and going there and back is a round trip
+        let pitch_classes = |key, scale| {
+            let mut notes: Vec<i32> = get_notes_in_scale(key, scale).iter().map(|n| n.note.to_int()).collect();
+            notes.sort();
+            notes.dedup();
+            notes
+        };
+        for scale in [Scale::Major, Scale::NaturalMinor, Scale::MajorPentatonic, Scale::MinorPentatonic] {
+            for key in circle_of_fifths() {
+                let (relative, relative_scale) = relative_key(key, scale).unwrap();
+                assert_eq!(pitch_classes(relative, relative_scale), pitch_classes(key, scale), "{:?} {:?}", key, scale);
+                assert_eq!(relative_key(relative, relative_scale), Some((key, scale)));
+            }
+        }
+
+        assert_eq!(parallel_scale(Scale::Major), Some(Scale::NaturalMinor));
+        assert_eq!(parallel_scale(Scale::MinorPentatonic), Some(Scale::MajorPentatonic));
+        assert_eq!(parallel_scale(Scale::MajorBlues), Some(Scale::MinorBlues));
+        assert_eq!(parallel_scale(Scale::MelodicMinor), Some(Scale::Major));
+        assert_eq!(parallel_scale(Scale::Lydian), None);
+    }
+
+    #[test]
+    fn test_is_note_in_chord() {
+        let c = Chord::new(Key::C, ChordQuality::Major);
+        let in_chord: Vec<Key> = (0..12).map(Key::from_int).filter(|&k| is_note_in_chord(Note::new(k, 3), &c)).collect();
+        assert_eq!(in_chord, vec![Key::C, Key::E, Key::G]);
+
+        let a7 = Chord::new(Key::A, ChordQuality::Dom7);
+        assert!(is_note_in_chord(Note::new(Key::G, 5), &a7));
+        assert_eq!(chord_degree(Note::new(Key::G, 5), &a7), Some("b7"));
+        assert_eq!(chord_degree(Note::new(Key::Cs, 4), &a7), Some("3"));
+        assert_eq!(chord_degree(Note::new(Key::D, 4), &a7), None);
+
+        for tag in 0..9 {
+            assert_eq!(ChordQuality::from_int(tag).to_int(), tag);
+        }
+    }
+
+    #[test]
+    fn test_scales_containing() {
+        let c_triad = [Note::new(Key::C, 3), Note::new(Key::E, 3), Note::new(Key::G, 4)];
+        let matches = scales_containing(&c_triad);
+        assert!(matches.contains(&(Key::C, Scale::Major)));
+        assert!(matches.contains(&(Key::G, Scale::Major)));
+        assert!(matches.contains(&(Key::A, Scale::NaturalMinor)));
+        assert!(matches.contains(&(Key::C, Scale::MajorPentatonic)));
+        assert!(!matches.contains(&(Key::C, Scale::NaturalMinor)));
+        assert!(matches.iter().all(|&(key, scale)| c_triad.iter().all(|&n| is_note_in_scale(n, key, scale))));
+
+        // Three semitones in a row only fit the blues scale's 4, b5, 5
+        let cluster = [Note::new(Key::C, 4), Note::new(Key::Cs, 4), Note::new(Key::D, 4)];
+        assert_eq!(scales_containing(&cluster), vec![(Key::G, Scale::MinorBlues)]);
+        let cluster = [Note::new(Key::C, 4), Note::new(Key::Cs, 4), Note::new(Key::D, 4), Note::new(Key::Ds, 4)];
+        assert!(scales_containing(&cluster).is_empty());
+
+        for (i, scale) in Scale::ALL.iter().enumerate() {
+            assert_eq!(scale.to_int(), i as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_suggest_key_scale() {
+        let phrase: Vec<Note> = [Key::C, Key::D, Key::E, Key::G, Key::A, Key::G, Key::E]
+            .iter()
+            .map(|&key| Note::new(key, 4))
+            .collect();
+        let suggestions = suggest_key_scale(&phrase);
+        let score_of = |key, scale| suggestions.iter().find(|s| (s.0, s.1) == (key, scale)).map(|s| s.2);
+        assert_eq!(score_of(Key::C, Scale::Major), Some(1.0));
+        assert_eq!(score_of(Key::A, Scale::MinorPentatonic), Some(1.0));
+        // Full marks without accidentals come first, led by C major
+        assert_eq!((suggestions[0].0, suggestions[0].1), (Key::C, Scale::Major));
+        let first_sharp = suggestions.iter().position(|s| scale_accidentals(s.0, s.1) > 0).unwrap();
+        assert!(suggestions[..first_sharp].iter().all(|s| s.2 == 1.0));
+        assert!(suggestions.windows(2).all(|w| w[0].2 >= w[1].2));
+        // One stray note lowers the score rather than ruling the scale out
+        let mut with_stray = phrase.clone();
+        with_stray.push(Note::new(Key::Fs, 4));
+        let c_major = suggest_key_scale(&with_stray).into_iter().find(|s| (s.0, s.1) == (Key::C, Scale::Major)).unwrap();
+        assert!((c_major.2 - 7.0 / 8.0).abs() < 1e-6);
+
+        // A chromatic cluster fits nothing well
+        let cluster: Vec<Note> = (0..6).map(|i| Note::new(Key::from_int(i), 4)).collect();
+        let best = suggest_key_scale(&cluster)[0].2;
+        assert!(best < 0.7, "best score {}", best);
+        assert!(suggest_key_scale(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_diatonic_chords() {
+        let names = |key, scale| diatonic_chords(key, scale).iter().map(|c| c.name()).collect::<Vec<_>>();
+        assert_eq!(names(Key::C, Scale::Major), vec!["C", "Dm", "Em", "F", "G", "Am", "Bdim"]);
+        assert_eq!(names(Key::A, Scale::NaturalMinor), vec!["Am", "Bdim", "C", "Dm", "Em", "F", "G"]);
+        assert_eq!(diatonic_chords(Key::G, Scale::Major)[4], Chord::new(Key::D, ChordQuality::Major));
+        // The raised 7th gives harmonic minor an augmented III and a major V
+        assert_eq!(names(Key::A, Scale::HarmonicMinor), vec!["Am", "Bdim", "Caug", "Dm", "E", "F", "G#dim"]);
+        assert!(diatonic_chords(Key::A, Scale::MinorPentatonic).is_empty());
+    }
+
+    #[test]
+    fn test_is_root_note() {
+        let open_low_e = get_note_at_position(0, 0, &Tuning::STANDARD);
+        assert!(is_root_note(open_low_e, Key::E));
+        assert!(!is_root_note(open_low_e, Key::A));
+        // Any octave of the tonic counts
+        assert!(is_root_note(get_note_at_position(0, 12, &Tuning::STANDARD), Key::E));
+    }
+
+    #[test]
+    fn test_degree_number() {
+        assert_eq!(degree_number(Note::new(Key::C, 4), Key::C), 1);
+        assert_eq!(degree_number(Note::new(Key::Ds, 4), Key::C), 3);
+        assert_eq!(degree_number(Note::new(Key::G, 4), Key::C), 5);
+        // Wraps below the root: B is the 7th of C
+        assert_eq!(degree_number(Note::new(Key::B, 3), Key::C), 7);
+        assert_eq!(degree_number(Note::new(Key::E, 4), Key::A), 5);
+    }
+
+    #[test]
+    fn test_scale_degree_labels() {
+        let a_minor_pentatonic = |key| scale_degree(Note::new(key, 4), Key::A, Scale::MinorPentatonic);
+        assert_eq!(a_minor_pentatonic(Key::A), Some("1"));
+        assert_eq!(a_minor_pentatonic(Key::C), Some("b3"));
+        assert_eq!(a_minor_pentatonic(Key::E), Some("5"));
+        assert_eq!(a_minor_pentatonic(Key::G), Some("b7"));
+        // Out-of-scale notes have no degree
+        assert_eq!(a_minor_pentatonic(Key::B), None);
+
+        assert_eq!(scale_degree(Note::new(Key::Fs, 4), Key::C, Scale::Lydian), Some("#4"));
+        assert_eq!(scale_degree(Note::new(Key::Fs, 4), Key::C, Scale::MinorBlues), Some("b5"));
+    }
+
+    #[test]
+    fn test_detune_cents_for_degree() {
+        // Chord tones stay in tune
+        assert_eq!(detune_cents_for_degree(1), 0.0);
+        assert_eq!(detune_cents_for_degree(3), 0.0);
+        assert_eq!(detune_cents_for_degree(5), 0.0);
+
+        // Tensions get the configured detune
+        for degree in [2, 4, 6, 7] {
+            assert_eq!(detune_cents_for_degree(degree), TENSION_DETUNE_CENTS);
+        }
+    }
+
     #[test]
     fn test_fret_markers() {
+        let scheme = MarkerScheme::Standard;
         // Test marked frets
-        assert!(is_fret_marked(3));
-        assert!(is_fret_marked(12));
-        assert!(is_fret_marked(21));
+        assert!(is_fret_marked(3, &scheme));
+        assert!(is_fret_marked(12, &scheme));
+        assert!(is_fret_marked(21, &scheme));
         
         // Test unmarked frets
-        assert!(!is_fret_marked(1));
-        assert!(!is_fret_marked(2));
-        assert!(!is_fret_marked(4));
+        assert!(!is_fret_marked(1, &scheme));
+        assert!(!is_fret_marked(2, &scheme));
+        assert!(!is_fret_marked(4, &scheme));
     }
-}
 
+    #[test]
+    fn test_marker_kinds() {
+        assert_eq!(fret_marker_kind(12), MarkerKind::Double);
+        assert_eq!(fret_marker_kind(24), MarkerKind::Double);
+        assert_eq!(fret_marker_kind(7), MarkerKind::Single);
+        assert_eq!(fret_marker_kind(8), MarkerKind::None);
+        assert_eq!(fret_marker_kind(0), MarkerKind::None);
+        assert_eq!(fret_marker_kind(19), MarkerKind::Single);
+        assert_eq!(get_marked_frets(&MarkerScheme::Standard), vec![3, 5, 7, 9, 12, 15, 17, 19, 21, 24]);
+
+        // A user's list decides which frets are marked; octave frets still get two dots
+        let custom = MarkerScheme::Custom(vec![5, 12]);
+        assert_eq!(marker_kind(5, &custom), MarkerKind::Single);
+        assert_eq!(marker_kind(12, &custom), MarkerKind::Double);
+        assert_eq!(marker_kind(7, &custom), MarkerKind::None);
+        assert_eq!(marker_kind(24, &MarkerScheme::Minimal), MarkerKind::Double);
+    }
+
+    #[test]
+    fn test_minimal_markers_only_octaves() {
+        let marked: Vec<u8> = (0..=24).filter(|&f| is_fret_marked(f, &MarkerScheme::Minimal)).collect();
+        assert_eq!(marked, vec![12, 24]);
+    }
+
+    #[test]
+    fn test_custom_markers_honor_list() {
+        let scheme = MarkerScheme::Custom(parse_fret_list("2, 4 10,x,4"));
+        assert_eq!(get_marked_frets(&scheme), vec![2, 4, 10]);
+        let marked: Vec<u8> = (0..=24).filter(|&f| is_fret_marked(f, &scheme)).collect();
+        assert_eq!(marked, vec![2, 4, 10]);
+
+        // An empty custom list marks nothing
+        assert!(!(0..=24).any(|f| is_fret_marked(f, &MarkerScheme::Custom(Vec::new()))));
+        assert_eq!(MarkerScheme::from_int(scheme.to_int()).name(), "Custom");
+    }
+
+    #[test]
+    fn test_scale_frequencies_c_major() {
+        let freqs = scale_frequencies(Key::C, Scale::Major, 4, 440.0);
+        assert_eq!(freqs.len(), 7);
+        assert!((freqs[0] - 261.63).abs() < 0.01);
+        assert!((freqs[6] - 493.88).abs() < 0.01);
+        assert!(freqs.windows(2).all(|w| w[0] < w[1]));
+
+        // The reference pitch scales every note
+        let low = scale_frequencies(Key::C, Scale::Major, 4, 432.0);
+        assert!((low[0] / freqs[0] - 432.0 / 440.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_scale_notes_one_octave() {
+        let c_major = scale_notes_one_octave(Key::C, Scale::Major, 4);
+        let keys: Vec<Key> = c_major.iter().map(|n| n.note).collect();
+        assert_eq!(keys, vec![Key::C, Key::D, Key::E, Key::F, Key::G, Key::A, Key::B]);
+        assert!(c_major.iter().all(|n| n.octave == 4));
+
+        // Five tones, ascending; A minor pentatonic crosses into octave 4 at C
+        let a_minor_pent = scale_notes_one_octave(Key::A, Scale::MinorPentatonic, 3);
+        assert_eq!(a_minor_pent.len(), 5);
+        assert_eq!(a_minor_pent[0], Note::new(Key::A, 3));
+        assert_eq!(a_minor_pent[1], Note::new(Key::C, 4));
+        assert!(a_minor_pent.windows(2).all(|w| w[0].semitone_value() < w[1].semitone_value()));
+    }
+
+    #[test]
+    fn test_harmonic_and_melodic_minor() {
+        let a_harmonic: Vec<Key> = get_notes_in_scale(Key::A, Scale::HarmonicMinor).iter().map(|n| n.note).collect();
+        assert!(a_harmonic.contains(&Key::Gs));
+        assert!(a_harmonic.contains(&Key::F));
+        assert!(!a_harmonic.contains(&Key::G));
+
+        let a_melodic: Vec<Key> = get_notes_in_scale(Key::A, Scale::MelodicMinor).iter().map(|n| n.note).collect();
+        assert!(a_melodic.contains(&Key::Fs));
+        assert!(a_melodic.contains(&Key::Gs));
+        assert!(!a_melodic.contains(&Key::F));
+
+        // The raised 7th is labelled as a natural 7
+        assert_eq!(scale_degree(Note::new(Key::Gs, 3), Key::A, Scale::HarmonicMinor), Some("7"));
+        assert_eq!(Scale::from_int(Scale::MelodicMinor.to_int()), Scale::MelodicMinor);
+    }
+
+    #[test]
+    fn test_harmony_note() {
+        let c4 = Note::new(Key::C, 4);
+        let third = harmony_note(c4, HarmonyInterval::MajorThird);
+        assert_eq!(third, Note::new(Key::E, 4));
+        assert!((calculate_frequency(third) - 329.63).abs() < 0.01);
+        assert!((calculate_frequency(third) / calculate_frequency(c4) - 2.0_f32.powf(4.0 / 12.0)).abs() < 1e-6);
+
+        // From the high end of the low string, across the octave
+        let a2 = get_note_at_position(0, 5, &Tuning::STANDARD);
+        assert_eq!(harmony_note(a2, HarmonyInterval::MinorThird), Note::new(Key::C, 3));
+        assert_eq!(harmony_note(a2, HarmonyInterval::Fifth), Note::new(Key::E, 3));
+        assert_eq!(harmony_note(a2, HarmonyInterval::Octave), Note::new(Key::A, 3));
+        for tag in 0..4 {
+            assert_eq!(HarmonyInterval::from_int(tag).to_int(), tag);
+        }
+    }
+
+    #[test]
+    fn test_symmetric_scales() {
+        let c_whole_tone: Vec<Key> = get_notes_in_scale(Key::C, Scale::WholeTone).iter().map(|n| n.note).collect();
+        let mut pitch_classes = c_whole_tone.clone();
+        pitch_classes.sort_by_key(|k| k.to_int());
+        pitch_classes.dedup();
+        assert_eq!(pitch_classes, vec![Key::C, Key::D, Key::E, Key::Fs, Key::Gs, Key::As]);
+        assert!(!c_whole_tone.contains(&Key::Cs));
+        assert_eq!(scale_degree(Note::new(Key::Gs, 4), Key::C, Scale::WholeTone), Some("#5"));
+
+        // The two diminished scales are each other a semitone apart
+        let half_whole: Vec<Note> = scale_notes_one_octave(Key::C, Scale::HalfWholeDiminished, 4);
+        let whole_half: Vec<Note> = scale_notes_one_octave(Key::Cs, Scale::WholeHalfDiminished, 4);
+        assert_eq!(half_whole.len(), 8);
+        assert_eq!(&half_whole[1..], &whole_half[..7]);
+
+        for scale in [Scale::WholeTone, Scale::HalfWholeDiminished, Scale::WholeHalfDiminished] {
+            assert_eq!(Scale::from_int(scale.to_int()), scale);
+        }
+    }
+
+    #[test]
+    fn test_scale_brightness_orders_modes() {
+        let brightest_to_darkest = [
+            Scale::Lydian,
+            Scale::Major,
+            Scale::Mixolydian,
+            Scale::Dorian,
+            Scale::NaturalMinor,
+            Scale::Phrygian,
+            Scale::Locrian,
+        ];
+        let scores: Vec<i32> = brightest_to_darkest.iter().map(|&s| scale_brightness(s)).collect();
+        assert!(scores.windows(2).all(|w| w[0] > w[1]), "{:?}", scores);
+        assert_eq!(scale_brightness(Scale::Major), 0);
+        assert_eq!(scale_brightness(Scale::MinorPentatonic), -2);
+    }
+}
@@ -0,0 +1,342 @@
+// Staff notation: where the notes of a scale sit on a treble or bass staff, and
+// the scale written out as SVG to read alongside the fretboard diagram
+
+use crate::music_theory::{self, Key, Note, NoteSpelling, Scale};
+use std::fmt::Write;
+
+// Half the gap between two staff lines: one staff position (line to space)
+const STEP: i32 = 5;
+const NOTE_SPACING: i32 = 36;
+// Room left of the first note for the clef
+const CLEF_WIDTH: i32 = 40;
+const MARGIN: i32 = 20;
+// Positions of the five staff lines, bottom line = 0
+const TOP_LINE: i32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clef {
+    Treble,
+    Bass,
+}
+
+impl Clef {
+    // The bass clef for bass, treble for guitars
+    pub fn for_instrument(instrument: music_theory::Instrument) -> Clef {
+        match instrument {
+            music_theory::Instrument::Bass4 => Clef::Bass,
+            _ => Clef::Treble,
+        }
+    }
+
+    // Note on the bottom line (E4 treble, G2 bass)
+    fn bottom_line(self) -> Note {
+        match self {
+            Clef::Treble => Note::new(Key::E, 4),
+            Clef::Bass => Note::new(Key::G, 2),
+        }
+    }
+
+    // Octave the written scale starts in, so it sits on or near the staff
+    fn scale_octave(self) -> i32 {
+        match self {
+            Clef::Treble => 4,
+            Clef::Bass => 3,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Clef::Treble => "\u{1D11E}",
+            Clef::Bass => "\u{1D122}",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Accidental {
+    Sharp,
+    Flat,
+    DoubleSharp,
+    DoubleFlat,
+}
+
+impl Accidental {
+    // Semitones away from the natural note; 0 has no accidental
+    fn from_semitones(semitones: i32) -> Option<Accidental> {
+        match semitones {
+            1 => Some(Accidental::Sharp),
+            -1 => Some(Accidental::Flat),
+            2 => Some(Accidental::DoubleSharp),
+            -2 => Some(Accidental::DoubleFlat),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Accidental::Sharp => "\u{266F}",
+            Accidental::Flat => "\u{266D}",
+            Accidental::DoubleSharp => "\u{1D12A}",
+            Accidental::DoubleFlat => "\u{1D12B}",
+        }
+    }
+
+    // As written in a note name: F#, Bb, Fx... spelled out with ASCII
+    fn name(self) -> &'static str {
+        match self {
+            Accidental::Sharp => "#",
+            Accidental::Flat => "b",
+            Accidental::DoubleSharp => "##",
+            Accidental::DoubleFlat => "bb",
+        }
+    }
+}
+
+// One note as written on the staff
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaffNote {
+    pub note: Note,
+    // Name as written, e.g. "E#" where the key spells F that way
+    pub name: String,
+    // Staff steps above the bottom line: even on a line, odd in a space
+    pub position: i32,
+    pub accidental: Option<Accidental>,
+    // Positions of the ledger lines the note needs above or below the staff
+    pub ledger_lines: Vec<i32>,
+}
+
+const LETTER_NAMES: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+// Semitones of each natural note above C, indexed by letter
+const NATURAL_SEMITONES: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+// Letter (C = 0 .. B = 6) and accidental of a note's name in the given spelling
+fn letter_and_accidental(note: Note, spelling: NoteSpelling) -> (i32, Option<Accidental>) {
+    let name = note.note.name_with(spelling);
+    let letter = match name.as_bytes()[0] {
+        b'C' => 0,
+        b'D' => 1,
+        b'E' => 2,
+        b'F' => 3,
+        b'G' => 4,
+        b'A' => 5,
+        _ => 6,
+    };
+    let accidental = match name.as_bytes().get(1) {
+        Some(b'#') => Some(Accidental::Sharp),
+        Some(b'b') => Some(Accidental::Flat),
+        _ => None,
+    };
+    (letter, accidental)
+}
+
+// Where a note goes on the staff, spelled with sharps or flats. Only the letter
+// moves the note; the accidental is drawn beside it.
+pub fn staff_position(note: Note, spelling: NoteSpelling, clef: Clef) -> (i32, Option<Accidental>) {
+    let (letter, accidental) = letter_and_accidental(note, spelling);
+    (steps_above_bottom_line(letter, note.octave, clef), accidental)
+}
+
+fn steps_above_bottom_line(letter: i32, octave: i32, clef: Clef) -> i32 {
+    let (bottom_letter, _) = letter_and_accidental(clef.bottom_line(), NoteSpelling::Sharp);
+    octave * 7 + letter - (clef.bottom_line().octave * 7 + bottom_letter)
+}
+
+// Where a note goes when it's written with the given letter, and the accidental
+// that takes; None if the letter is more than a double accidental away. The octave
+// is the letter's own, so B#3 sounds as C4 but sits on the B3 step.
+fn position_as_letter(note: Note, letter: i32, clef: Clef) -> Option<(i32, Option<Accidental>)> {
+    let semitone = note.octave * 12 + note.note.to_int();
+    let octave = (semitone - NATURAL_SEMITONES[letter as usize] + 6).div_euclid(12);
+    let offset = semitone - (octave * 12 + NATURAL_SEMITONES[letter as usize]);
+    if offset.abs() > 2 {
+        return None;
+    }
+    Some((steps_above_bottom_line(letter, octave, clef), Accidental::from_semitones(offset)))
+}
+
+// Ledger lines between the staff and a note above or below it, nearest the staff first
+pub fn ledger_lines(position: i32) -> Vec<i32> {
+    if position < 0 {
+        (position..0).rev().filter(|p| p % 2 == 0).collect()
+    } else {
+        (TOP_LINE + 1..=position).filter(|p| p % 2 == 0).collect()
+    }
+}
+
+// One octave of the scale ascending from the root, closed with the root an octave up.
+// Seven-note scales take the next letter for each degree, so every step of the
+// staff gets one note (D minor has Bb, not A#; F# major has E#, not F). Others are
+// spelled with the key's sharps or flats.
+pub fn scale_on_staff(key: Key, scale: Scale, clef: Clef) -> Vec<StaffNote> {
+    let spelling = NoteSpelling::for_key(key);
+    let (root_letter, _) = letter_and_accidental(Note::new(key, 0), spelling);
+    let by_degree = scale.intervals().len() == 7;
+    let (bottom_letter, _) = letter_and_accidental(clef.bottom_line(), NoteSpelling::Sharp);
+    let mut notes = music_theory::scale_notes_one_octave(key, scale, clef.scale_octave());
+    notes.push(Note::new(key, clef.scale_octave() + 1));
+    notes
+        .into_iter()
+        .enumerate()
+        .map(|(degree, note)| {
+            let letter = (root_letter + degree as i32) % 7;
+            let (position, accidental) = by_degree
+                .then(|| position_as_letter(note, letter, clef))
+                .flatten()
+                .unwrap_or_else(|| staff_position(note, spelling, clef));
+            // The letter back from the step, whichever way the note was spelled
+            let written = LETTER_NAMES[(bottom_letter + position).rem_euclid(7) as usize];
+            let name = format!("{}{}", written, accidental.map_or("", Accidental::name));
+            StaffNote { note, name, position, accidental, ledger_lines: ledger_lines(position) }
+        })
+        .collect()
+}
+
+// Write the scale on a staff as whole notes, each named underneath
+pub fn export_staff_svg(key: Key, scale: Scale, clef: Clef) -> String {
+    let notes = scale_on_staff(key, scale, clef);
+    // Make room for notes above or below the staff
+    let highest = notes.iter().map(|n| n.position).max().unwrap_or(TOP_LINE).max(TOP_LINE);
+    let lowest = notes.iter().map(|n| n.position).min().unwrap_or(0).min(0);
+    let top = MARGIN + 2 * STEP;
+    let y = |position: i32| top + (highest - position) * STEP;
+    let label_y = y(lowest) + 4 * STEP;
+    let width = MARGIN * 2 + CLEF_WIDTH + NOTE_SPACING * notes.len() as i32;
+    let height = label_y + MARGIN;
+    let staff_left = MARGIN;
+    let staff_right = width - MARGIN;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="10">"#,
+        w = width,
+        h = height
+    );
+    let _ = writeln!(svg, r#"<title>{} {}</title>"#, key.name(), scale.name());
+    let _ = writeln!(svg, r##"<rect width="{}" height="{}" fill="#1E1F24"/>"##, width, height);
+
+    for position in (0..=TOP_LINE).step_by(2) {
+        let _ = writeln!(
+            svg,
+            r##"<line class="staff" x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="#9AA0A6" stroke-width="1"/>"##,
+            staff_left,
+            staff_right,
+            y = y(position)
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r##"<text x="{}" y="{}" fill="#E6E6E6" font-size="{}" dominant-baseline="central">{}</text>"##,
+        staff_left + 4,
+        y(TOP_LINE / 2),
+        STEP * 9,
+        clef.symbol()
+    );
+
+    for (i, staff_note) in notes.iter().enumerate() {
+        let x = staff_left + CLEF_WIDTH + NOTE_SPACING * i as i32 + NOTE_SPACING / 2;
+        for &ledger in &staff_note.ledger_lines {
+            let _ = writeln!(
+                svg,
+                r##"<line class="ledger" x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="#9AA0A6" stroke-width="1"/>"##,
+                x - 10,
+                x + 10,
+                y = y(ledger)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r##"<ellipse class="note" cx="{}" cy="{}" rx="{}" ry="{}" fill="none" stroke="#5CC8FF" stroke-width="2"/>"##,
+            x,
+            y(staff_note.position),
+            STEP + 2,
+            STEP - 1
+        );
+        if let Some(accidental) = staff_note.accidental {
+            let _ = writeln!(
+                svg,
+                r##"<text class="accidental" x="{}" y="{}" fill="#E6E6E6" font-size="14" text-anchor="middle" dominant-baseline="central">{}</text>"##,
+                x - 14,
+                y(staff_note.position),
+                accidental.symbol()
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r##"<text x="{}" y="{}" fill="#9AA0A6" text-anchor="middle">{}</text>"##,
+            x,
+            label_y,
+            staff_note.name
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_treble_staff_positions() {
+        // Middle C sits on one ledger line below the staff
+        let c4 = Note::new(Key::C, 4);
+        assert_eq!(staff_position(c4, NoteSpelling::Sharp, Clef::Treble), (-2, None));
+        assert_eq!(ledger_lines(-2), vec![-2]);
+        // G4 is the second line, the one the treble clef curls around
+        let g4 = Note::new(Key::G, 4);
+        assert_eq!(staff_position(g4, NoteSpelling::Sharp, Clef::Treble), (2, None));
+        assert!(ledger_lines(2).is_empty());
+
+        // The accidental doesn't move the note: F#4 and Gb4 sit on different steps
+        let fs4 = Note::new(Key::Fs, 4);
+        assert_eq!(staff_position(fs4, NoteSpelling::Sharp, Clef::Treble), (1, Some(Accidental::Sharp)));
+        assert_eq!(staff_position(fs4, NoteSpelling::Flat, Clef::Treble), (2, Some(Accidental::Flat)));
+
+        // A5 is on one ledger line above, C6 on the second; G3 hangs under two below
+        assert_eq!(ledger_lines(10), vec![10]);
+        assert_eq!(ledger_lines(12), vec![10, 12]);
+        assert_eq!(ledger_lines(-5), vec![-2, -4]);
+    }
+
+    #[test]
+    fn test_bass_staff_positions() {
+        // G2 on the bottom line, middle C one ledger line above the bass staff
+        assert_eq!(staff_position(Note::new(Key::G, 2), NoteSpelling::Sharp, Clef::Bass), (0, None));
+        assert_eq!(staff_position(Note::new(Key::C, 4), NoteSpelling::Sharp, Clef::Bass), (10, None));
+    }
+
+    #[test]
+    fn test_scale_on_staff() {
+        // F major: F4 up to F5 one step at a time, with B spelled flat
+        let notes = scale_on_staff(Key::F, Scale::Major, Clef::Treble);
+        let positions: Vec<i32> = notes.iter().map(|n| n.position).collect();
+        assert_eq!(positions, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        let accidentals: Vec<_> = notes.iter().filter_map(|n| n.accidental).collect();
+        assert_eq!(accidentals, vec![Accidental::Flat]);
+
+        // C major starts on middle C's ledger line; the rest fit on the staff
+        let svg = export_staff_svg(Key::C, Scale::Major, Clef::Treble);
+        assert_eq!(svg.matches(r#"<ellipse class="note""#).count(), 8);
+        assert_eq!(svg.matches(r#"<line class="staff""#).count(), 5);
+        assert_eq!(svg.matches(r#"<line class="ledger""#).count(), 1);
+        assert_eq!(svg.matches(r#"class="accidental""#).count(), 0);
+    }
+
+    #[test]
+    fn test_seven_note_scales_take_one_step_per_degree() {
+        let positions = |key, scale| -> Vec<i32> { scale_on_staff(key, scale, Clef::Treble).iter().map(|n| n.position).collect() };
+        let names = |key, scale| -> Vec<String> { scale_on_staff(key, scale, Clef::Treble).into_iter().map(|n| n.name).collect() };
+        for (key, scale) in [(Key::D, Scale::NaturalMinor), (Key::G, Scale::NaturalMinor), (Key::Fs, Scale::Major)] {
+            let positions = positions(key, scale);
+            assert!(positions.windows(2).all(|pair| pair[1] == pair[0] + 1), "{:?} {:?}: {:?}", key, scale, positions);
+        }
+        assert_eq!(names(Key::D, Scale::NaturalMinor), ["D", "E", "F", "G", "A", "Bb", "C", "D"]);
+        assert_eq!(names(Key::Fs, Scale::Major), ["F#", "G#", "A#", "B", "C#", "D#", "E#", "F#"]);
+        // E# sits on the E step with a sharp, below the top F#
+        let e_sharp = &scale_on_staff(Key::Fs, Scale::Major, Clef::Treble)[6];
+        assert_eq!((e_sharp.position, e_sharp.accidental), (7, Some(Accidental::Sharp)));
+        assert_eq!(e_sharp.note, Note::new(Key::F, 5));
+    }
+}
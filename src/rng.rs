@@ -0,0 +1,53 @@
+// Small seeded random numbers for the quizzes and the click noise: xorshift32,
+// which is plenty for both and keeps a seed reproducible in tests
+
+pub struct Xorshift {
+    // Never zero; xorshift would stay at zero forever
+    state: u32,
+}
+
+impl Xorshift {
+    pub fn new(seed: u32) -> Self {
+        Xorshift { state: seed.max(1) }
+    }
+
+    // Seeded from the sub-second part of the system clock
+    pub fn from_clock() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        Xorshift::new(nanos)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    // Next number below `bound` (0 for a bound of 0)
+    pub fn next_below(&mut self, bound: u32) -> u32 {
+        self.next_u32() % bound.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_numbers_within_bound() {
+        let mut a = Xorshift::new(42);
+        let mut b = Xorshift::new(42);
+        for _ in 0..100 {
+            let n = a.next_below(7);
+            assert!(n < 7);
+            assert_eq!(n, b.next_below(7));
+        }
+        // A zero seed still moves
+        let mut zero = Xorshift::new(0);
+        assert_ne!((0..10).map(|_| zero.next_below(1000)).collect::<Vec<_>>(), vec![0; 10]);
+        assert_eq!(zero.next_below(0), 0);
+    }
+}
@@ -0,0 +1,122 @@
+// Startup self-check of music-theory invariants (SELF_CHECK=1)
+// Catches broken tables and off-by-one errors before they show up as wrong notes
+
+use crate::audio::Waveform;
+use crate::fret_click::{ClickMode, DoubleClickAction};
+use crate::music_theory::{self, Instrument, Key, MarkerScheme, Note, Scale, TuningPreset};
+
+const KEY_COUNT: i32 = 12;
+const SCALE_TAGS: std::ops::RangeInclusive<i32> = 1..=16;
+const TUNING_COUNT: i32 = 5;
+const INSTRUMENT_COUNT: i32 = 3;
+const HIGHEST_FRET: u8 = 24;
+
+// Every failed invariant, described; empty when everything holds
+pub fn run() -> Vec<String> {
+    let mut failures = scale_table_failures(Scale::intervals);
+    failures.extend(fret_frequency_failures());
+    failures.extend(tag_round_trip_failures());
+    failures
+}
+
+// Each scale must start on the root and climb strictly within one octave,
+// and every key must give a non-empty one-octave note set
+fn scale_table_failures(intervals_for: impl Fn(Scale) -> &'static [i32]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for tag in SCALE_TAGS {
+        let scale = Scale::from_int(tag);
+        let intervals = intervals_for(scale);
+        if intervals.first() != Some(&0) {
+            failures.push(format!("{}: intervals must start at 0, got {:?}", scale.name(), intervals));
+        }
+        if !intervals.windows(2).all(|w| w[0] < w[1]) {
+            failures.push(format!("{}: intervals must strictly ascend, got {:?}", scale.name(), intervals));
+        }
+        if intervals.iter().any(|&i| !(0..12).contains(&i)) {
+            failures.push(format!("{}: intervals must stay within an octave, got {:?}", scale.name(), intervals));
+        }
+        for key_tag in 0..KEY_COUNT {
+            let key = Key::from_int(key_tag);
+            let root = Note::new(key, 4).semitone_value();
+            let notes: Vec<i32> = intervals.iter().map(|&i| root + i).collect();
+            if notes.is_empty() || notes.iter().any(|&n| n < root || n >= root + 12) {
+                failures.push(format!("{} {}: note set not within one octave: {:?}", key.name(), scale.name(), notes));
+            }
+        }
+    }
+    failures
+}
+
+// Frequencies must rise with every fret up every string, in every tuning of
+// every instrument
+fn fret_frequency_failures() -> Vec<String> {
+    let mut failures = Vec::new();
+    for instrument in (0..INSTRUMENT_COUNT).map(Instrument::from_int) {
+        for preset in (0..TUNING_COUNT).map(TuningPreset::from_int) {
+            let tuning = preset.tuning_for(instrument);
+            if tuning.strings.len() != instrument.string_count() as usize {
+                failures.push(format!("{} {}: wrong number of strings", instrument.name(), preset.name()));
+            }
+            for string in 0..tuning.strings.len() as u8 {
+                let frequencies: Vec<f32> = (0..=HIGHEST_FRET)
+                    .map(|fret| music_theory::calculate_frequency(music_theory::get_note_at_position(string, fret, &tuning)))
+                    .collect();
+                if let Some(fret) = frequencies.windows(2).position(|w| w[0] >= w[1]) {
+                    failures.push(format!(
+                        "{} {} string {}: pitch does not rise from fret {} to {}",
+                        instrument.name(), preset.name(), string, fret, fret + 1
+                    ));
+                }
+            }
+        }
+    }
+    failures
+}
+
+// from_int/to_int must agree for every tag the UI and layout file use
+fn tag_round_trip_failures() -> Vec<String> {
+    let mut failures = Vec::new();
+    // A local copy to iterate; the const itself can't be borrowed mutably
+    let mut scale_tags = SCALE_TAGS;
+    let mut check = |name: &str, tags: &mut dyn Iterator<Item = i32>, round_trip: &dyn Fn(i32) -> i32| {
+        for tag in tags {
+            let back = round_trip(tag);
+            if back != tag {
+                failures.push(format!("{} tag {} round-trips to {}", name, tag, back));
+            }
+        }
+    };
+    check("Key", &mut (0..KEY_COUNT), &|t| Key::from_int(t).to_int());
+    check("Scale", &mut scale_tags, &|t| Scale::from_int(t).to_int());
+    check("TuningPreset", &mut (0..TUNING_COUNT), &|t| TuningPreset::from_int(t).to_int());
+    check("Instrument", &mut (0..INSTRUMENT_COUNT), &|t| Instrument::from_int(t).to_int());
+    check("MarkerScheme", &mut (0..3), &|t| MarkerScheme::from_int(t).to_int());
+    check("DoubleClickAction", &mut (0..DoubleClickAction::COUNT), &|t| DoubleClickAction::from_int(t).to_int());
+    check("ClickMode", &mut (0..2), &|t| ClickMode::from_int(t).to_int());
+    check("Waveform", &mut (0..5), &|t| Waveform::from_int(t).to_int());
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_check_passes() {
+        assert_eq!(run(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_self_check_catches_broken_intervals() {
+        // Major's 7th pushed past the octave, and Dorian left empty
+        let failures = scale_table_failures(|scale| match scale {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 13],
+            Scale::Dorian => &[],
+            _ => scale.intervals(),
+        });
+        assert!(failures.iter().any(|f| f.starts_with("Major: intervals must stay within an octave")));
+        assert!(failures.iter().any(|f| f.starts_with("Dorian: intervals must start at 0")));
+        assert!(failures.iter().any(|f| f.starts_with("C Dorian: note set")));
+        assert!(failures.iter().all(|f| f.contains("Major") || f.contains("Dorian")));
+    }
+}
@@ -0,0 +1,175 @@
+// Practice sessions: a start/stop timer, and a log of finished sessions kept as JSON lines
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// Times practice runs; each stopped run is logged, and the log adds them up
+#[derive(Debug, Default)]
+pub struct PracticeTimer {
+    started: Option<Instant>,
+}
+
+impl PracticeTimer {
+    pub fn new() -> Self {
+        PracticeTimer::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    // Starting a running timer keeps the original start
+    pub fn start(&mut self, now: Instant) {
+        if self.started.is_none() {
+            self.started = Some(now);
+        }
+    }
+
+    // Returns how long the run just stopped lasted, or None if the timer wasn't running
+    pub fn stop(&mut self, now: Instant) -> Option<Duration> {
+        let started = self.started.take()?;
+        Some(now.saturating_duration_since(started))
+    }
+}
+
+// One line of the practice log
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEntry {
+    // When the session ended, in seconds since the Unix epoch
+    pub timestamp: u64,
+    pub duration_secs: u64,
+    pub key: String,
+    pub scale: String,
+}
+
+impl SessionEntry {
+    pub fn new(ended: SystemTime, duration: Duration, key: &str, scale: &str) -> Self {
+        SessionEntry {
+            timestamp: unix_seconds(ended),
+            duration_secs: duration.as_secs(),
+            key: key.to_string(),
+            scale: scale.to_string(),
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+// Add one entry to the end of the log, creating the file if needed
+pub fn append_entry(path: &Path, entry: &SessionEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(entry)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Failed to serialize session: {}", e)))?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
+
+// Lines that don't parse (a write cut short, a hand edit) are skipped
+pub fn parse_log(text: &str) -> Vec<SessionEntry> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+// A missing or unreadable log reads as no sessions yet
+pub fn read_log(path: &Path) -> Vec<SessionEntry> {
+    match std::fs::read(path) {
+        Ok(bytes) => parse_log(&String::from_utf8_lossy(&bytes)),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Practice logged on the same (UTC) day as `now`; sessions count toward the day they ended
+pub fn total_today(entries: &[SessionEntry], now: SystemTime) -> Duration {
+    let today = unix_seconds(now) / SECONDS_PER_DAY;
+    let secs = entries.iter()
+        .filter(|entry| entry.timestamp / SECONDS_PER_DAY == today)
+        .map(|entry| entry.duration_secs)
+        .sum();
+    Duration::from_secs(secs)
+}
+
+// Short label for the UI, e.g. "1h 05m" or "12m"
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_times_each_run() {
+        let t0 = Instant::now();
+        let mut timer = PracticeTimer::new();
+        assert_eq!(timer.stop(t0), None);
+
+        timer.start(t0);
+        assert_eq!(timer.stop(t0 + Duration::from_secs(10)), Some(Duration::from_secs(10)));
+        // The gap between runs doesn't count
+        timer.start(t0 + Duration::from_secs(20));
+        // Starting again mid-run keeps the first start
+        timer.start(t0 + Duration::from_secs(30));
+        assert!(timer.is_running());
+        assert_eq!(timer.stop(t0 + Duration::from_secs(50)), Some(Duration::from_secs(30)));
+        assert!(!timer.is_running());
+    }
+
+    #[test]
+    fn test_parse_log_skips_bad_lines() {
+        let day = 20_000 * SECONDS_PER_DAY;
+        let text = [
+            // Yesterday evening
+            format!(r#"{{"timestamp":{},"duration_secs":600,"key":"A","scale":"Minor Pentatonic"}}"#, day - 600),
+            // Cut off mid-write
+            r#"{"timestamp":17280"#.to_string(),
+            String::new(),
+            format!(r#"{{"timestamp":{},"duration_secs":900,"key":"C","scale":"Major"}}"#, day + 100),
+        ].join("\n");
+        let entries = parse_log(&text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, "A");
+        assert_eq!(entries[1].duration_secs, 900);
+
+        // Only the session that ended today counts
+        let now = UNIX_EPOCH + Duration::from_secs(day + 3600);
+        assert_eq!(total_today(&entries, now), Duration::from_secs(900));
+    }
+
+    #[test]
+    fn test_log_round_trip_and_missing_file() {
+        let dir = std::env::temp_dir().join(format!("gpd-session-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sessions.jsonl");
+        assert!(read_log(&path).is_empty());
+
+        let now = SystemTime::now();
+        append_entry(&path, &SessionEntry::new(now, Duration::from_secs(300), "E", "Major")).unwrap();
+        append_entry(&path, &SessionEntry::new(now, Duration::from_secs(120), "G", "Dorian")).unwrap();
+        let entries = read_log(&path);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].scale, "Dorian");
+        assert_eq!(total_today(&entries, now), Duration::from_secs(420));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(59)), "0m");
+        assert_eq!(format_duration(Duration::from_secs(12 * 60 + 30)), "12m");
+        assert_eq!(format_duration(Duration::from_secs(65 * 60)), "1h 05m");
+    }
+}
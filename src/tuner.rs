@@ -0,0 +1,294 @@
+// Tuner: listens on the default input device, detects the pitch being played
+// and reports the nearest note with how many cents sharp or flat it is
+
+use crate::music_theory::{CentsOffsets, Key, Note};
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample};
+
+// Range of pitches worth detecting: below drop-tuned low strings, above the
+// highest fret of the high E string
+const MIN_FREQUENCY: f32 = 60.0;
+const MAX_FREQUENCY: f32 = 1400.0;
+
+// Fewest samples analysed per reading (~46ms at 44.1kHz)
+const MIN_WINDOW_SIZE: usize = 2048;
+
+// Quieter than this (RMS) counts as silence
+const SILENCE_RMS: f32 = 0.01;
+// How periodic the signal must be (normalized autocorrelation) to trust a reading
+const MIN_CLARITY: f32 = 0.8;
+
+// Nearest note to the frequency, and the deviation from it in cents (positive =
+// sharp), with each note at its microtonal offset; the inverse of
+// calculate_frequency_with_offsets
+pub fn nearest_note(frequency: f32, reference_a4: f32, offsets: &CentsOffsets) -> Option<(Note, f32)> {
+    if !(frequency > 0.0 && frequency.is_finite() && reference_a4 > 0.0) {
+        return None;
+    }
+    let semitones_from_a4 = 12.0 * (frequency / reference_a4).log2();
+    let nearest = semitones_from_a4.round() as i32;
+    // An offset can move a note closer than its equal-tempered neighbour, so
+    // weigh up the notes either side too
+    (nearest - 1..=nearest + 1)
+        .map(|semitones| {
+            let note = Note::new(Key::A, 4).transpose(semitones);
+            let cents = (semitones_from_a4 - semitones as f32) * 100.0 - offsets.get(note.note);
+            (note, cents)
+        })
+        .min_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+}
+
+// Fundamental frequency of a mono signal by normalized autocorrelation, or None
+// for silence and noise
+pub fn detect_pitch(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    if rms < SILENCE_RMS {
+        return None;
+    }
+
+    let min_lag = (sample_rate as f32 / MAX_FREQUENCY) as usize;
+    let max_lag = ((sample_rate as f32 / MIN_FREQUENCY) as usize).min(samples.len() / 2);
+    if min_lag < 1 || min_lag + 2 > max_lag {
+        return None;
+    }
+    let correlation = |lag: usize| {
+        let (head, tail) = (&samples[..samples.len() - lag], &samples[lag..]);
+        let dot: f32 = head.iter().zip(tail).map(|(a, b)| a * b).sum();
+        let energy: f32 = head.iter().map(|a| a * a).sum::<f32>() * tail.iter().map(|b| b * b).sum::<f32>();
+        if energy > 0.0 { dot / energy.sqrt() } else { 0.0 }
+    };
+    // One extra lag either side so every candidate has neighbours to compare against
+    let scores: Vec<f32> = (min_lag - 1..=max_lag + 1).map(correlation).collect();
+    let best = scores.iter().cloned().fold(f32::MIN, f32::max);
+    if best < MIN_CLARITY {
+        return None;
+    }
+
+    // Multiples of the period correlate almost as well as the period itself;
+    // taking the first peak close to the best avoids reading an octave low
+    let peak = (1..scores.len() - 1).find(|&i| {
+        scores[i] >= best * 0.9 && scores[i] >= scores[i - 1] && scores[i] >= scores[i + 1]
+    })?;
+
+    // Fit a parabola through the peak and its neighbours for a sub-sample period
+    let (left, centre, right) = (scores[peak - 1], scores[peak], scores[peak + 1]);
+    let curvature = left - 2.0 * centre + right;
+    let shift = if curvature != 0.0 { 0.5 * (left - right) / curvature } else { 0.0 };
+    let period = (min_lag - 1 + peak) as f32 + shift;
+    Some(sample_rate as f32 / period)
+}
+
+// Samples analysed per reading at the given rate: enough for two periods of the
+// lowest pitch detected, in a power of two (2048 at 44.1/48kHz, 4096 at 96kHz).
+// The window slides half its length between readings.
+fn window_size(sample_rate: u32) -> usize {
+    let two_periods = (2.0 * sample_rate as f32 / MIN_FREQUENCY).ceil() as usize;
+    two_periods.next_power_of_two().max(MIN_WINDOW_SIZE)
+}
+
+// Buffers incoming audio and produces a reading each time a full window is available
+pub struct PitchTracker {
+    sample_rate: u32,
+    channels: usize,
+    reference_a4: f32,
+    offsets: CentsOffsets,
+    window: Vec<f32>,
+    window_size: usize,
+}
+
+impl PitchTracker {
+    pub fn new(sample_rate: u32, channels: u16, reference_a4: f32, offsets: CentsOffsets) -> Self {
+        PitchTracker {
+            sample_rate,
+            channels: channels.max(1) as usize,
+            reference_a4,
+            offsets,
+            window: Vec::with_capacity(window_size(sample_rate)),
+            window_size: window_size(sample_rate),
+        }
+    }
+
+    // Feed interleaved samples (channels are averaged to mono); returns the latest
+    // note and cents deviation if a window completed and held a clear pitch
+    pub fn push(&mut self, interleaved: &[f32]) -> Option<(Note, f32)> {
+        let mut reading = None;
+        for frame in interleaved.chunks(self.channels) {
+            self.window.push(frame.iter().sum::<f32>() / frame.len() as f32);
+            if self.window.len() == self.window_size {
+                if let Some(frequency) = detect_pitch(&self.window, self.sample_rate) {
+                    reading = nearest_note(frequency, self.reference_a4, &self.offsets).or(reading);
+                }
+                self.window.drain(..self.window_size / 2);
+            }
+        }
+        reading
+    }
+}
+
+// Microphone capture; the stream runs until stop() or until the tuner is dropped
+#[derive(Default)]
+pub struct Tuner {
+    stream: Option<cpal::Stream>,
+}
+
+impl Tuner {
+    pub fn new() -> Self {
+        Tuner::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // Start listening; on_reading is called from the audio input thread with the
+    // nearest note and its deviation in cents
+    pub fn start<F>(&mut self, reference_a4: f32, offsets: CentsOffsets, on_reading: F) -> Result<()>
+    where
+        F: FnMut(Note, f32) + Send + 'static,
+    {
+        self.stop();
+
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No audio input device"))?;
+        let supported = device.default_input_config()
+            .map_err(|e| anyhow::anyhow!("Failed to query input device: {}", e))?;
+        let config: cpal::StreamConfig = supported.clone().into();
+        let tracker = PitchTracker::new(config.sample_rate.0, config.channels, reference_a4, offsets);
+
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build_input_stream::<f32, F>(&device, &config, tracker, on_reading)?,
+            cpal::SampleFormat::I16 => build_input_stream::<i16, F>(&device, &config, tracker, on_reading)?,
+            cpal::SampleFormat::U16 => build_input_stream::<u16, F>(&device, &config, tracker, on_reading)?,
+            format => anyhow::bail!("Unsupported input sample format: {}", format),
+        };
+        stream.play().map_err(|e| anyhow::anyhow!("Failed to start input stream: {}", e))?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        // Dropping the stream closes the input device
+        self.stream = None;
+    }
+}
+
+fn build_input_stream<T, F>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut tracker: PitchTracker,
+    mut on_reading: F,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+    F: FnMut(Note, f32) + Send + 'static,
+{
+    let mut samples: Vec<f32> = Vec::new();
+    let stream = device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                samples.clear();
+                samples.extend(data.iter().map(|&s| f32::from_sample(s)));
+                if let Some((note, cents)) = tracker.push(&samples) {
+                    on_reading(note, cents);
+                }
+            },
+            |e| eprintln!("[tuner] Input stream error: {}", e),
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to open input stream: {}", e))?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music_theory::{self, DEFAULT_REFERENCE_A4};
+
+    fn sine(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_nearest_note_and_cents() {
+        let tet = CentsOffsets::default();
+        let (note, cents) = nearest_note(445.0, DEFAULT_REFERENCE_A4, &tet).unwrap();
+        assert_eq!(note, Note::new(Key::A, 4));
+        assert!((cents - 19.56).abs() < 0.05, "{}", cents);
+
+        let (note, cents) = nearest_note(435.0, DEFAULT_REFERENCE_A4, &tet).unwrap();
+        assert_eq!(note, Note::new(Key::A, 4));
+        assert!((cents + 19.79).abs() < 0.05, "{}", cents);
+
+        // Exact pitches read as in tune, across octaves
+        let low_e = Note::new(Key::E, 2);
+        let (note, cents) = nearest_note(music_theory::calculate_frequency(low_e), DEFAULT_REFERENCE_A4, &tet).unwrap();
+        assert_eq!(note, low_e);
+        assert!(cents.abs() < 0.01);
+
+        // The reference pitch moves what counts as in tune
+        let (note, cents) = nearest_note(442.0, 442.0, &tet).unwrap();
+        assert_eq!(note, Note::new(Key::A, 4));
+        assert!(cents.abs() < 0.01);
+
+        assert_eq!(nearest_note(0.0, DEFAULT_REFERENCE_A4, &tet), None);
+        assert_eq!(nearest_note(f32::NAN, DEFAULT_REFERENCE_A4, &tet), None);
+    }
+
+    #[test]
+    fn test_nearest_note_with_offsets() {
+        // E a quarter tone flat: its own pitch reads in tune
+        let mut offsets = CentsOffsets::default();
+        offsets.set(Key::E, -50.0);
+        let e4 = Note::new(Key::E, 4);
+        let frequency = music_theory::calculate_frequency_with_offsets(e4, &offsets, DEFAULT_REFERENCE_A4);
+        let (note, cents) = nearest_note(frequency, DEFAULT_REFERENCE_A4, &offsets).unwrap();
+        assert_eq!(note, e4);
+        assert!(cents.abs() < 0.01, "{}", cents);
+
+        // Equal-tempered E now reads sharp of it, and other notes are unaffected
+        let (note, cents) = nearest_note(music_theory::calculate_frequency(e4), DEFAULT_REFERENCE_A4, &offsets).unwrap();
+        assert_eq!(note, e4);
+        assert!((cents - 50.0).abs() < 0.01, "{}", cents);
+        let (note, cents) = nearest_note(440.0, DEFAULT_REFERENCE_A4, &offsets).unwrap();
+        assert_eq!(note, Note::new(Key::A, 4));
+        assert!(cents.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_detect_pitch_of_synthetic_tones() {
+        for frequency in [82.41, 110.0, 196.0, 440.0, 659.26] {
+            let detected = detect_pitch(&sine(frequency, 44100, window_size(44100)), 44100).unwrap();
+            assert!((detected - frequency).abs() / frequency < 0.002, "{} read as {}", frequency, detected);
+        }
+        // USB interfaces often run at 96kHz, where a low E period is ~1165 samples
+        assert_eq!(window_size(96000), 4096);
+        for frequency in [73.42, 82.41, 659.26] {
+            let detected = detect_pitch(&sine(frequency, 96000, window_size(96000)), 96000).unwrap();
+            assert!((detected - frequency).abs() / frequency < 0.002, "{} read as {} at 96kHz", frequency, detected);
+        }
+        assert_eq!(detect_pitch(&[0.0; MIN_WINDOW_SIZE], 44100), None);
+        assert_eq!(detect_pitch(&[], 44100), None);
+    }
+
+    #[test]
+    fn test_tracker_reads_stereo_input() {
+        let mut tracker = PitchTracker::new(48000, 2, DEFAULT_REFERENCE_A4, CentsOffsets::default());
+        let window = window_size(48000);
+        let interleaved: Vec<f32> = sine(445.0, 48000, window).iter().flat_map(|&s| [s, s]).collect();
+
+        // Nothing until a full window has arrived
+        assert_eq!(tracker.push(&interleaved[..window]), None);
+        let (note, cents) = tracker.push(&interleaved[window..]).unwrap();
+        assert_eq!(note, Note::new(Key::A, 4));
+        assert!((cents - 19.56).abs() < 1.0, "{}", cents);
+    }
+}